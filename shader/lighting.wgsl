@@ -0,0 +1,181 @@
+// Punctual lights, cascaded shadows and metallic-roughness shading shared by the lit
+// shaders, prepended through `includes` in graph.toml. `shadow_factor` expects the shader
+// to bind `camera`.
+
+// Keep in sync with `engine::light::Light::as_raw`
+struct Light {
+    position: vec3f,
+    // 0 when unbounded
+    range: f32,
+    direction: vec3f,
+    kind: u32,
+    color: vec3f,
+    intensity: f32,
+    inner_cone_cos: f32,
+    outer_cone_cos: f32,
+    // first layer in `shadow_maps`, -1 without shadows
+    shadow_layer: i32,
+    padding: f32,
+};
+
+struct Lights {
+    ambient: vec4f,
+    count: u32,
+    lights: array<Light>,
+};
+
+const LIGHT_DIRECTIONAL: u32 = 0u;
+const LIGHT_POINT: u32 = 1u;
+const LIGHT_SPOT: u32 = 2u;
+
+const PI: f32 = 3.14159265359;
+
+@group(3) @binding(0)
+var<storage, read> lights: Lights;
+
+// Keep in sync with `engine::shadow::ShadowMaps::update`
+struct Shadows {
+    // camera forward, picks the cascade by view depth
+    view_dir: vec4f,
+    // far distance of every cascade
+    splits: vec4f,
+    cascades: u32,
+    matrices: array<mat4x4f>,
+};
+
+// world units, scaled by how much the surface turns away from the light
+const SHADOW_NORMAL_OFFSET: f32 = 0.05;
+
+@group(3) @binding(1)
+var shadow_maps: texture_depth_2d_array;
+@group(3) @binding(2)
+var shadow_sampler: sampler_comparison;
+@group(3) @binding(3)
+var<storage, read> shadows: Shadows;
+
+// Fraction of the light reaching `world_pos`, 3x3 PCF in the shadow map of the light
+fn shadow_factor(light: Light, world_pos: vec3f, norm: vec3f, light_dir: vec3f) -> f32 {
+    if light.shadow_layer < 0 {
+        return 1.0;
+    }
+
+    var layer = u32(light.shadow_layer);
+    if light.kind == LIGHT_DIRECTIONAL {
+        let depth = dot(world_pos - camera.view_pos.xyz, shadows.view_dir.xyz);
+        if depth > shadows.splits[shadows.cascades - 1u] {
+            return 1.0;
+        }
+        var cascade = 0u;
+        while cascade + 1u < shadows.cascades && depth > shadows.splits[cascade] {
+            cascade++;
+        }
+        layer += cascade;
+    }
+
+    let offset = norm * SHADOW_NORMAL_OFFSET * (1.0 - max(dot(norm, light_dir), 0.0));
+    let clip = shadows.matrices[layer] * vec4f(world_pos + offset, 1.0);
+    if clip.w <= 0.0 {
+        return 1.0;
+    }
+    let ndc = clip.xyz / clip.w;
+    if any(abs(ndc.xy) > vec2f(1.0)) || ndc.z > 1.0 {
+        return 1.0;
+    }
+
+    let uv = ndc.xy * vec2f(0.5, -0.5) + vec2f(0.5);
+    let texel = 1.0 / vec2f(textureDimensions(shadow_maps));
+    var lit = 0.0;
+    for (var y = -1; y <= 1; y++) {
+        for (var x = -1; x <= 1; x++) {
+            let sample_uv = uv + vec2f(f32(x), f32(y)) * texel;
+            lit += textureSampleCompareLevel(shadow_maps, shadow_sampler, sample_uv, layer, ndc.z);
+        }
+    }
+    return lit / 9.0;
+}
+
+// Trowbridge-Reitz GGX normal distribution
+fn distribution_ggx(n_dot_h: f32, alpha: f32) -> f32 {
+    let alpha2 = alpha * alpha;
+    let d = n_dot_h * n_dot_h * (alpha2 - 1.0) + 1.0;
+    return alpha2 / (PI * d * d);
+}
+
+// Height correlated Smith visibility, includes the 1 / (4 n.l n.v) of Cook-Torrance
+fn visibility_smith_ggx(n_dot_v: f32, n_dot_l: f32, alpha: f32) -> f32 {
+    let alpha2 = alpha * alpha;
+    let ggx_v = n_dot_l * sqrt(n_dot_v * n_dot_v * (1.0 - alpha2) + alpha2);
+    let ggx_l = n_dot_v * sqrt(n_dot_l * n_dot_l * (1.0 - alpha2) + alpha2);
+    return 0.5 / max(ggx_v + ggx_l, 1e-5);
+}
+
+fn fresnel_schlick(v_dot_h: f32, f0: vec3f) -> vec3f {
+    return f0 + (vec3f(1.0) - f0) * pow(1.0 - v_dot_h, 5.0);
+}
+
+// KHR_lights_punctual falloff
+fn range_attenuation(range: f32, distance: f32) -> f32 {
+    let inverse_square = 1.0 / max(distance * distance, 1e-4);
+    if range <= 0.0 {
+        return inverse_square;
+    }
+    let falloff = clamp(1.0 - pow(distance / range, 4.0), 0.0, 1.0);
+    return falloff * falloff * inverse_square;
+}
+
+fn spot_attenuation(light: Light, light_dir: vec3f) -> f32 {
+    let cos_angle = dot(light.direction, -light_dir);
+    let scale = 1.0 / max(light.inner_cone_cos - light.outer_cone_cos, 1e-4);
+    let t = clamp((cos_angle - light.outer_cone_cos) * scale, 0.0, 1.0);
+    return t * t;
+}
+
+// Direct light of every punctual light on a metallic-roughness surface
+fn shade(
+    norm: vec3f,
+    view_dir: vec3f,
+    world_pos: vec3f,
+    albedo: vec3f,
+    metallic: f32,
+    roughness: f32,
+) -> vec3f {
+    let perceptual_roughness = clamp(roughness, 0.045, 1.0);
+    let alpha = perceptual_roughness * perceptual_roughness;
+    let f0 = mix(vec3f(0.04), albedo, metallic);
+    let diffuse_color = albedo * (1.0 - metallic);
+    let n_dot_v = max(dot(norm, view_dir), 1e-4);
+
+    var color = vec3f(0.0);
+    for (var idx = 0u; idx < lights.count; idx++) {
+        let light = lights.lights[idx];
+
+        var light_dir = -light.direction;
+        var radiance = light.color * light.intensity;
+        if light.kind != LIGHT_DIRECTIONAL {
+            let to_light = light.position - world_pos;
+            let distance = length(to_light);
+            light_dir = to_light / max(distance, 1e-4);
+            radiance *= range_attenuation(light.range, distance);
+            if light.kind == LIGHT_SPOT {
+                radiance *= spot_attenuation(light, light_dir);
+            }
+        }
+
+        let n_dot_l = dot(norm, light_dir);
+        if n_dot_l <= 0.0 {
+            continue;
+        }
+        let shadow = shadow_factor(light, world_pos, norm, light_dir);
+        let half_dir = normalize(light_dir + view_dir);
+        let n_dot_h = max(dot(norm, half_dir), 0.0);
+        let v_dot_h = max(dot(view_dir, half_dir), 0.0);
+
+        let fresnel = fresnel_schlick(v_dot_h, f0);
+        let specular = fresnel * distribution_ggx(n_dot_h, alpha)
+            * visibility_smith_ggx(n_dot_v, n_dot_l, alpha);
+        let diffuse = (vec3f(1.0) - fresnel) * diffuse_color / PI;
+
+        color += (diffuse + specular) * radiance * n_dot_l * shadow;
+    }
+    return color;
+}
@@ -0,0 +1,120 @@
+struct VertexInput {
+    @location(0) position: vec3f,
+    @location(1) tex_coords: vec2f,
+    @location(2) norm: vec3f,
+};
+
+struct InstanceInput {
+    @location(5) model_matrix_0: vec4f,
+    @location(6) model_matrix_1: vec4f,
+    @location(7) model_matrix_2: vec4f,
+    @location(8) model_matrix_3: vec4f,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4f,
+    @location(0) tex_coords: vec2f,
+    @location(1) norm: vec3f,
+    @location(2) world_pos: vec3f,
+};
+
+struct CameraUniform {
+    view_pos: vec4f,
+    view_proj: mat4x4f,
+};
+
+@group(1) @binding(0)
+var<uniform> camera: CameraUniform;
+
+@group(2) @binding(0)
+var<uniform> transform: mat4x4f;
+
+@vertex
+fn vs_main(
+    model: VertexInput,
+    instance: InstanceInput,
+) -> VertexOutput {
+    let model_matrix = mat4x4f(
+        instance.model_matrix_0,
+        instance.model_matrix_1,
+        instance.model_matrix_2,
+        instance.model_matrix_3,
+    );
+
+    var out: VertexOutput;
+    out.tex_coords = model.tex_coords;
+    let world_pos = model_matrix * transform * vec4f(model.position, 1.0);
+    out.clip_position = camera.view_proj * world_pos;
+    out.norm = (model_matrix * transform * vec4f(model.norm, 0.0)).xyz;
+    out.world_pos = world_pos.xyz;
+    return out;
+}
+
+
+// Keep in sync with `engine::material::MaterialUniform`
+struct Material {
+    base_color: vec4f,
+    emissive: vec3f,
+    metallic: f32,
+    roughness: f32,
+}
+
+@group(0) @binding(0)
+var<uniform> material: Material;
+@group(0) @binding(3)
+var base_color_texture: texture_2d<f32>;
+@group(0) @binding(4)
+var base_color_sampler: sampler;
+@group(0) @binding(5)
+var metallic_roughness_texture: texture_2d<f32>;
+@group(0) @binding(6)
+var metallic_roughness_sampler: sampler;
+@group(0) @binding(7)
+var normal_texture: texture_2d<f32>;
+@group(0) @binding(8)
+var normal_sampler: sampler;
+@group(0) @binding(9)
+var occlusion_texture: texture_2d<f32>;
+@group(0) @binding(10)
+var occlusion_sampler: sampler;
+@group(0) @binding(12)
+var emissive_texture: texture_2d<f32>;
+@group(0) @binding(13)
+var emissive_sampler: sampler;
+
+// Tangent frame from screen space derivatives, meshes carry no tangents
+fn perturb_normal(norm: vec3f, world_pos: vec3f, uv: vec2f, tangent_normal: vec3f) -> vec3f {
+    let dp1 = dpdx(world_pos);
+    let dp2 = dpdy(world_pos);
+    let duv1 = dpdx(uv);
+    let duv2 = dpdy(uv);
+
+    let dp2_perp = cross(dp2, norm);
+    let dp1_perp = cross(norm, dp1);
+    let tangent = dp2_perp * duv1.x + dp1_perp * duv2.x;
+    let bitangent = dp2_perp * duv1.y + dp1_perp * duv2.y;
+    let scale = inverseSqrt(max(max(dot(tangent, tangent), dot(bitangent, bitangent)), 1e-12));
+
+    let tbn = mat3x3f(tangent * scale, bitangent * scale, norm);
+    return normalize(tbn * (tangent_normal * 2.0 - 1.0));
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4f {
+    // sampled up front, derivatives need uniform control flow
+    let base = textureSample(base_color_texture, base_color_sampler, in.tex_coords) * material.base_color;
+    let metallic_roughness = textureSample(metallic_roughness_texture, metallic_roughness_sampler, in.tex_coords);
+    let tangent_normal = textureSample(normal_texture, normal_sampler, in.tex_coords).xyz;
+    let occlusion = textureSample(occlusion_texture, occlusion_sampler, in.tex_coords).r;
+    let emission = textureSample(emissive_texture, emissive_sampler, in.tex_coords).rgb * material.emissive;
+    let norm = perturb_normal(normalize(in.norm), in.world_pos, in.tex_coords, tangent_normal);
+
+    // glTF packs roughness in green and metalness in blue
+    let surface_roughness = metallic_roughness.g * material.roughness;
+    let surface_metallic = metallic_roughness.b * material.metallic;
+    let view_dir = normalize(camera.view_pos.xyz - in.world_pos);
+
+    let direct = shade(norm, view_dir, in.world_pos, base.rgb, surface_metallic, surface_roughness);
+    let ambient = lights.ambient.rgb * base.rgb * occlusion;
+    return vec4f(direct + ambient + emission, base.a);
+}
@@ -15,7 +15,7 @@ struct VertexOutput {
     @builtin(position) clip_position: vec4f,
     @location(0) tex_coords: vec2f,
     @location(1) norm: vec3f,
-    @location(2) view_pos: vec3f,
+    @location(2) world_pos: vec3f,
 };
 
 struct CameraUniform {
@@ -43,39 +43,32 @@ fn vs_main(
 
     var out: VertexOutput;
     out.tex_coords = model.tex_coords;
-    out.clip_position = camera.view_proj * model_matrix * transform * vec4f(model.position, 1.0);
-    out.norm = model.norm;
-    out.view_pos = camera.view_pos.xyz;
+    let world_pos = model_matrix * transform * vec4f(model.position, 1.0);
+    out.clip_position = camera.view_proj * world_pos;
+    out.norm = (model_matrix * transform * vec4f(model.norm, 0.0)).xyz;
+    out.world_pos = world_pos.xyz;
     return out;
 }
 
 
+// Keep in sync with `engine::material::MaterialUniform`
+struct Material {
+    base_color: vec4f,
+    emissive: vec3f,
+    metallic: f32,
+    roughness: f32,
+}
+
 @group(0) @binding(0)
-var<uniform> base_color: vec4f;
-@group(0) @binding(1)
-var<uniform> metallic: f32;
-@group(0) @binding(2)
-var<uniform> roughness: f32;
-@group(0) @binding(11)
-var<uniform> emissive: f32;
+var<uniform> material: Material;
 
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4f {
-    var norm: vec3f = normalize(in.norm);
-    var viewDir: vec3f = normalize(in.view_pos - in.clip_position.xyz);  // FragPos -> viewPos
-    var lightDir: vec3f = normalize(in.view_pos);  // FragPos -> lightPos
-    var reflectDir: vec3f = reflect(lightDir, norm);
-
-    var lightWeight: f32 = max(dot(norm, lightDir), max(emissive, 0.1));
-    var specWeight: f32 = 1.5 * pow(max(dot(viewDir, reflectDir), 0.0), roughness);
-
-
-    // calculate
-    var cal_diffuse: vec3f = lightWeight * base_color.xyz;// * vec3(texture(materialDiffuse, TexCoord));
-    // var cal_specular: f32 = specWeight * metallic;// * vec3(texture(materialSpecular, TexCoord));
-
-    var cal_val: vec3f = cal_diffuse;
-    return vec4f(cal_val, 1.0);
+    let norm = normalize(in.norm);
+    let view_dir = normalize(camera.view_pos.xyz - in.world_pos);
 
-    // return base_color;
+    let base_color = material.base_color;
+    let direct = shade(norm, view_dir, in.world_pos, base_color.rgb, material.metallic, material.roughness);
+    let ambient = lights.ambient.rgb * base_color.rgb;
+    return vec4f(direct + ambient + material.emissive, base_color.a);
 }
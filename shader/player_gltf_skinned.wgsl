@@ -0,0 +1,86 @@
+struct VertexInput {
+    @location(0) position: vec3f,
+    @location(1) tex_coords: vec2f,
+    @location(2) norm: vec3f,
+    @location(3) joints: vec4u,
+    @location(4) weights: vec4f,
+};
+
+struct InstanceInput {
+    @location(5) model_matrix_0: vec4f,
+    @location(6) model_matrix_1: vec4f,
+    @location(7) model_matrix_2: vec4f,
+    @location(8) model_matrix_3: vec4f,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4f,
+    @location(0) tex_coords: vec2f,
+    @location(1) norm: vec3f,
+    @location(2) world_pos: vec3f,
+};
+
+struct CameraUniform {
+    view_pos: vec4f,
+    view_proj: mat4x4f,
+};
+
+// Keep in sync with `Skin::MAX_JOINTS`
+const MAX_JOINTS: u32 = 64u;
+
+@group(1) @binding(0)
+var<uniform> camera: CameraUniform;
+
+@group(2) @binding(0)
+var<uniform> joint_matrices: array<mat4x4f, MAX_JOINTS>;
+
+@vertex
+fn vs_main(
+    model: VertexInput,
+    instance: InstanceInput,
+) -> VertexOutput {
+    let model_matrix = mat4x4f(
+        instance.model_matrix_0,
+        instance.model_matrix_1,
+        instance.model_matrix_2,
+        instance.model_matrix_3,
+    );
+
+    let skin_matrix = model_matrix * (
+        model.weights.x * joint_matrices[model.joints.x] +
+        model.weights.y * joint_matrices[model.joints.y] +
+        model.weights.z * joint_matrices[model.joints.z] +
+        model.weights.w * joint_matrices[model.joints.w]
+    );
+
+    var out: VertexOutput;
+    out.tex_coords = model.tex_coords;
+    let world_pos = skin_matrix * vec4f(model.position, 1.0);
+    out.clip_position = camera.view_proj * world_pos;
+    out.norm = normalize((skin_matrix * vec4f(model.norm, 0.0)).xyz);
+    out.world_pos = world_pos.xyz;
+    return out;
+}
+
+
+// Keep in sync with `engine::material::MaterialUniform`
+struct Material {
+    base_color: vec4f,
+    emissive: vec3f,
+    metallic: f32,
+    roughness: f32,
+}
+
+@group(0) @binding(0)
+var<uniform> material: Material;
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4f {
+    let norm = normalize(in.norm);
+    let view_dir = normalize(camera.view_pos.xyz - in.world_pos);
+
+    let base_color = material.base_color;
+    let direct = shade(norm, view_dir, in.world_pos, base_color.rgb, material.metallic, material.roughness);
+    let ambient = lights.ambient.rgb * base_color.rgb;
+    return vec4f(direct + ambient + material.emissive, base_color.a);
+}
@@ -0,0 +1,37 @@
+struct VertexInput {
+    @location(0) position: vec3f,
+};
+
+struct InstanceInput {
+    @location(5) model_matrix_0: vec4f,
+    @location(6) model_matrix_1: vec4f,
+    @location(7) model_matrix_2: vec4f,
+    @location(8) model_matrix_3: vec4f,
+}
+
+// light camera of one shadow map layer
+struct CameraUniform {
+    view_pos: vec4f,
+    view_proj: mat4x4f,
+};
+
+@group(1) @binding(0)
+var<uniform> camera: CameraUniform;
+
+@group(2) @binding(0)
+var<uniform> transform: mat4x4f;
+
+@vertex
+fn vs_main(
+    model: VertexInput,
+    instance: InstanceInput,
+) -> @builtin(position) vec4f {
+    let model_matrix = mat4x4f(
+        instance.model_matrix_0,
+        instance.model_matrix_1,
+        instance.model_matrix_2,
+        instance.model_matrix_3,
+    );
+
+    return camera.view_proj * model_matrix * transform * vec4f(model.position, 1.0);
+}
@@ -0,0 +1,49 @@
+struct VertexInput {
+    @location(0) position: vec3f,
+    @location(3) joints: vec4u,
+    @location(4) weights: vec4f,
+};
+
+struct InstanceInput {
+    @location(5) model_matrix_0: vec4f,
+    @location(6) model_matrix_1: vec4f,
+    @location(7) model_matrix_2: vec4f,
+    @location(8) model_matrix_3: vec4f,
+}
+
+// light camera of one shadow map layer
+struct CameraUniform {
+    view_pos: vec4f,
+    view_proj: mat4x4f,
+};
+
+// Keep in sync with `Skin::MAX_JOINTS`
+const MAX_JOINTS: u32 = 64u;
+
+@group(1) @binding(0)
+var<uniform> camera: CameraUniform;
+
+@group(2) @binding(0)
+var<uniform> joint_matrices: array<mat4x4f, MAX_JOINTS>;
+
+@vertex
+fn vs_main(
+    model: VertexInput,
+    instance: InstanceInput,
+) -> @builtin(position) vec4f {
+    let model_matrix = mat4x4f(
+        instance.model_matrix_0,
+        instance.model_matrix_1,
+        instance.model_matrix_2,
+        instance.model_matrix_3,
+    );
+
+    let skin_matrix = model_matrix * (
+        model.weights.x * joint_matrices[model.joints.x] +
+        model.weights.y * joint_matrices[model.joints.y] +
+        model.weights.z * joint_matrices[model.joints.z] +
+        model.weights.w * joint_matrices[model.joints.w]
+    );
+
+    return camera.view_proj * skin_matrix * vec4f(model.position, 1.0);
+}
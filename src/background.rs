@@ -1,147 +1,127 @@
-use std::sync::Arc;
-
-use async_trait::async_trait;
-use cgmath::Rotation3;
-use tokio::sync::Mutex;
-use wgpu::util::DeviceExt;
+use anyhow::anyhow;
+use gltf::texture::WrappingMode;
 
 use crate::engine::{
-    WgpuApp,
-    instance::Instance,
-    model::{Mesh, Model, ModelVertex, ObjMaterial},
-    module::WgpuAppModule,
-    register_model_instances,
-    resource::load_texture,
+    material::{Material, MaterialUniform},
+    plugin::{Plugin, PluginContext},
+    resources::{self, load_binary},
+    scene::ModelId,
+    texture::{ColorSpace, Texture},
 };
 
 const BACKGOUND_IMGAE_PATH: &str = "grassland.jpg";
 const BG_SIZE: f32 = 100.0;
 const BG_LAYZER: f32 = 2.0;
 const BG_X_SCALE: f32 = 10.0;
-const VERTICES: &[ModelVertex] = &[
-    ModelVertex {
-        position: [BG_SIZE * BG_X_SCALE, BG_LAYZER, BG_SIZE],
-        tex_coord: [2.0 * BG_X_SCALE, -2.0],
-        normal: [0.0, 0.0, 0.0],
-    },
-    ModelVertex {
-        position: [-BG_SIZE * BG_X_SCALE, BG_LAYZER, BG_SIZE],
-        tex_coord: [-2.0 * BG_X_SCALE, -2.0],
-        normal: [0.0, 0.0, 0.0],
-    },
-    ModelVertex {
-        position: [BG_SIZE * BG_X_SCALE, BG_LAYZER, -BG_SIZE],
-        tex_coord: [2.0 * BG_X_SCALE, 2.0],
-        normal: [0.0, 0.0, 0.0],
-    },
-    ModelVertex {
-        position: [-BG_SIZE * BG_X_SCALE, BG_LAYZER, -BG_SIZE],
-        tex_coord: [-2.0 * BG_X_SCALE, 2.0],
-        normal: [0.0, 0.0, 0.0],
-    },
-];
 
-// const INDICES: &[u32] = &[0, 1, 2, 2, 1, 3, 3, 1, 2, 2, 1, 0];
-const INDICES: &[u32] = &[3, 1, 2, 2, 1, 0];
+fn background_resource() -> resources::Resource {
+    let primitive = resources::Primitive {
+        positions: vec![
+            [BG_SIZE * BG_X_SCALE, BG_LAYZER, BG_SIZE],
+            [-BG_SIZE * BG_X_SCALE, BG_LAYZER, BG_SIZE],
+            [BG_SIZE * BG_X_SCALE, BG_LAYZER, -BG_SIZE],
+            [-BG_SIZE * BG_X_SCALE, BG_LAYZER, -BG_SIZE],
+        ],
+        tex_coords: vec![
+            [2.0 * BG_X_SCALE, -2.0],
+            [-2.0 * BG_X_SCALE, -2.0],
+            [2.0 * BG_X_SCALE, 2.0],
+            [-2.0 * BG_X_SCALE, 2.0],
+        ],
+        normals: vec![[0.0, 1.0, 0.0]; 4],
+        joints: None,
+        weights: None,
+        indices: vec![3, 1, 2, 2, 1, 0],
+        material: 0,
+    };
 
-async fn load_background(app: Arc<Mutex<WgpuApp>>) {
-    let app = app.lock().await;
-    let vertices_data = unsafe { VERTICES.align_to::<u8>().1 };
-    let vertex_buffer =
-        app.app_surface
-            .device
-            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                label: Some("Background vertex buffer"),
-                contents: vertices_data,
-                usage: wgpu::BufferUsages::VERTEX,
-            });
-    // let vertex_buffer_layout = ImageVertex::desc();
+    resources::Resource::from_meshes(
+        "Backgound",
+        vec![resources::Mesh {
+            name: Some("Backgound".to_string()),
+            primitives: vec![primitive],
+        }],
+    )
+}
 
-    let indices_data = unsafe { INDICES.align_to::<u8>().1 };
-    let index_buffer =
-        app.app_surface
-            .device
-            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                label: Some("Background indices buffer"),
-                contents: indices_data,
-                usage: wgpu::BufferUsages::INDEX,
-            });
+pub struct BackgroundModule {
+    model: Option<ModelId>,
+}
 
-    let background_texture = load_texture(
-        &app.app_surface.device,
-        &app.app_surface.queue,
-        BACKGOUND_IMGAE_PATH,
-    )
-    .await
-    .unwrap();
-    let background_texture_bind_group_layout = app
-        .graph_resource
-        .bind_group_info
-        .get("bg_texture")
-        .unwrap();
-    let background_texture_bind_group =
-        app.app_surface
-            .device
-            .create_bind_group(&wgpu::BindGroupDescriptor {
-                label: Some("Backgound bind group"),
-                layout: background_texture_bind_group_layout,
-                entries: &[
-                    wgpu::BindGroupEntry {
-                        binding: 8,
-                        resource: wgpu::BindingResource::TextureView(&background_texture.view),
-                    },
-                    wgpu::BindGroupEntry {
-                        binding: 9,
-                        resource: wgpu::BindingResource::Sampler(&background_texture.sampler),
-                    },
-                ],
-            });
+impl BackgroundModule {
+    pub fn new() -> Self {
+        Self { model: None }
+    }
+}
 
-    // pack as model
-    let model = Model {
-        meshes: vec![Mesh {
-            name: "Backgound".to_string(),
-            vertex: VERTICES.to_vec(),
-            vertex_buffer,
-            index_buffer,
-            num_elements: INDICES.len() as u32,
-            material: 0,
-            animation: None,
-            uniform_transform: None,
-        }],
-        materials: vec![Arc::new(std::sync::Mutex::new(ObjMaterial {
-            name: "Backgound".to_string(),
-            diffuse_texture: Some(background_texture),
-            bind_group: Some(background_texture_bind_group),
-            ..Default::default()
-        })) as Arc<_>],
-    };
+impl Plugin for BackgroundModule {
+    fn name(&self) -> &str {
+        "background"
+    }
 
-    let instances = {
-        let position = cgmath::vec3(0.0, 0.0, 0.0);
-        let rotation =
-            cgmath::Quaternion::from_axis_angle(cgmath::vec3(0.0, 0.0, 0.0), cgmath::Deg(0.0));
-        let scale = 1.0;
-        Instance {
-            position,
-            rotation,
-            scale,
-        }
-    };
+    fn probe(&mut self, ctx: &mut PluginContext) -> anyhow::Result<()> {
+        let device = &ctx.app_surface.device;
 
-    register_model_instances("background", &*app, model, &[instances]);
-}
+        let bytes = load_binary(BACKGOUND_IMGAE_PATH).map_err(|err| anyhow!("{err}"))?;
+        let background_texture = Texture::load_texture_from_bytes(
+            device,
+            &ctx.app_surface.queue,
+            Some(BACKGOUND_IMGAE_PATH),
+            &bytes,
+            ColorSpace::Srgb,
+            &resources::TextureSampler {
+                wrap_s: WrappingMode::MirroredRepeat,
+                wrap_t: WrappingMode::MirroredRepeat,
+                ..Default::default()
+            },
+            &mut ctx.renderer.samplers,
+            &mut ctx.renderer.mipmaps,
+        )?;
 
-pub struct BackgroundModule {}
+        let layout = ctx
+            .graph_resource
+            .bind_group_info
+            .get("bg_texture")
+            .ok_or_else(|| anyhow!("Missing bind group layout: bg_texture"))?;
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Backgound bind group"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 8,
+                    resource: wgpu::BindingResource::TextureView(&background_texture.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 9,
+                    resource: wgpu::BindingResource::Sampler(&background_texture.sampler),
+                },
+            ],
+        });
 
-#[async_trait]
-impl WgpuAppModule for BackgroundModule {
-    fn new() -> Self {
-        Self {}
-    }
+        let material = Material {
+            name: Some("Backgound".to_string()),
+            pipeline: None,
+            uniform: MaterialUniform::default(),
+            factors: None,
+            bind_group,
+        };
 
-    async fn probe(&mut self, app: Arc<Mutex<WgpuApp>>) -> anyhow::Result<()> {
-        load_background(app.clone()).await;
+        let id = ctx.renderer.load_resources_with_materials(
+            ctx.app_surface,
+            ctx.graph_resource,
+            background_resource(),
+            vec![material],
+        )?;
+        if let Some(model) = ctx.renderer.scene.get_model_mut(id) {
+            model.set_pipeline("background");
+        }
+        self.model.replace(id);
         Ok(())
     }
+
+    fn teardown(&mut self, ctx: &mut PluginContext) {
+        if let Some(id) = self.model.take() {
+            ctx.renderer.scene.remove_model(id);
+        }
+    }
 }
@@ -0,0 +1,237 @@
+use cgmath::InnerSpace;
+
+use crate::engine::{
+    entity::Entity,
+    resources::{Animation, Interpolation, Outputs, Sampler},
+};
+
+#[allow(unused)]
+#[derive(Debug, Clone)]
+pub struct AnimationPlayer {
+    pub animation_index: usize,
+    pub time: f32,
+    pub speed: f32,
+    pub looping: bool,
+}
+
+#[allow(unused)]
+impl AnimationPlayer {
+    pub fn new(animation_index: usize, looping: bool) -> Self {
+        Self {
+            animation_index,
+            time: 0.0,
+            speed: 1.0,
+            looping,
+        }
+    }
+
+    pub fn advance(&mut self, dt: f32, duration: f32) {
+        self.time += dt * self.speed;
+        if duration <= 0.0 {
+            self.time = 0.0;
+        } else if self.looping {
+            self.time = self.time.rem_euclid(duration);
+        } else {
+            self.time = self.time.clamp(0.0, duration);
+        }
+    }
+
+    pub fn is_finished(&self, duration: f32) -> bool {
+        !self.looping && self.time >= duration
+    }
+}
+
+// Write the sampled values of every channel into the targeted entities
+pub fn apply_animation(animation: &Animation, time: f32, entities: &mut [Entity]) {
+    for channel in animation.channels.iter() {
+        let Some(entity) = entities.get_mut(channel.node) else {
+            continue;
+        };
+        let sampler = &animation.samplers[channel.sampler];
+
+        match &sampler.outputs {
+            Outputs::Translations(values) => {
+                let val = sample(sampler, values.as_flattened(), 3, time);
+                entity.translation = cgmath::Vector3::new(val[0], val[1], val[2]);
+            }
+            Outputs::Rotations(values) => {
+                entity.rotation = sample_rotation(sampler, values, time);
+            }
+            Outputs::Scales(values) => {
+                let val = sample(sampler, values.as_flattened(), 3, time);
+                entity.scale = cgmath::Vector3::new(val[0], val[1], val[2]);
+            }
+            Outputs::MorphTargetWeights(values) => {
+                let keyframes = match sampler.interpolation {
+                    Interpolation::CubicSpline => sampler.inputs.len() * 3,
+                    _ => sampler.inputs.len(),
+                };
+                let stride = values.len() / keyframes.max(1);
+                entity.weights = sample(sampler, values, stride, time);
+            }
+        }
+        entity.update_transform();
+    }
+}
+
+// (previous keyframe, next keyframe, factor between them)
+fn keyframe(inputs: &[f32], time: f32) -> (usize, usize, f32) {
+    let last = inputs.len().saturating_sub(1);
+    if inputs.is_empty() || time <= inputs[0] {
+        return (0, 0, 0.0);
+    }
+    if time >= inputs[last] {
+        return (last, last, 0.0);
+    }
+
+    let next = inputs.partition_point(|&t| t <= time);
+    let prev = next - 1;
+    let factor = (time - inputs[prev]) / (inputs[next] - inputs[prev]);
+    (prev, next, factor)
+}
+
+fn sample(sampler: &Sampler, values: &[f32], stride: usize, time: f32) -> Vec<f32> {
+    if stride == 0 || sampler.inputs.is_empty() {
+        return Vec::new();
+    }
+
+    let get = |idx: usize| &values[idx * stride..(idx + 1) * stride];
+    let (prev, next, t) = keyframe(&sampler.inputs, time);
+
+    match sampler.interpolation {
+        Interpolation::Step => get(prev).to_vec(),
+        Interpolation::Linear => get(prev)
+            .iter()
+            .zip(get(next))
+            .map(|(v0, v1)| v0 + (v1 - v0) * t)
+            .collect(),
+        Interpolation::CubicSpline => {
+            // keyframe k stores [in_tangent, value, out_tangent]
+            let dt = sampler.inputs[next] - sampler.inputs[prev];
+            let v0 = get(prev * 3 + 1);
+            let b0 = get(prev * 3 + 2);
+            let a1 = get(next * 3);
+            let v1 = get(next * 3 + 1);
+
+            let t2 = t * t;
+            let t3 = t2 * t;
+            (0..stride)
+                .map(|i| {
+                    (2.0 * t3 - 3.0 * t2 + 1.0) * v0[i]
+                        + (t3 - 2.0 * t2 + t) * dt * b0[i]
+                        + (-2.0 * t3 + 3.0 * t2) * v1[i]
+                        + (t3 - t2) * dt * a1[i]
+                })
+                .collect()
+        }
+    }
+}
+
+fn sample_rotation(sampler: &Sampler, values: &[[f32; 4]], time: f32) -> cgmath::Quaternion<f32> {
+    // glTF rotation: [x, y, z, w]
+    let to_quat = |val: &[f32]| cgmath::Quaternion::new(val[3], val[0], val[1], val[2]);
+
+    if sampler.interpolation == Interpolation::Linear {
+        let (prev, next, t) = keyframe(&sampler.inputs, time);
+        let q0 = to_quat(&values[prev]);
+        let mut q1 = to_quat(&values[next]);
+        // take the shortest path
+        if q0.dot(q1) < 0.0 {
+            q1 = -q1;
+        }
+        return q0.slerp(q1, t).normalize();
+    }
+
+    to_quat(&sample(sampler, values.as_flattened(), 4, time)).normalize()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn sampler(interpolation: Interpolation, inputs: Vec<f32>, outputs: Outputs) -> Sampler {
+        Sampler {
+            interpolation,
+            inputs,
+            outputs,
+        }
+    }
+
+    #[test]
+    fn linear_translation() {
+        let values = vec![[0.0, 0.0, 0.0], [2.0, 4.0, -2.0]];
+        let s = sampler(
+            Interpolation::Linear,
+            vec![0.0, 1.0],
+            Outputs::Translations(values.clone()),
+        );
+        assert_eq!(
+            sample(&s, values.as_flattened(), 3, 0.5),
+            vec![1.0, 2.0, -1.0]
+        );
+        assert_eq!(
+            sample(&s, values.as_flattened(), 3, -1.0),
+            vec![0.0, 0.0, 0.0]
+        );
+        assert_eq!(
+            sample(&s, values.as_flattened(), 3, 3.0),
+            vec![2.0, 4.0, -2.0]
+        );
+    }
+
+    #[test]
+    fn step_scale() {
+        let values = vec![[1.0, 1.0, 1.0], [2.0, 2.0, 2.0]];
+        let s = sampler(
+            Interpolation::Step,
+            vec![0.0, 1.0],
+            Outputs::Scales(values.clone()),
+        );
+        assert_eq!(
+            sample(&s, values.as_flattened(), 3, 0.99),
+            vec![1.0, 1.0, 1.0]
+        );
+        assert_eq!(
+            sample(&s, values.as_flattened(), 3, 1.0),
+            vec![2.0, 2.0, 2.0]
+        );
+    }
+
+    #[test]
+    fn cubic_spline_hits_keyframes() {
+        // [in_tangent, value, out_tangent] per keyframe
+        let values = vec![0.0, 1.0, 5.0, -5.0, 3.0, 0.0];
+        let s = sampler(
+            Interpolation::CubicSpline,
+            vec![0.0, 2.0],
+            Outputs::MorphTargetWeights(values.clone()),
+        );
+        assert_eq!(sample(&s, &values, 1, 0.0), vec![1.0]);
+        assert_eq!(sample(&s, &values, 1, 2.0), vec![3.0]);
+    }
+
+    #[test]
+    fn linear_rotation_is_normalized() {
+        let half = std::f32::consts::FRAC_1_SQRT_2;
+        let values = vec![[0.0, 0.0, 0.0, 1.0], [0.0, half, 0.0, half]];
+        let s = sampler(
+            Interpolation::Linear,
+            vec![0.0, 1.0],
+            Outputs::Rotations(values.clone()),
+        );
+        let q = sample_rotation(&s, &values, 0.5);
+        assert!((q.magnitude() - 1.0).abs() < 1e-5);
+        assert!(q.v.y > 0.0 && q.v.y < half);
+    }
+
+    #[test]
+    fn player_loops() {
+        let mut player = AnimationPlayer::new(0, true);
+        player.advance(2.5, 1.0);
+        assert!((player.time - 0.5).abs() < 1e-5);
+
+        let mut player = AnimationPlayer::new(0, false);
+        player.advance(2.5, 1.0);
+        assert!(player.is_finished(1.0));
+    }
+}
@@ -26,10 +26,20 @@ impl BindGroupInfo {
         match visibility {
             BindGroupVisibilty::Vertex => wgpu::ShaderStages::VERTEX,
             BindGroupVisibilty::Fragment => wgpu::ShaderStages::FRAGMENT,
+            BindGroupVisibilty::Compute => wgpu::ShaderStages::COMPUTE,
             BindGroupVisibilty::ALL => wgpu::ShaderStages::all(),
         }
     }
 
+    #[inline]
+    fn get_buffer_type(ty: &BindGroupEntryType) -> wgpu::BufferBindingType {
+        match ty {
+            BindGroupEntryType::Storage => wgpu::BufferBindingType::Storage { read_only: false },
+            BindGroupEntryType::StorageRo => wgpu::BufferBindingType::Storage { read_only: true },
+            _ => wgpu::BufferBindingType::Uniform,
+        }
+    }
+
     pub fn setup(&mut self, config: &GraphConfig, device: &Device) {
         // let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
         //     label: Some("Camera buffer"),
@@ -54,34 +64,55 @@ impl BindGroupInfo {
                         };
                         entries.push(entry);
                     }
-                    BindGroupEntryType::Sampler => {
+                    BindGroupEntryType::DepthTexture | BindGroupEntryType::DepthTextureArray => {
                         let entry = wgpu::BindGroupLayoutEntry {
                             binding: entry.binding as u32,
                             visibility: Self::get_visibility(&entry.visibility),
-                            ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                            ty: wgpu::BindingType::Texture {
+                                sample_type: wgpu::TextureSampleType::Depth,
+                                view_dimension: if entry.ty == BindGroupEntryType::DepthTextureArray
+                                {
+                                    wgpu::TextureViewDimension::D2Array
+                                } else {
+                                    wgpu::TextureViewDimension::D2
+                                },
+                                multisampled: false,
+                            },
                             count: None,
                         };
                         entries.push(entry);
                     }
-                    BindGroupEntryType::Uniform => {
+                    BindGroupEntryType::Sampler | BindGroupEntryType::ComparisonSampler => {
+                        let ty = if entry.ty == BindGroupEntryType::ComparisonSampler {
+                            wgpu::SamplerBindingType::Comparison
+                        } else {
+                            wgpu::SamplerBindingType::Filtering
+                        };
+                        let entry = wgpu::BindGroupLayoutEntry {
+                            binding: entry.binding as u32,
+                            visibility: Self::get_visibility(&entry.visibility),
+                            ty: wgpu::BindingType::Sampler(ty),
+                            count: None,
+                        };
+                        entries.push(entry);
+                    }
+                    BindGroupEntryType::Uniform
+                    | BindGroupEntryType::Storage
+                    | BindGroupEntryType::StorageRo => {
                         let entry = wgpu::BindGroupLayoutEntry {
                             binding: entry.binding as u32,
                             visibility: Self::get_visibility(&entry.visibility),
                             ty: wgpu::BindingType::Buffer {
-                                ty: wgpu::BufferBindingType::Uniform,
-                                has_dynamic_offset: false,
-                                min_binding_size: None,
+                                ty: Self::get_buffer_type(&entry.ty),
+                                has_dynamic_offset: entry.has_dynamic_offset,
+                                min_binding_size: entry
+                                    .min_binding_size
+                                    .and_then(wgpu::BufferSize::new),
                             },
                             count: None,
                         };
                         entries.push(entry);
                     }
-                    BindGroupEntryType::Storage => {
-                        unimplemented!()
-                    }
-                    BindGroupEntryType::StorageRo => {
-                        unimplemented!()
-                    }
                 }
             }
 
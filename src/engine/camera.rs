@@ -1,14 +1,23 @@
-use cgmath::{Deg, InnerSpace, SquareMatrix};
+use cgmath::{Deg, EuclideanSpace, InnerSpace, SquareMatrix};
 use wgpu::{Device, util::DeviceExt};
 use wgpu_util::hal::AppSurface;
 
-use crate::engine::{WgpuAppGraphResource, controller::Controller};
+use crate::engine::{WgpuAppGraphResource, controller::Controller, resources};
 
 type Pos3 = cgmath::Point3<f32>;
 type Vec3 = cgmath::Vector3<f32>;
 type Vec4 = cgmath::Vector4<f32>;
 type Mat4 = cgmath::Matrix4<f32>;
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Projection {
+    // uses `fovy` and `aspect`
+    Perspective,
+    // half extents of the view volume
+    Orthographic { xmag: f32, ymag: f32 },
+}
+
+#[derive(Debug, Clone)]
 pub struct CameraConfig {
     pub eye: Pos3,
     pub target: Pos3,
@@ -16,7 +25,52 @@ pub struct CameraConfig {
     pub aspect: f32,
     pub fovy: f32,
     pub znear: f32,
+    // infinite for a perspective without far plane
     pub zfar: f32,
+    pub projection: Projection,
+}
+
+impl CameraConfig {
+    // Camera of an asset placed by the world transform of its node, looking along -Z.
+    // The target is put at the distance of the world origin for the controller to orbit.
+    pub fn from_node(camera: &resources::Camera, world: Mat4, aspect: f32) -> Self {
+        let eye = Pos3::from_homogeneous(world * Vec4::new(0.0, 0.0, 0.0, 1.0));
+        let forward = (world * Vec4::new(0.0, 0.0, -1.0, 0.0))
+            .truncate()
+            .normalize();
+        let up = (world * Vec4::new(0.0, 1.0, 0.0, 0.0))
+            .truncate()
+            .normalize();
+        let distance = eye.to_vec().magnitude().max(1.0);
+
+        let (fovy, znear, zfar, projection) = match camera.projection {
+            resources::Projection::Perspective {
+                yfov, znear, zfar, ..
+            } => (
+                Deg::from(cgmath::Rad(yfov)).0,
+                znear,
+                zfar.unwrap_or(f32::INFINITY),
+                Projection::Perspective,
+            ),
+            resources::Projection::Orthographic {
+                xmag,
+                ymag,
+                znear,
+                zfar,
+            } => (45.0, znear, zfar, Projection::Orthographic { xmag, ymag }),
+        };
+
+        Self {
+            eye,
+            target: eye + forward * distance,
+            up,
+            aspect,
+            fovy,
+            znear,
+            zfar,
+            projection,
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -175,6 +229,8 @@ pub struct CameraInfo {
     pub bind_group: Option<wgpu::BindGroup>,
 
     pub controller: CameraController,
+    // uniform changed since the last upload
+    dirty: bool,
 }
 
 #[allow(unused)]
@@ -190,22 +246,80 @@ impl CameraInfo {
             // bind_group_layout: None,
             bind_group: None,
             controller,
+            dirty: false,
         }
     }
 
     pub fn build_view_projection_matrix(&self) -> Mat4 {
         // MVP = proj * view * model
         let view = Mat4::look_at_rh(self.config.eye, self.config.target, self.config.up);
-        let proj = cgmath::perspective(
-            Deg(self.config.fovy),
-            self.config.aspect,
-            self.config.znear,
-            self.config.zfar,
-        );
+        let proj = match self.config.projection {
+            Projection::Perspective if self.config.zfar.is_infinite() => {
+                Self::infinite_perspective(self.config.fovy, self.config.aspect, self.config.znear)
+            }
+            Projection::Perspective => cgmath::perspective(
+                Deg(self.config.fovy),
+                self.config.aspect,
+                self.config.znear,
+                self.config.zfar,
+            ),
+            Projection::Orthographic { xmag, ymag } => cgmath::ortho(
+                -xmag,
+                xmag,
+                -ymag,
+                ymag,
+                self.config.znear,
+                self.config.zfar,
+            ),
+        };
 
         return proj * view;
     }
 
+    // Limit of `cgmath::perspective` as zfar goes to infinity
+    fn infinite_perspective(fovy: f32, aspect: f32, znear: f32) -> Mat4 {
+        let f = 1.0 / (cgmath::Rad::from(Deg(fovy)).0 / 2.0).tan();
+        Mat4::new(
+            f / aspect,
+            0.0,
+            0.0,
+            0.0,
+            0.0,
+            f,
+            0.0,
+            0.0,
+            0.0,
+            0.0,
+            -1.0,
+            -1.0,
+            0.0,
+            0.0,
+            -2.0 * znear,
+            0.0,
+        )
+    }
+
+    pub fn config(&self) -> &CameraConfig {
+        &self.config
+    }
+
+    // Takes effect on the next `upload`
+    pub fn set_config(&mut self, config: CameraConfig) {
+        self.config = config;
+        self.update_view();
+        self.dirty = true;
+    }
+
+    pub fn upload(&mut self, queue: &wgpu::Queue) {
+        if !self.dirty {
+            return;
+        }
+        self.dirty = false;
+        if let Some(buffer) = self.buffer.as_ref() {
+            queue.write_buffer(buffer, 0, &self.uniform.as_bytes());
+        }
+    }
+
     pub fn update_view(&mut self) {
         let view_pos = Vec4::new(self.config.eye.x, self.config.eye.y, self.config.eye.z, 0.0);
         let view_proj = self.build_view_projection_matrix();
@@ -251,6 +365,7 @@ impl CameraInfo {
     pub fn update(&mut self) {
         self.controller.update_camera_config(&mut self.config);
         self.update_view();
+        self.dirty = true;
     }
 }
 
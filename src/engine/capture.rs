@@ -0,0 +1,299 @@
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use anyhow::anyhow;
+
+// Copies a 2D color texture back to the CPU, blocking until the GPU is done with it.
+// The texture needs `COPY_SRC`; 8 bit RGBA and BGRA formats are supported.
+#[allow(unused)]
+pub fn read_texture(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    texture: &wgpu::Texture,
+) -> anyhow::Result<image::RgbaImage> {
+    // copying without the usage is a validation error, which panics by default
+    if !texture.usage().contains(wgpu::TextureUsages::COPY_SRC) {
+        return Err(anyhow!("Can't read back textures without COPY_SRC usage"));
+    }
+    let format = texture.format().remove_srgb_suffix();
+    let bgra = match format {
+        wgpu::TextureFormat::Rgba8Unorm => false,
+        wgpu::TextureFormat::Bgra8Unorm => true,
+        _ => return Err(anyhow!("Can't read back {format:?} textures")),
+    };
+
+    let (width, height) = (texture.width(), texture.height());
+    let row_bytes = 4 * width;
+    // rows of a texture copy start at multiples of 256 bytes
+    let padded_row_bytes = wgpu::util::align_to(row_bytes, wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
+
+    let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Readback buffer"),
+        size: (padded_row_bytes * height) as u64,
+        usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("Readback encoder"),
+    });
+    encoder.copy_texture_to_buffer(
+        texture.as_image_copy(),
+        wgpu::TexelCopyBufferInfo {
+            buffer: &buffer,
+            layout: wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(padded_row_bytes),
+                rows_per_image: Some(height),
+            },
+        },
+        texture.size(),
+    );
+    queue.submit(Some(encoder.finish()));
+
+    let slice = buffer.slice(..);
+    let (sender, receiver) = std::sync::mpsc::channel();
+    slice.map_async(wgpu::MapMode::Read, move |result| {
+        let _ = sender.send(result);
+    });
+    device.poll(wgpu::PollType::Wait)?;
+    receiver.recv()??;
+
+    let mut pixels = Vec::with_capacity((row_bytes * height) as usize);
+    {
+        let data = slice.get_mapped_range();
+        for row in data.chunks(padded_row_bytes as usize) {
+            pixels.extend_from_slice(&row[..row_bytes as usize]);
+        }
+    }
+    buffer.unmap();
+
+    if bgra {
+        for pixel in pixels.chunks_mut(4) {
+            pixel.swap(0, 2);
+        }
+    }
+    image::RgbaImage::from_raw(width, height, pixels)
+        .ok_or_else(|| anyhow!("Readback of {width}x{height} texture is too short"))
+}
+
+// How far an image is from its reference
+#[allow(unused)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImageDiff {
+    // largest difference of any channel
+    pub max: u8,
+    // pixels with a channel differing by more than the tolerance
+    pub mismatched: usize,
+}
+
+#[allow(unused)]
+impl ImageDiff {
+    // Allows `max_mismatched` of the pixels to be off, e.g. 0.001 for antialiased edges
+    pub fn within(&self, image: &image::RgbaImage, max_mismatched: f32) -> bool {
+        let pixels = (image.width() * image.height()) as f32;
+        self.mismatched as f32 <= pixels * max_mismatched
+    }
+}
+
+#[allow(unused)]
+pub fn compare_images(
+    actual: &image::RgbaImage,
+    expected: &image::RgbaImage,
+    tolerance: u8,
+) -> anyhow::Result<ImageDiff> {
+    if actual.dimensions() != expected.dimensions() {
+        return Err(anyhow!(
+            "Image is {:?}, the reference is {:?}",
+            actual.dimensions(),
+            expected.dimensions()
+        ));
+    }
+
+    let mut diff = ImageDiff {
+        max: 0,
+        mismatched: 0,
+    };
+    for (a, b) in actual.pixels().zip(expected.pixels()) {
+        let max =
+            a.0.iter()
+                .zip(b.0.iter())
+                .map(|(a, b)| a.abs_diff(*b))
+                .max()
+                .unwrap_or(0);
+        diff.max = diff.max.max(max);
+        if max > tolerance {
+            diff.mismatched += 1;
+        }
+    }
+    Ok(diff)
+}
+
+#[derive(Debug)]
+struct Sequence {
+    dir: PathBuf,
+    next: u32,
+    count: u32,
+    timestep: Duration,
+}
+
+// Screenshots and numbered frame sequences of the rendered frames, written as PNG
+#[allow(unused)]
+#[derive(Debug)]
+pub struct FrameCapture {
+    dir: PathBuf,
+    screenshot: Option<PathBuf>,
+    sequence: Option<Sequence>,
+}
+
+#[allow(unused)]
+impl FrameCapture {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            screenshot: None,
+            sequence: None,
+        }
+    }
+
+    fn timestamp() -> u128 {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|time| time.as_millis())
+            .unwrap_or(0)
+    }
+
+    // Writes the next frame to `path`, by default a timestamped file in the capture directory
+    pub fn screenshot(&mut self, path: Option<&Path>) {
+        let path = path.map(Path::to_path_buf).unwrap_or_else(|| {
+            self.dir
+                .join(format!("screenshot-{}.png", Self::timestamp()))
+        });
+        self.screenshot.replace(path);
+    }
+
+    // Writes the next `count` frames as `sequence-<time>/frame-0000.png` and on, the scene
+    // advancing by `timestep` per frame however long the capture takes
+    pub fn record(&mut self, count: u32, timestep: Duration) {
+        let dir = self.dir.join(format!("sequence-{}", Self::timestamp()));
+        self.sequence.replace(Sequence {
+            dir,
+            next: 0,
+            count,
+            timestep,
+        });
+    }
+
+    pub fn stop_recording(&mut self) {
+        self.sequence.take();
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.sequence.is_some()
+    }
+
+    pub fn is_pending(&self) -> bool {
+        self.screenshot.is_some() || self.sequence.is_some()
+    }
+
+    // Time to advance the scene by, fixed while recording
+    pub fn frame_time(&self, dt: Duration) -> Duration {
+        self.sequence
+            .as_ref()
+            .map_or(dt, |sequence| sequence.timestep)
+    }
+
+    // Writes `texture` for the pending requests. Returns the finished screenshot and the
+    // directory of a finished sequence.
+    pub fn capture(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        texture: &wgpu::Texture,
+    ) -> anyhow::Result<Vec<PathBuf>> {
+        if !self.is_pending() {
+            return Ok(Vec::new());
+        }
+        let image = match read_texture(device, queue, texture) {
+            Ok(image) => image,
+            Err(err) => {
+                self.screenshot.take();
+                self.sequence.take();
+                return Err(err);
+            }
+        };
+
+        let mut finished = Vec::new();
+        if let Some(path) = self.screenshot.take() {
+            save_png(&image, &path)?;
+            finished.push(path);
+        }
+        if let Some(sequence) = self.sequence.as_mut() {
+            let path = sequence.dir.join(format!("frame-{:04}.png", sequence.next));
+            sequence.next += 1;
+            let done = sequence.next >= sequence.count;
+            let result = save_png(&image, &path);
+            if done || result.is_err() {
+                let sequence = self.sequence.take().unwrap();
+                result?;
+                finished.push(sequence.dir);
+            }
+        }
+        Ok(finished)
+    }
+}
+
+fn save_png(image: &image::RgbaImage, path: &Path) -> anyhow::Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    image
+        .save_with_format(path, image::ImageFormat::Png)
+        .map_err(|err| anyhow!("{}: {err}", path.display()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn image_diff() {
+        let expected = image::RgbaImage::from_pixel(4, 4, image::Rgba([10, 20, 30, 255]));
+        let mut actual = expected.clone();
+        actual.put_pixel(0, 0, image::Rgba([12, 20, 30, 255]));
+        actual.put_pixel(1, 0, image::Rgba([10, 60, 30, 255]));
+
+        let diff = compare_images(&actual, &expected, 2).unwrap();
+        assert_eq!(
+            diff,
+            ImageDiff {
+                max: 40,
+                mismatched: 1
+            }
+        );
+        assert!(!diff.within(&actual, 0.0));
+        assert!(diff.within(&actual, 1.0 / 16.0));
+
+        let small = image::RgbaImage::new(2, 2);
+        assert!(compare_images(&small, &expected, 0).is_err());
+    }
+
+    #[test]
+    fn sequence_timestep() {
+        let mut capture = FrameCapture::new("./captures");
+        let dt = Duration::from_millis(5);
+        assert!(!capture.is_pending());
+        assert_eq!(capture.frame_time(dt), dt);
+
+        let timestep = Duration::from_millis(40);
+        capture.record(3, timestep);
+        assert!(capture.is_recording());
+        assert_eq!(capture.frame_time(dt), timestep);
+
+        capture.stop_recording();
+        assert!(!capture.is_pending());
+        assert_eq!(capture.frame_time(dt), dt);
+    }
+}
@@ -0,0 +1,218 @@
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, HashMap, HashSet},
+};
+
+use anyhow::anyhow;
+
+use crate::engine::{bindgroup::BindGroupInfo, config::GraphConfig, shader::ShaderInfo};
+
+#[allow(unused)]
+pub struct ComputePipelineInfoInner {
+    layout: wgpu::PipelineLayout,
+    pipeline: wgpu::ComputePipeline,
+    pub workgroup_size: [u32; 3],
+}
+
+pub struct ComputePipelineInfo {
+    pub map: HashMap<String, ComputePipelineInfoInner>,
+}
+
+#[allow(unused)]
+impl ComputePipelineInfo {
+    pub fn new() -> Self {
+        Self {
+            map: HashMap::new(),
+        }
+    }
+
+    pub fn setup(
+        &mut self,
+        device: &wgpu::Device,
+        graph_config: &GraphConfig,
+        shader: &ShaderInfo,
+        bind_group_info: &BindGroupInfo,
+    ) -> anyhow::Result<()> {
+        for pl in &graph_config.compute_pipelines {
+            let nametag = pl.0;
+
+            let mut bind_group_layouts = Vec::new();
+            for label in &pl.1.bind_group_layouts {
+                bind_group_layouts.push(
+                    bind_group_info
+                        .get(label)
+                        .ok_or_else(|| anyhow!("{nametag}: unknown bindgroup `{label}`"))?,
+                );
+            }
+
+            let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some(nametag),
+                bind_group_layouts: bind_group_layouts.as_slice(),
+                push_constant_ranges: &[],
+            });
+
+            let shader_name = &pl.1.shader;
+            let shader_module = shader
+                .map
+                .get(shader_name)
+                .ok_or_else(|| anyhow!("{nametag}: shader `{shader_name}` is not loaded"))?;
+
+            let compute_pipeline =
+                device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                    label: Some(nametag),
+                    layout: Some(&pipeline_layout),
+                    module: shader_module,
+                    entry_point: Some(&pl.1.entry_point),
+                    compilation_options: Default::default(),
+                    cache: None,
+                });
+
+            let inner = ComputePipelineInfoInner {
+                layout: pipeline_layout,
+                pipeline: compute_pipeline,
+                workgroup_size: pl.1.workgroup_size.unwrap_or([1; 3]),
+            };
+
+            self.map.insert(nametag.to_string(), inner);
+        }
+        Ok(())
+    }
+
+    pub fn get_info(&self, label: &str) -> Option<&ComputePipelineInfoInner> {
+        self.map.get(label)
+    }
+
+    pub fn get(&self, label: &str) -> Option<&wgpu::ComputePipeline> {
+        self.map.get(label).map(|info| &info.pipeline)
+    }
+
+    // Workgroup count covering `size` invocations in each dimension
+    pub fn workgroups(&self, label: &str, size: [u32; 3]) -> Option<[u32; 3]> {
+        let workgroup_size = self.map.get(label)?.workgroup_size;
+        Some(dispatch_size(size, workgroup_size))
+    }
+}
+
+fn dispatch_size(size: [u32; 3], workgroup_size: [u32; 3]) -> [u32; 3] {
+    [0, 1, 2].map(|idx| size[idx].div_ceil(workgroup_size[idx].max(1)))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ComputeId(u64);
+
+// One dispatch recorded into the compute pass that runs before rendering every frame
+#[allow(unused)]
+#[derive(Debug)]
+pub struct ComputeDispatch {
+    pub pipeline: String,
+    pub bind_groups: Vec<(u32, wgpu::BindGroup)>,
+    pub workgroups: [u32; 3],
+}
+
+#[allow(unused)]
+impl ComputeDispatch {
+    pub fn new(pipeline: &str, workgroups: [u32; 3]) -> Self {
+        Self {
+            pipeline: pipeline.to_string(),
+            bind_groups: Vec::new(),
+            workgroups,
+        }
+    }
+
+    pub fn with_bind_group(mut self, index: u32, bind_group: &wgpu::BindGroup) -> Self {
+        self.set_bind_group(index, bind_group);
+        self
+    }
+
+    pub fn set_bind_group(&mut self, index: u32, bind_group: &wgpu::BindGroup) {
+        self.bind_groups.retain(|(idx, _)| *idx != index);
+        self.bind_groups.push((index, bind_group.clone()));
+    }
+
+    // false when `info` has no pipeline by that name
+    pub fn record(&self, compute_pass: &mut wgpu::ComputePass, info: &ComputePipelineInfo) -> bool {
+        let Some(pipeline) = info.get(&self.pipeline) else {
+            return false;
+        };
+        compute_pass.set_pipeline(pipeline);
+        for (index, bind_group) in self.bind_groups.iter() {
+            compute_pass.set_bind_group(*index, bind_group, &[]);
+        }
+        let [x, y, z] = self.workgroups;
+        compute_pass.dispatch_workgroups(x, y, z);
+        true
+    }
+}
+
+// Dispatches of a frame, run in insertion order
+#[allow(unused)]
+pub struct ComputeQueue {
+    dispatches: BTreeMap<ComputeId, ComputeDispatch>,
+    next_id: u64,
+    // dispatches skipped for an unknown pipeline, warned about once
+    skipped: RefCell<HashSet<ComputeId>>,
+}
+
+#[allow(unused)]
+impl ComputeQueue {
+    pub fn new() -> Self {
+        Self {
+            dispatches: BTreeMap::new(),
+            next_id: 0,
+            skipped: RefCell::new(HashSet::new()),
+        }
+    }
+
+    pub fn add(&mut self, dispatch: ComputeDispatch) -> ComputeId {
+        let id = ComputeId(self.next_id);
+        self.next_id += 1;
+        self.dispatches.insert(id, dispatch);
+        id
+    }
+
+    pub fn get_mut(&mut self, id: ComputeId) -> Option<&mut ComputeDispatch> {
+        self.dispatches.get_mut(&id)
+    }
+
+    pub fn remove(&mut self, id: ComputeId) -> Option<ComputeDispatch> {
+        self.skipped.get_mut().remove(&id);
+        self.dispatches.remove(&id)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.dispatches.is_empty()
+    }
+
+    pub fn record(&self, encoder: &mut wgpu::CommandEncoder, info: &ComputePipelineInfo) {
+        if self.dispatches.is_empty() {
+            return;
+        }
+        let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("Compute Pass"),
+            timestamp_writes: None,
+        });
+        let mut skipped = self.skipped.borrow_mut();
+        for (id, dispatch) in self.dispatches.iter() {
+            if dispatch.record(&mut compute_pass, info) {
+                skipped.remove(id);
+            } else if skipped.insert(*id) {
+                eprintln!(
+                    "Skipping compute dispatch {id:?}: unknown pipeline `{}`",
+                    dispatch.pipeline
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn dispatch_covers_size() {
+        assert_eq!(dispatch_size([100, 1, 1], [64, 1, 1]), [2, 1, 1]);
+        assert_eq!(dispatch_size([128, 8, 3], [64, 8, 1]), [2, 1, 3]);
+        assert_eq!(dispatch_size([0, 1, 1], [64, 1, 1]), [0, 1, 1]);
+    }
+}
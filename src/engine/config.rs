@@ -1,27 +1,57 @@
 use std::{
-    collections::HashMap,
-    fs,
+    collections::{HashMap, HashSet},
+    fmt, fs,
     io::{BufReader, Read},
+    path::Path,
 };
 
 use serde::Deserialize;
 
+use crate::engine::shader::{SHADER_DIR, ShaderInfo, ShaderReflection};
+
 #[derive(Debug, Deserialize)]
 pub struct ShaderConfig {
     pub filename: String,
+    // compute only shaders leave these out
+    #[serde(default)]
     pub vertex_entry: String,
+    // also left out by depth only shaders
+    #[serde(default)]
     pub fragment_entry: String,
+    // WGSL has no imports, these files are prepended to `filename`
+    #[serde(default)]
+    pub includes: Vec<String>,
 }
 
-#[derive(Debug, Deserialize)]
+impl ShaderConfig {
+    pub fn files(&self) -> impl Iterator<Item = &str> {
+        self.includes
+            .iter()
+            .chain(std::iter::once(&self.filename))
+            .map(String::as_str)
+    }
+
+    // The concatenated source of `files`
+    pub fn read_source(&self, shader_dir: &Path) -> std::io::Result<String> {
+        let mut source = String::new();
+        for file in self.files() {
+            source += &fs::read_to_string(shader_dir.join(file))?;
+            source.push('\n');
+        }
+        Ok(source)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
 pub enum VertexBufferLayoutFormat {
     Float32,
     Float32x2,
     Float32x3,
     Float32x4,
+    Uint32x4,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 pub struct VertexBufferLayout {
     pub location: usize,
     pub format: VertexBufferLayoutFormat,
@@ -35,21 +65,28 @@ pub enum VertexStepMode {
 
 #[derive(Debug, Deserialize)]
 pub struct VertexBufferConfig {
+    // derived from the vertex shader inputs when omitted
+    #[serde(default)]
     pub layouts: Vec<VertexBufferLayout>,
     pub mode: VertexStepMode,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Deserialize)]
 pub enum BindGroupVisibilty {
     Vertex,
     Fragment,
+    Compute,
     ALL,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Deserialize)]
 pub enum BindGroupEntryType {
     Texture,
     Sampler,
+    // texture_depth_2d, texture_depth_2d_array and sampler_comparison
+    DepthTexture,
+    DepthTextureArray,
+    ComparisonSampler,
 
     // Buffer subtype
     Uniform,
@@ -57,15 +94,30 @@ pub enum BindGroupEntryType {
     StorageRo, // read-only
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Deserialize)]
 pub struct BindGroupEntry {
+    // what the entry binds, e.g. a material slot
+    pub name: Option<String>,
     pub binding: usize,
     pub ty: BindGroupEntryType,
     pub visibility: BindGroupVisibilty,
+    #[serde(default)]
+    pub has_dynamic_offset: bool,
+    // buffer entries only, in bytes
+    #[serde(default)]
+    pub min_binding_size: Option<u64>,
 }
 
-#[derive(Debug, Deserialize)]
+impl BindGroupEntryType {
+    pub fn is_buffer(&self) -> bool {
+        matches!(self, Self::Uniform | Self::Storage | Self::StorageRo)
+    }
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
 pub struct BindGroupConfig {
+    // derived from the shader resources when omitted
+    #[serde(default)]
     pub entries: Vec<BindGroupEntry>,
 }
 
@@ -76,6 +128,162 @@ pub struct ResourceConfig {
     pub bindgroups: HashMap<String, BindGroupConfig>,
 }
 
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+pub enum PrimitiveTopology {
+    PointList,
+    LineList,
+    LineStrip,
+    #[default]
+    TriangleList,
+    TriangleStrip,
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+pub enum CullMode {
+    None,
+    Front,
+    #[default]
+    Back,
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+pub enum FrontFace {
+    #[default]
+    Ccw,
+    Cw,
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+pub enum PolygonMode {
+    #[default]
+    Fill,
+    Line,
+    Point,
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+pub enum BlendPreset {
+    #[default]
+    Replace,
+    Alpha,
+    Additive,
+    Premultiplied,
+}
+
+// Name of the window surface in `passes`
+pub const SURFACE_TARGET: &str = "surface";
+
+// `Surface` follows the sRGB format of the window surface
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+pub enum ColorFormat {
+    #[default]
+    Surface,
+    Rgba8Unorm,
+    Rgba8UnormSrgb,
+    Bgra8Unorm,
+    Bgra8UnormSrgb,
+    Rgba16Float,
+    Rgba32Float,
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+pub enum CompareFunction {
+    Never,
+    #[default]
+    Less,
+    Equal,
+    LessEqual,
+    Greater,
+    NotEqual,
+    GreaterEqual,
+    Always,
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+pub enum StencilOperation {
+    #[default]
+    Keep,
+    Zero,
+    Replace,
+    Invert,
+    IncrementClamp,
+    DecrementClamp,
+    IncrementWrap,
+    DecrementWrap,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct DepthBiasConfig {
+    pub constant: i32,
+    pub slope_scale: f32,
+    pub clamp: f32,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct DepthConfig {
+    pub test: bool,
+    pub write: bool,
+    pub compare: CompareFunction,
+    pub bias: DepthBiasConfig,
+}
+
+impl Default for DepthConfig {
+    fn default() -> Self {
+        Self {
+            test: true,
+            write: true,
+            compare: CompareFunction::Less,
+            bias: DepthBiasConfig::default(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct StencilFaceConfig {
+    pub compare: CompareFunction,
+    pub fail_op: StencilOperation,
+    pub depth_fail_op: StencilOperation,
+    pub pass_op: StencilOperation,
+}
+
+impl Default for StencilFaceConfig {
+    fn default() -> Self {
+        Self {
+            compare: CompareFunction::Always,
+            fail_op: StencilOperation::Keep,
+            depth_fail_op: StencilOperation::Keep,
+            pass_op: StencilOperation::Keep,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct StencilConfig {
+    pub front: StencilFaceConfig,
+    pub back: StencilFaceConfig,
+    pub read_mask: u32,
+    pub write_mask: u32,
+}
+
+impl Default for StencilConfig {
+    fn default() -> Self {
+        Self {
+            front: StencilFaceConfig::default(),
+            back: StencilFaceConfig::default(),
+            read_mask: 0xff,
+            write_mask: 0xff,
+        }
+    }
+}
+
+fn default_sample_count() -> u32 {
+    1
+}
+
 #[allow(unused)]
 #[derive(Debug, Deserialize)]
 pub struct PipelineConfig {
@@ -83,6 +291,196 @@ pub struct PipelineConfig {
     pub depth_texture: bool,
     pub vertex_buffer_layouts: Vec<String>,
     pub bind_group_layouts: Vec<String>,
+
+    #[serde(default)]
+    pub topology: PrimitiveTopology,
+    #[serde(default)]
+    pub cull_mode: CullMode,
+    #[serde(default)]
+    pub front_face: FrontFace,
+    #[serde(default)]
+    pub polygon_mode: PolygonMode,
+    #[serde(default)]
+    pub blend: BlendPreset,
+    #[serde(default)]
+    pub color_format: ColorFormat,
+    #[serde(default = "default_sample_count")]
+    pub sample_count: u32,
+    // only used when depth_texture = true
+    #[serde(default)]
+    pub depth: DepthConfig,
+    #[serde(default)]
+    pub stencil: Option<StencilConfig>,
+}
+
+#[allow(unused)]
+#[derive(Debug, Deserialize)]
+pub struct ComputePipelineConfig {
+    pub shader: String,
+    pub entry_point: String,
+    pub bind_group_layouts: Vec<String>,
+    // taken from `@workgroup_size` when omitted
+    #[serde(default)]
+    pub workgroup_size: Option<[u32; 3]>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub enum DepthFormat {
+    Depth32Float,
+    Depth24Plus,
+    Depth24PlusStencil8,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(untagged)]
+pub enum TargetFormat {
+    Color(ColorFormat),
+    Depth(DepthFormat),
+}
+
+fn default_scale() -> f32 {
+    1.0
+}
+
+#[allow(unused)]
+#[derive(Debug, Deserialize)]
+pub struct TargetConfig {
+    pub format: TargetFormat,
+    // fraction of the surface size, unless `size` fixes it
+    #[serde(default = "default_scale")]
+    pub scale: f32,
+    #[serde(default)]
+    pub size: Option<[u32; 2]>,
+    #[serde(default = "default_sample_count")]
+    pub sample_count: u32,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+pub enum LoadOp {
+    #[default]
+    Clear,
+    Load,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+pub enum StoreOp {
+    #[default]
+    Store,
+    Discard,
+}
+
+fn default_clear_color() -> [f64; 4] {
+    [0.0, 0.0, 0.0, 1.0]
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ColorAttachmentConfig {
+    pub target: String,
+    // multisampled targets resolve into this one
+    #[serde(default)]
+    pub resolve: Option<String>,
+    #[serde(default)]
+    pub load: LoadOp,
+    #[serde(default)]
+    pub store: StoreOp,
+    #[serde(default = "default_clear_color")]
+    pub clear: [f64; 4],
+}
+
+fn default_clear_depth() -> f32 {
+    1.0
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DepthAttachmentConfig {
+    pub target: String,
+    #[serde(default)]
+    pub load: LoadOp,
+    #[serde(default)]
+    pub store: StoreOp,
+    #[serde(default = "default_clear_depth")]
+    pub clear: f32,
+    #[serde(default)]
+    pub clear_stencil: u32,
+}
+
+// Targets of earlier passes bound as textures at `group`: they fill the Texture entries of
+// `bindgroup` in binding order, every Sampler entry gets a linear sampler
+#[derive(Debug, Deserialize)]
+pub struct SampleConfig {
+    pub bindgroup: String,
+    pub group: u32,
+    pub targets: Vec<String>,
+}
+
+fn default_shadow_resolution() -> u32 {
+    2048
+}
+
+fn default_cascades() -> u32 {
+    3
+}
+
+fn default_shadow_distance() -> f32 {
+    150.0
+}
+
+fn default_spot_shadows() -> u32 {
+    4
+}
+
+#[allow(unused)]
+#[derive(Debug, PartialEq, Deserialize)]
+pub struct ShadowConfig {
+    // width and height of every shadow map layer
+    #[serde(default = "default_shadow_resolution")]
+    pub resolution: u32,
+    // cascades of the directional light, at most `MAX_CASCADES`
+    #[serde(default = "default_cascades")]
+    pub cascades: u32,
+    // view distance covered by the cascades
+    #[serde(default = "default_shadow_distance")]
+    pub distance: f32,
+    // spot lights casting shadows at the same time
+    #[serde(default = "default_spot_shadows")]
+    pub spot_lights: u32,
+    // lit pipeline -> depth only pipeline drawing its models into the shadow maps
+    #[serde(default)]
+    pub pipelines: HashMap<String, String>,
+}
+
+impl ShadowConfig {
+    // Keep in sync with the `vec4f` of cascade splits in the lit shaders
+    pub const MAX_CASCADES: u32 = 4;
+}
+
+impl Default for ShadowConfig {
+    fn default() -> Self {
+        Self {
+            resolution: default_shadow_resolution(),
+            cascades: default_cascades(),
+            distance: default_shadow_distance(),
+            spot_lights: default_spot_shadows(),
+            pipelines: HashMap::new(),
+        }
+    }
+}
+
+#[allow(unused)]
+#[derive(Debug, Deserialize)]
+pub struct PassConfig {
+    pub name: String,
+    // scene models drawn with these pipelines, in this order
+    pub pipelines: Vec<String>,
+    #[serde(default)]
+    pub color: Vec<ColorAttachmentConfig>,
+    #[serde(default)]
+    pub depth: Option<DepthAttachmentConfig>,
+    #[serde(default)]
+    pub samples: Option<SampleConfig>,
+    // draw a fullscreen triangle per pipeline instead of the scene
+    #[serde(default)]
+    pub fullscreen: bool,
 }
 
 #[allow(unused)]
@@ -92,16 +490,962 @@ pub struct GraphConfig {
     pub name: String,
     pub resources: ResourceConfig,
     pub pipelines: HashMap<String, PipelineConfig>,
+    #[serde(default)]
+    pub compute_pipelines: HashMap<String, ComputePipelineConfig>,
+    #[serde(default)]
+    pub targets: HashMap<String, TargetConfig>,
+    // without passes every pipeline draws into the surface
+    #[serde(default)]
+    pub passes: Vec<PassConfig>,
+    #[serde(default)]
+    pub shadows: ShadowConfig,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ConfigError {
+    Io {
+        path: String,
+        message: String,
+    },
+    Parse(String),
+    UnknownKey(String),
+    UnknownShader {
+        pipeline: String,
+        shader: String,
+    },
+    UnknownVertexBuffer {
+        pipeline: String,
+        vertexbuffer: String,
+    },
+    UnknownBindGroup {
+        pipeline: String,
+        bindgroup: String,
+    },
+    DuplicateBinding {
+        bindgroup: String,
+        binding: usize,
+    },
+    DuplicateLocation {
+        vertexbuffer: String,
+        location: usize,
+    },
+    BufferOption {
+        bindgroup: String,
+        binding: usize,
+    },
+    OverlappingLocation {
+        pipeline: String,
+        location: usize,
+        vertexbuffers: [String; 2],
+    },
+    MissingShaderFile {
+        shader: String,
+        path: String,
+    },
+    ShaderReflection {
+        shader: String,
+        message: String,
+    },
+    MissingBindGroup {
+        pipeline: String,
+        group: usize,
+    },
+    MissingBinding {
+        pipeline: String,
+        bindgroup: String,
+        binding: usize,
+    },
+    BindingType {
+        pipeline: String,
+        bindgroup: String,
+        binding: usize,
+        shader: BindGroupEntryType,
+    },
+    BindingVisibility {
+        pipeline: String,
+        bindgroup: String,
+        binding: usize,
+        shader: BindGroupVisibilty,
+    },
+    MissingVertexInput {
+        pipeline: String,
+        location: usize,
+    },
+    VertexFormat {
+        pipeline: String,
+        vertexbuffer: String,
+        location: usize,
+        shader: VertexBufferLayoutFormat,
+    },
+    AmbiguousVertexLayout {
+        pipeline: String,
+    },
+    SampleCount {
+        pipeline: String,
+        count: u32,
+    },
+    StencilWithoutDepth {
+        pipeline: String,
+    },
+    MissingEntryPoint {
+        pipeline: String,
+        shader: String,
+    },
+    WorkgroupSize {
+        pipeline: String,
+        shader: [u32; 3],
+    },
+    // errors of a `[compute_pipelines.*]` entry
+    ComputePipeline(Box<ConfigError>),
+    // `kind` is target, pipeline or bindgroup
+    UnknownPassReference {
+        pass: String,
+        kind: String,
+        name: String,
+    },
+    TargetKind {
+        pass: String,
+        target: String,
+    },
+    PipelineTarget {
+        pass: String,
+        pipeline: String,
+    },
+    SampledTarget {
+        pass: String,
+        target: String,
+    },
+    ShadowCascades {
+        cascades: u32,
+    },
+    // `kind` is lit or shadow
+    UnknownShadowPipeline {
+        kind: String,
+        pipeline: String,
+    },
+    ShadowPipelineDepth {
+        pipeline: String,
+    },
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io { path, message } => write!(f, "{path}: {message}"),
+            Self::Parse(message) => write!(f, "{message}"),
+            Self::UnknownKey(key) => write!(f, "unknown key `{key}`"),
+            Self::UnknownShader { pipeline, shader } => {
+                write!(f, "pipelines.{pipeline}: unknown shader `{shader}`")
+            }
+            Self::UnknownVertexBuffer {
+                pipeline,
+                vertexbuffer,
+            } => {
+                write!(
+                    f,
+                    "pipelines.{pipeline}: unknown vertexbuffer `{vertexbuffer}`"
+                )
+            }
+            Self::UnknownBindGroup {
+                pipeline,
+                bindgroup,
+            } => {
+                write!(f, "pipelines.{pipeline}: unknown bindgroup `{bindgroup}`")
+            }
+            Self::DuplicateBinding { bindgroup, binding } => {
+                write!(
+                    f,
+                    "resources.bindgroups.{bindgroup}: duplicate binding {binding}"
+                )
+            }
+            Self::DuplicateLocation {
+                vertexbuffer,
+                location,
+            } => write!(
+                f,
+                "resources.vertexbuffers.{vertexbuffer}: duplicate location {location}"
+            ),
+            Self::BufferOption { bindgroup, binding } => write!(
+                f,
+                "resources.bindgroups.{bindgroup}: binding {binding}: has_dynamic_offset and min_binding_size need a buffer, min_binding_size must be > 0"
+            ),
+            Self::OverlappingLocation {
+                pipeline,
+                location,
+                vertexbuffers,
+            } => write!(
+                f,
+                "pipelines.{pipeline}: location {location} is used by both `{}` and `{}`",
+                vertexbuffers[0], vertexbuffers[1]
+            ),
+            Self::MissingShaderFile { shader, path } => {
+                write!(f, "resources.shaders.{shader}: missing shader file {path}")
+            }
+            Self::ShaderReflection { shader, message } => {
+                write!(f, "resources.shaders.{shader}: {message}")
+            }
+            Self::MissingBindGroup { pipeline, group } => write!(
+                f,
+                "pipelines.{pipeline}: shader uses @group({group}) but no bindgroup is set for it"
+            ),
+            Self::MissingBinding {
+                pipeline,
+                bindgroup,
+                binding,
+            } => write!(
+                f,
+                "pipelines.{pipeline}: shader uses binding {binding} missing from bindgroup `{bindgroup}`"
+            ),
+            Self::BindingType {
+                pipeline,
+                bindgroup,
+                binding,
+                shader,
+            } => write!(
+                f,
+                "pipelines.{pipeline}: binding {binding} of `{bindgroup}` is {shader:?} in the shader"
+            ),
+            Self::BindingVisibility {
+                pipeline,
+                bindgroup,
+                binding,
+                shader,
+            } => write!(
+                f,
+                "pipelines.{pipeline}: binding {binding} of `{bindgroup}` needs visibility {shader:?}"
+            ),
+            Self::MissingVertexInput { pipeline, location } => write!(
+                f,
+                "pipelines.{pipeline}: no vertexbuffer provides shader input @location({location})"
+            ),
+            Self::VertexFormat {
+                pipeline,
+                vertexbuffer,
+                location,
+                shader,
+            } => write!(
+                f,
+                "pipelines.{pipeline}: location {location} of `{vertexbuffer}` is {shader:?} in the shader"
+            ),
+            Self::AmbiguousVertexLayout { pipeline } => write!(
+                f,
+                "pipelines.{pipeline}: more than one vertexbuffer omits its layouts"
+            ),
+            Self::SampleCount { pipeline, count } => write!(
+                f,
+                "pipelines.{pipeline}: sample_count {count} must be 1, 2, 4 or 8 and the same for every pipeline"
+            ),
+            Self::StencilWithoutDepth { pipeline } => write!(
+                f,
+                "pipelines.{pipeline}: stencil needs depth_texture = true"
+            ),
+            Self::MissingEntryPoint { pipeline, shader } => write!(
+                f,
+                "pipelines.{pipeline}: shader `{shader}` needs vertex_entry, and fragment_entry unless depth only"
+            ),
+            Self::WorkgroupSize { pipeline, shader } => write!(
+                f,
+                "pipelines.{pipeline}: workgroup_size must match {shader:?} in the shader"
+            ),
+            // the inner error names its section `pipelines`
+            Self::ComputePipeline(err) => write!(f, "compute_{err}"),
+            Self::UnknownPassReference { pass, kind, name } => {
+                write!(f, "passes.{pass}: unknown {kind} `{name}`")
+            }
+            Self::TargetKind { pass, target } => write!(
+                f,
+                "passes.{pass}: target `{target}` is not a color target, depth target or sampleable as used"
+            ),
+            Self::PipelineTarget { pass, pipeline } => write!(
+                f,
+                "passes.{pass}: color_format, depth_texture or sample_count of pipeline `{pipeline}` does not match the attachments"
+            ),
+            Self::SampledTarget { pass, target } => write!(
+                f,
+                "passes.{pass}: target `{target}` must be written by an earlier pass and not attached here"
+            ),
+            Self::ShadowCascades { cascades } => write!(
+                f,
+                "shadows: cascades {cascades} must be between 1 and {}",
+                ShadowConfig::MAX_CASCADES
+            ),
+            Self::UnknownShadowPipeline { kind, pipeline } => {
+                write!(f, "shadows.pipelines: unknown {kind} pipeline `{pipeline}`")
+            }
+            Self::ShadowPipelineDepth { pipeline } => write!(
+                f,
+                "shadows.pipelines: `{pipeline}` needs depth_texture = true and no color output"
+            ),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ConfigErrors(pub Vec<ConfigError>);
+
+impl fmt::Display for ConfigErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "graph config has {} error(s):", self.0.len())?;
+        for error in self.0.iter() {
+            writeln!(f, "  - {error}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ConfigErrors {}
+
+impl From<ConfigError> for ConfigErrors {
+    fn from(value: ConfigError) -> Self {
+        Self(vec![value])
+    }
+}
+
+fn sorted<V>(map: &HashMap<String, V>) -> Vec<(&String, &V)> {
+    let mut items = map.iter().collect::<Vec<_>>();
+    items.sort_by_key(|item| item.0);
+    items
 }
 
 impl GraphConfig {
-    pub fn new(path: &str) -> Self {
-        let file = fs::File::open(path).unwrap();
+    pub fn new(path: &str) -> Result<Self, ConfigErrors> {
+        let io_error = |err: std::io::Error| ConfigError::Io {
+            path: path.to_string(),
+            message: err.to_string(),
+        };
+        let file = fs::File::open(path).map_err(io_error)?;
         let mut reader = BufReader::new(file);
         let mut buffer = String::new();
-        reader.read_to_string(&mut buffer).unwrap();
-        let config: GraphConfig = toml::from_str(&buffer).unwrap();
-        config
+        reader.read_to_string(&mut buffer).map_err(io_error)?;
+        Self::parse(&buffer, Path::new(SHADER_DIR))
+    }
+
+    // Deserialize and validate, collecting every problem instead of stopping at the first one
+    pub fn parse(text: &str, shader_dir: &Path) -> Result<Self, ConfigErrors> {
+        let parse_error = |err: toml::de::Error| ConfigError::Parse(err.to_string());
+
+        let mut errors = Vec::new();
+        let deserializer = toml::Deserializer::parse(text).map_err(parse_error)?;
+        let mut config: GraphConfig = serde_ignored::deserialize(deserializer, |path| {
+            errors.push(ConfigError::UnknownKey(path.to_string()))
+        })
+        .map_err(parse_error)?;
+
+        errors.extend(config.validate(shader_dir));
+        errors.extend(config.reflect(shader_dir));
+        if errors.is_empty() {
+            Ok(config)
+        } else {
+            Err(ConfigErrors(errors))
+        }
+    }
+
+    pub fn sample_count(&self) -> u32 {
+        self.pipelines
+            .values()
+            .map(|pipeline| pipeline.sample_count)
+            .max()
+            .unwrap_or(1)
+    }
+
+    pub fn validate(&self, shader_dir: &Path) -> Vec<ConfigError> {
+        let mut errors = Vec::new();
+        let resources = &self.resources;
+
+        for (name, shader) in sorted(&resources.shaders) {
+            for file in shader.files() {
+                let path = shader_dir.join(file);
+                if !path.is_file() {
+                    errors.push(ConfigError::MissingShaderFile {
+                        shader: name.clone(),
+                        path: path.display().to_string(),
+                    });
+                }
+            }
+        }
+
+        for (name, vertexbuffer) in sorted(&resources.vertexbuffers) {
+            let mut locations = HashSet::new();
+            for layout in vertexbuffer.layouts.iter() {
+                if !locations.insert(layout.location) {
+                    errors.push(ConfigError::DuplicateLocation {
+                        vertexbuffer: name.clone(),
+                        location: layout.location,
+                    });
+                }
+            }
+        }
+
+        for (name, bindgroup) in sorted(&resources.bindgroups) {
+            let mut bindings = HashSet::new();
+            for entry in bindgroup.entries.iter() {
+                if !bindings.insert(entry.binding) {
+                    errors.push(ConfigError::DuplicateBinding {
+                        bindgroup: name.clone(),
+                        binding: entry.binding,
+                    });
+                }
+
+                let buffer_option = entry.has_dynamic_offset || entry.min_binding_size.is_some();
+                if (buffer_option && !entry.ty.is_buffer()) || entry.min_binding_size == Some(0) {
+                    errors.push(ConfigError::BufferOption {
+                        bindgroup: name.clone(),
+                        binding: entry.binding,
+                    });
+                }
+            }
+        }
+
+        for (name, pipeline) in sorted(&self.pipelines) {
+            match resources.shaders.get(&pipeline.shader) {
+                None => errors.push(ConfigError::UnknownShader {
+                    pipeline: name.clone(),
+                    shader: pipeline.shader.clone(),
+                }),
+                // depth only pipelines may omit the fragment stage
+                Some(shader)
+                    if shader.vertex_entry.is_empty()
+                        || (shader.fragment_entry.is_empty() && !pipeline.depth_texture) =>
+                {
+                    errors.push(ConfigError::MissingEntryPoint {
+                        pipeline: name.clone(),
+                        shader: pipeline.shader.clone(),
+                    })
+                }
+                Some(_) => {}
+            }
+
+            let mut locations: HashMap<usize, &String> = HashMap::new();
+            for label in pipeline.vertex_buffer_layouts.iter() {
+                let Some(vertexbuffer) = resources.vertexbuffers.get(label) else {
+                    errors.push(ConfigError::UnknownVertexBuffer {
+                        pipeline: name.clone(),
+                        vertexbuffer: label.clone(),
+                    });
+                    continue;
+                };
+                for layout in vertexbuffer.layouts.iter() {
+                    match locations.get(&layout.location) {
+                        Some(&other) if other != label => {
+                            errors.push(ConfigError::OverlappingLocation {
+                                pipeline: name.clone(),
+                                location: layout.location,
+                                vertexbuffers: [other.clone(), label.clone()],
+                            });
+                        }
+                        _ => {
+                            locations.insert(layout.location, label);
+                        }
+                    }
+                }
+            }
+
+            // without passes every pipeline draws into the same targets
+            let sample_count = self
+                .pipelines
+                .values()
+                .map(|pipeline| pipeline.sample_count)
+                .min()
+                .unwrap_or(1);
+            if ![1, 2, 4, 8].contains(&pipeline.sample_count)
+                || (self.passes.is_empty() && pipeline.sample_count != sample_count)
+            {
+                errors.push(ConfigError::SampleCount {
+                    pipeline: name.clone(),
+                    count: pipeline.sample_count,
+                });
+            }
+
+            if pipeline.stencil.is_some() && !pipeline.depth_texture {
+                errors.push(ConfigError::StencilWithoutDepth {
+                    pipeline: name.clone(),
+                });
+            }
+
+            for label in pipeline.bind_group_layouts.iter() {
+                if !resources.bindgroups.contains_key(label) {
+                    errors.push(ConfigError::UnknownBindGroup {
+                        pipeline: name.clone(),
+                        bindgroup: label.clone(),
+                    });
+                }
+            }
+        }
+
+        for (name, pipeline) in sorted(&self.compute_pipelines) {
+            let mut compute_errors = Vec::new();
+            if !resources.shaders.contains_key(&pipeline.shader) {
+                compute_errors.push(ConfigError::UnknownShader {
+                    pipeline: name.clone(),
+                    shader: pipeline.shader.clone(),
+                });
+            }
+            for label in pipeline.bind_group_layouts.iter() {
+                if !resources.bindgroups.contains_key(label) {
+                    compute_errors.push(ConfigError::UnknownBindGroup {
+                        pipeline: name.clone(),
+                        bindgroup: label.clone(),
+                    });
+                }
+            }
+            errors.extend(
+                compute_errors
+                    .into_iter()
+                    .map(|err| ConfigError::ComputePipeline(Box::new(err))),
+            );
+        }
+
+        errors.extend(self.validate_passes());
+        errors.extend(self.validate_shadows());
+        errors
+    }
+
+    fn validate_shadows(&self) -> Vec<ConfigError> {
+        let mut errors = Vec::new();
+        let shadows = &self.shadows;
+
+        if !(1..=ShadowConfig::MAX_CASCADES).contains(&shadows.cascades) {
+            errors.push(ConfigError::ShadowCascades {
+                cascades: shadows.cascades,
+            });
+        }
+
+        for (lit, shadow) in sorted(&shadows.pipelines) {
+            if !self.pipelines.contains_key(lit) {
+                errors.push(ConfigError::UnknownShadowPipeline {
+                    kind: "lit".to_string(),
+                    pipeline: lit.clone(),
+                });
+            }
+            let Some(pipeline) = self.pipelines.get(shadow) else {
+                errors.push(ConfigError::UnknownShadowPipeline {
+                    kind: "shadow".to_string(),
+                    pipeline: shadow.clone(),
+                });
+                continue;
+            };
+            let depth_only = self
+                .resources
+                .shaders
+                .get(&pipeline.shader)
+                .is_some_and(|shader| shader.fragment_entry.is_empty());
+            if !pipeline.depth_texture || !depth_only {
+                errors.push(ConfigError::ShadowPipelineDepth {
+                    pipeline: shadow.clone(),
+                });
+            }
+        }
+
+        errors
+    }
+
+    pub fn is_shadow_pipeline(&self, pipeline: &str) -> bool {
+        self.shadows.pipelines.values().any(|name| name == pipeline)
+    }
+
+    fn validate_passes(&self) -> Vec<ConfigError> {
+        let mut errors = Vec::new();
+        let mut written: HashSet<&str> = HashSet::new();
+
+        for pass in self.passes.iter() {
+            let target_error = |target: &str| {
+                if target != SURFACE_TARGET && !self.targets.contains_key(target) {
+                    ConfigError::UnknownPassReference {
+                        pass: pass.name.clone(),
+                        kind: "target".to_string(),
+                        name: target.to_string(),
+                    }
+                } else {
+                    ConfigError::TargetKind {
+                        pass: pass.name.clone(),
+                        target: target.to_string(),
+                    }
+                }
+            };
+
+            let mut attached = Vec::new();
+            for color in pass.color.iter() {
+                for target in std::iter::once(&color.target).chain(color.resolve.as_ref()) {
+                    if self.color_format(target).is_none() {
+                        errors.push(target_error(target));
+                    }
+                    attached.push(target.as_str());
+                }
+            }
+            if let Some(depth) = pass.depth.as_ref() {
+                match self.targets.get(&depth.target) {
+                    Some(TargetConfig {
+                        format: TargetFormat::Depth(_),
+                        ..
+                    }) => {}
+                    _ => errors.push(target_error(&depth.target)),
+                }
+                attached.push(depth.target.as_str());
+            }
+
+            if let Some(samples) = pass.samples.as_ref() {
+                if !self.resources.bindgroups.contains_key(&samples.bindgroup) {
+                    errors.push(ConfigError::UnknownPassReference {
+                        pass: pass.name.clone(),
+                        kind: "bindgroup".to_string(),
+                        name: samples.bindgroup.clone(),
+                    });
+                }
+                for target in samples.targets.iter() {
+                    if !self.targets.contains_key(target) {
+                        errors.push(target_error(target));
+                    } else if !written.contains(target.as_str())
+                        || attached.contains(&target.as_str())
+                    {
+                        errors.push(ConfigError::SampledTarget {
+                            pass: pass.name.clone(),
+                            target: target.clone(),
+                        });
+                    }
+                }
+            }
+
+            // a pipeline is built for one depth format
+            let depth_format = pass.depth.as_ref().and_then(|depth| {
+                match self.targets.get(&depth.target)?.format {
+                    TargetFormat::Depth(format) => Some(format),
+                    TargetFormat::Color(_) => None,
+                }
+            });
+            let color = pass.color.first().map(|color| {
+                (
+                    self.color_format(&color.target),
+                    self.sample_count_of(&color.target),
+                )
+            });
+            for name in pass.pipelines.iter() {
+                let Some(pipeline) = self.pipelines.get(name) else {
+                    errors.push(ConfigError::UnknownPassReference {
+                        pass: pass.name.clone(),
+                        kind: "pipeline".to_string(),
+                        name: name.clone(),
+                    });
+                    continue;
+                };
+                let matches = match color {
+                    Some((format, sample_count)) => {
+                        format == Some(pipeline.color_format)
+                            && sample_count == pipeline.sample_count
+                    }
+                    None => false,
+                } && pipeline.depth_texture == pass.depth.is_some()
+                    && self.depth_format(name) == depth_format;
+                if !matches {
+                    errors.push(ConfigError::PipelineTarget {
+                        pass: pass.name.clone(),
+                        pipeline: name.clone(),
+                    });
+                }
+            }
+
+            written.extend(attached);
+        }
+
+        errors
+    }
+
+    // Format of the depth target of the first pass drawing `pipeline`
+    pub fn depth_format(&self, pipeline: &str) -> Option<DepthFormat> {
+        if self.is_shadow_pipeline(pipeline) {
+            return Some(DepthFormat::Depth32Float);
+        }
+        self.passes
+            .iter()
+            .filter(|pass| pass.pipelines.iter().any(|name| name == pipeline))
+            .find_map(
+                |pass| match self.targets.get(&pass.depth.as_ref()?.target)?.format {
+                    TargetFormat::Depth(format) => Some(format),
+                    TargetFormat::Color(_) => None,
+                },
+            )
+    }
+
+    // `None` for depth and unknown targets
+    pub fn color_format(&self, target: &str) -> Option<ColorFormat> {
+        if target == SURFACE_TARGET {
+            return Some(ColorFormat::Surface);
+        }
+        match self.targets.get(target)?.format {
+            TargetFormat::Color(format) => Some(format),
+            TargetFormat::Depth(_) => None,
+        }
+    }
+
+    pub fn sample_count_of(&self, target: &str) -> u32 {
+        self.targets
+            .get(target)
+            .map(|target| target.sample_count)
+            .unwrap_or(1)
+    }
+}
+
+// Checks the pipelines against their WGSL and fills in omitted bindgroup entries and
+// vertexbuffer layouts from it
+impl GraphConfig {
+    pub fn reflect(&mut self, shader_dir: &Path) -> Vec<ConfigError> {
+        let mut errors = Vec::new();
+
+        let mut reflections: HashMap<String, ShaderReflection> = HashMap::new();
+        for (name, shader) in sorted(&self.resources.shaders) {
+            if shader.vertex_entry.is_empty() {
+                // compute only, reflected per compute pipeline below
+                continue;
+            }
+            let Ok(source) = shader.read_source(shader_dir) else {
+                // reported by validate
+                continue;
+            };
+            match ShaderInfo::reflect(&source, &shader.vertex_entry, &shader.fragment_entry) {
+                Ok(reflection) => {
+                    reflections.insert(name.clone(), reflection);
+                }
+                Err(err) => errors.push(ConfigError::ShaderReflection {
+                    shader: name.clone(),
+                    message: err.to_string(),
+                }),
+            }
+        }
+
+        let mut names = self.pipelines.keys().cloned().collect::<Vec<_>>();
+        names.sort();
+
+        for name in names.iter() {
+            let pipeline = &self.pipelines[name];
+            let Some(reflection) = reflections.get(&pipeline.shader) else {
+                continue;
+            };
+            self.derive_bind_groups(pipeline.bind_group_layouts.clone(), reflection);
+            if let Err(err) = self.derive_vertex_buffers(name, reflection) {
+                errors.push(err);
+            }
+        }
+
+        let mut compute_reflections = Vec::new();
+        for (name, pipeline) in sorted(&self.compute_pipelines) {
+            let Some(shader) = self.resources.shaders.get(&pipeline.shader) else {
+                continue;
+            };
+            let Ok(source) = shader.read_source(shader_dir) else {
+                continue;
+            };
+            match ShaderInfo::reflect_compute(&source, &pipeline.entry_point) {
+                Ok(reflection) => compute_reflections.push((name.clone(), reflection)),
+                Err(err) => errors.push(ConfigError::ShaderReflection {
+                    shader: pipeline.shader.clone(),
+                    message: err.to_string(),
+                }),
+            }
+        }
+        for (name, reflection) in compute_reflections.iter() {
+            let labels = self.compute_pipelines[name].bind_group_layouts.clone();
+            self.derive_bind_groups(labels, reflection);
+        }
+
+        for name in names.iter() {
+            let pipeline = &self.pipelines[name];
+            if let Some(reflection) = reflections.get(&pipeline.shader) {
+                errors.extend(self.verify_pipeline(name, reflection));
+            }
+        }
+
+        for (name, reflection) in compute_reflections {
+            let pipeline = self.compute_pipelines.get_mut(&name).unwrap();
+            let shader = reflection.workgroup_size.unwrap_or([1; 3]);
+            let mut compute_errors = Vec::new();
+            match pipeline.workgroup_size {
+                None => pipeline.workgroup_size = Some(shader),
+                Some(size) if size != shader => compute_errors.push(ConfigError::WorkgroupSize {
+                    pipeline: name.clone(),
+                    shader,
+                }),
+                Some(_) => {}
+            }
+
+            let pipeline = &self.compute_pipelines[&name];
+            compute_errors.extend(self.verify_bindings(
+                &name,
+                &pipeline.bind_group_layouts,
+                &reflection,
+            ));
+            errors.extend(
+                compute_errors
+                    .into_iter()
+                    .map(|err| ConfigError::ComputePipeline(Box::new(err))),
+            );
+        }
+
+        errors
+    }
+
+    fn derive_bind_groups(&mut self, labels: Vec<String>, reflection: &ShaderReflection) {
+        for (group, label) in labels.iter().enumerate() {
+            let Some(bindgroup) = self.resources.bindgroups.get_mut(label) else {
+                continue;
+            };
+            if !bindgroup.entries.is_empty() {
+                continue;
+            }
+            bindgroup.entries = reflection
+                .bindings
+                .iter()
+                .filter(|binding| binding.group == group)
+                .map(|binding| BindGroupEntry {
+                    name: binding.name.clone(),
+                    binding: binding.binding,
+                    ty: binding.ty.clone(),
+                    visibility: binding.visibility.clone(),
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                })
+                .collect();
+        }
+    }
+
+    // Shader inputs not covered by the other buffers go to the one without layouts
+    fn derive_vertex_buffers(
+        &mut self,
+        pipeline: &str,
+        reflection: &ShaderReflection,
+    ) -> Result<(), ConfigError> {
+        let labels = &self.pipelines[pipeline].vertex_buffer_layouts;
+        let vertexbuffers = &self.resources.vertexbuffers;
+
+        let empty = labels
+            .iter()
+            .filter(|label| {
+                vertexbuffers
+                    .get(*label)
+                    .is_some_and(|vertexbuffer| vertexbuffer.layouts.is_empty())
+            })
+            .collect::<Vec<_>>();
+        let target = match empty.as_slice() {
+            [] => return Ok(()),
+            [target] => (*target).clone(),
+            _ => {
+                return Err(ConfigError::AmbiguousVertexLayout {
+                    pipeline: pipeline.to_string(),
+                });
+            }
+        };
+
+        let covered = labels
+            .iter()
+            .filter_map(|label| vertexbuffers.get(label))
+            .flat_map(|vertexbuffer| vertexbuffer.layouts.iter())
+            .map(|layout| layout.location)
+            .collect::<HashSet<_>>();
+
+        let layouts = reflection
+            .vertex_inputs
+            .iter()
+            .filter(|input| !covered.contains(&input.location))
+            .map(|input| VertexBufferLayout {
+                location: input.location,
+                format: input.format.clone(),
+            })
+            .collect();
+        if let Some(vertexbuffer) = self.resources.vertexbuffers.get_mut(&target) {
+            vertexbuffer.layouts = layouts;
+        }
+        Ok(())
+    }
+
+    fn verify_pipeline(&self, name: &str, reflection: &ShaderReflection) -> Vec<ConfigError> {
+        let pipeline = &self.pipelines[name];
+        let mut errors = self.verify_bindings(name, &pipeline.bind_group_layouts, reflection);
+
+        for input in reflection.vertex_inputs.iter() {
+            let found = pipeline.vertex_buffer_layouts.iter().find_map(|label| {
+                let vertexbuffer = self.resources.vertexbuffers.get(label)?;
+                let layout = vertexbuffer
+                    .layouts
+                    .iter()
+                    .find(|layout| layout.location == input.location)?;
+                Some((label, layout))
+            });
+
+            match found {
+                None => errors.push(ConfigError::MissingVertexInput {
+                    pipeline: name.to_string(),
+                    location: input.location,
+                }),
+                Some((label, layout)) if layout.format != input.format => {
+                    errors.push(ConfigError::VertexFormat {
+                        pipeline: name.to_string(),
+                        vertexbuffer: label.clone(),
+                        location: input.location,
+                        shader: input.format.clone(),
+                    })
+                }
+                Some(_) => {}
+            }
+        }
+
+        errors
+    }
+
+    fn verify_bindings(
+        &self,
+        name: &str,
+        bind_group_layouts: &[String],
+        reflection: &ShaderReflection,
+    ) -> Vec<ConfigError> {
+        let mut errors = Vec::new();
+
+        for binding in reflection.bindings.iter() {
+            let Some(label) = bind_group_layouts.get(binding.group) else {
+                errors.push(ConfigError::MissingBindGroup {
+                    pipeline: name.to_string(),
+                    group: binding.group,
+                });
+                continue;
+            };
+            let Some(bindgroup) = self.resources.bindgroups.get(label) else {
+                // reported by validate
+                continue;
+            };
+            let Some(entry) = bindgroup
+                .entries
+                .iter()
+                .find(|entry| entry.binding == binding.binding)
+            else {
+                errors.push(ConfigError::MissingBinding {
+                    pipeline: name.to_string(),
+                    bindgroup: label.clone(),
+                    binding: binding.binding,
+                });
+                continue;
+            };
+
+            if entry.ty != binding.ty {
+                errors.push(ConfigError::BindingType {
+                    pipeline: name.to_string(),
+                    bindgroup: label.clone(),
+                    binding: binding.binding,
+                    shader: binding.ty.clone(),
+                });
+            }
+
+            let visible = match (&entry.visibility, &binding.visibility) {
+                (BindGroupVisibilty::ALL, _) => true,
+                (config, shader) => config == shader,
+            };
+            if !visible {
+                errors.push(ConfigError::BindingVisibility {
+                    pipeline: name.to_string(),
+                    bindgroup: label.clone(),
+                    binding: binding.binding,
+                    shader: binding.visibility.clone(),
+                });
+            }
+        }
+
+        errors
     }
 }
 
@@ -245,4 +1589,540 @@ bind_group_layouts = ["player_gltf_texture", "camera"]
         let config: GraphConfig = toml::from_str(TESTCONFIG).unwrap();
         println!("{:#?}", config);
     }
+
+    #[test]
+    fn graph_config_is_valid() {
+        let text = include_str!("../config/graph.toml");
+        if let Err(errors) = GraphConfig::parse(text, Path::new(SHADER_DIR)) {
+            panic!("{errors}");
+        }
+    }
+
+    #[test]
+    fn collect_errors() {
+        let errors = GraphConfig::parse(TESTCONFIG, Path::new("./missing"))
+            .unwrap_err()
+            .0;
+        assert!(errors.contains(&ConfigError::UnknownKey(
+            "resources.bindgroups.camera.usgae".to_string()
+        )));
+        assert!(errors.contains(&ConfigError::MissingShaderFile {
+            shader: "item".to_string(),
+            path: Path::new("./missing/item.wgsl").display().to_string(),
+        }));
+    }
+
+    #[test]
+    fn broken_references() {
+        let text = r#"
+version = "1.0.0"
+name = "broken"
+
+[resources.shaders]
+[resources.vertexbuffers.a]
+layouts = [{ location = 0, format = "Float32x3" }, { location = 0, format = "Float32x2" }]
+mode = "VERTEX"
+
+[resources.vertexbuffers.b]
+layouts = [{ location = 0, format = "Float32x4" }]
+mode = "INSTANCE"
+
+[resources.bindgroups.camera]
+entries = [
+    { binding = 0, ty = "Uniform", visibility = "Vertex" },
+    { binding = 0, ty = "Uniform", visibility = "Vertex" },
+]
+
+[pipelines.main]
+shader = "mian"
+depth_texture = true
+vertex_buffer_layouts = ["a", "b", "c"]
+bind_group_layouts = ["camera", "camrea"]
+"#;
+        let errors = GraphConfig::parse(text, Path::new(SHADER_DIR))
+            .unwrap_err()
+            .0;
+        let pipeline = "main".to_string();
+        assert_eq!(
+            errors,
+            vec![
+                ConfigError::DuplicateLocation {
+                    vertexbuffer: "a".to_string(),
+                    location: 0
+                },
+                ConfigError::DuplicateBinding {
+                    bindgroup: "camera".to_string(),
+                    binding: 0
+                },
+                ConfigError::UnknownShader {
+                    pipeline: pipeline.clone(),
+                    shader: "mian".to_string()
+                },
+                ConfigError::OverlappingLocation {
+                    pipeline: pipeline.clone(),
+                    location: 0,
+                    vertexbuffers: ["a".to_string(), "b".to_string()]
+                },
+                ConfigError::UnknownVertexBuffer {
+                    pipeline: pipeline.clone(),
+                    vertexbuffer: "c".to_string()
+                },
+                ConfigError::UnknownBindGroup {
+                    pipeline,
+                    bindgroup: "camrea".to_string()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn buffer_options() {
+        let text = r#"
+version = "1.0.0"
+name = "storage"
+
+[resources.shaders]
+[resources.vertexbuffers]
+
+[resources.bindgroups.lights]
+entries = [
+    { binding = 0, ty = "StorageRo", visibility = "Fragment", min_binding_size = 32 },
+    { binding = 1, ty = "Storage", visibility = "ALL", has_dynamic_offset = true },
+    { binding = 2, ty = "Uniform", visibility = "Fragment", min_binding_size = 0 },
+    { binding = 3, ty = "Texture", visibility = "Fragment", min_binding_size = 16 },
+]
+
+[pipelines]
+"#;
+        let errors = GraphConfig::parse(text, Path::new(SHADER_DIR))
+            .unwrap_err()
+            .0;
+        let bindgroup = "lights".to_string();
+        assert_eq!(
+            errors,
+            vec![
+                ConfigError::BufferOption {
+                    bindgroup: bindgroup.clone(),
+                    binding: 2
+                },
+                ConfigError::BufferOption {
+                    bindgroup,
+                    binding: 3
+                },
+            ]
+        );
+
+        let config = GraphConfig::parse(&text.replace(", min_binding_size = 0", "").replace(
+            r#"{ binding = 3, ty = "Texture", visibility = "Fragment", min_binding_size = 16 },"#,
+            "",
+        ), Path::new(SHADER_DIR))
+        .unwrap();
+        let entries = &config.resources.bindgroups["lights"].entries;
+        assert_eq!(entries[0].ty, BindGroupEntryType::StorageRo);
+        assert_eq!(entries[0].min_binding_size, Some(32));
+        assert!(entries[1].has_dynamic_offset);
+    }
+
+    const BACKGROUND: &str = r#"
+version = "1.0.0"
+name = "reflect"
+
+[resources.shaders.background]
+filename = "background.wgsl"
+vertex_entry = "vs_main"
+fragment_entry = "fs_main"
+
+[resources.vertexbuffers.mesh]
+mode = "VERTEX"
+
+[resources.bindgroups.camera]
+
+[resources.bindgroups.bg_texture]
+entries = [
+    { binding = 8, ty = "Sampler", visibility = "Fragment" },
+    { binding = 9, ty = "Sampler", visibility = "Vertex" },
+]
+
+[pipelines.background]
+shader = "background"
+depth_texture = true
+vertex_buffer_layouts = ["mesh"]
+bind_group_layouts = ["bg_texture", "camera"]
+"#;
+
+    #[test]
+    fn reflect_shader() {
+        let errors = GraphConfig::parse(BACKGROUND, Path::new(SHADER_DIR))
+            .unwrap_err()
+            .0;
+        let pipeline = "background".to_string();
+        let bindgroup = "bg_texture".to_string();
+        assert_eq!(
+            errors,
+            vec![
+                ConfigError::BindingType {
+                    pipeline: pipeline.clone(),
+                    bindgroup: bindgroup.clone(),
+                    binding: 8,
+                    shader: BindGroupEntryType::Texture
+                },
+                ConfigError::BindingVisibility {
+                    pipeline,
+                    bindgroup,
+                    binding: 9,
+                    shader: BindGroupVisibilty::Fragment
+                },
+            ]
+        );
+
+        let text = BACKGROUND
+            .replace(
+                r#"ty = "Sampler", visibility = "Fragment""#,
+                r#"ty = "Texture", visibility = "Fragment""#,
+            )
+            .replace(r#"visibility = "Vertex" }"#, r#"visibility = "ALL" }"#);
+        let config = GraphConfig::parse(&text, Path::new(SHADER_DIR)).unwrap();
+
+        let camera = &config.resources.bindgroups["camera"].entries;
+        assert_eq!(camera.len(), 1);
+        assert_eq!(camera[0].binding, 0);
+        assert_eq!(camera[0].ty, BindGroupEntryType::Uniform);
+        assert_eq!(camera[0].visibility, BindGroupVisibilty::Vertex);
+
+        let mesh = &config.resources.vertexbuffers["mesh"].layouts;
+        let formats = mesh
+            .iter()
+            .map(|layout| (layout.location, layout.format.clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            formats,
+            vec![
+                (0, VertexBufferLayoutFormat::Float32x3),
+                (1, VertexBufferLayoutFormat::Float32x2),
+                (2, VertexBufferLayoutFormat::Float32x3),
+            ]
+        );
+    }
+
+    #[test]
+    fn shader_includes() {
+        let text = BACKGROUND.replace(
+            r#"filename = "background.wgsl""#,
+            r#"filename = "background.wgsl"
+includes = ["missing.wgsl"]"#,
+        );
+        let errors = GraphConfig::parse(&text, Path::new(SHADER_DIR))
+            .unwrap_err()
+            .0;
+        assert!(
+            errors.contains(&ConfigError::MissingShaderFile {
+                shader: "background".to_string(),
+                path: Path::new(SHADER_DIR)
+                    .join("missing.wgsl")
+                    .display()
+                    .to_string(),
+            })
+        );
+
+        let text = include_str!("../config/graph.toml");
+        let config = GraphConfig::parse(text, Path::new(SHADER_DIR)).unwrap();
+        let files = config.resources.shaders["player_gltf"]
+            .files()
+            .collect::<Vec<_>>();
+        assert_eq!(files, vec!["lighting.wgsl", "player_gltf.wgsl"]);
+    }
+
+    #[test]
+    fn pipeline_state() {
+        let text = BACKGROUND
+            .replace(
+                r#"ty = "Sampler", visibility = "Fragment""#,
+                r#"ty = "Texture", visibility = "Fragment""#,
+            )
+            .replace(r#"visibility = "Vertex" }"#, r#"visibility = "ALL" }"#);
+
+        let config = GraphConfig::parse(&text, Path::new(SHADER_DIR)).unwrap();
+        let pipeline = &config.pipelines["background"];
+        assert!(matches!(pipeline.cull_mode, CullMode::Back));
+        assert!(matches!(pipeline.blend, BlendPreset::Replace));
+        assert!(pipeline.depth.test && pipeline.depth.write);
+        assert_eq!(config.sample_count(), 1);
+
+        let state = r#"
+cull_mode = "None"
+blend = "Alpha"
+sample_count = 4
+depth = { write = false, compare = "LessEqual" }
+"#;
+        let config = GraphConfig::parse(&(text.clone() + state), Path::new(SHADER_DIR)).unwrap();
+        let pipeline = &config.pipelines["background"];
+        assert!(matches!(pipeline.cull_mode, CullMode::None));
+        assert!(matches!(pipeline.blend, BlendPreset::Alpha));
+        assert!(pipeline.depth.test && !pipeline.depth.write);
+        assert!(matches!(pipeline.depth.compare, CompareFunction::LessEqual));
+        assert_eq!(config.sample_count(), 4);
+
+        let state = r#"
+sample_count = 3
+stencil = { front = { compare = "Equal" } }
+"#;
+        let text = text.replace("depth_texture = true", "depth_texture = false") + state;
+        let errors = GraphConfig::parse(&text, Path::new(SHADER_DIR))
+            .unwrap_err()
+            .0;
+        let pipeline = "background".to_string();
+        assert_eq!(
+            errors,
+            vec![
+                ConfigError::SampleCount {
+                    pipeline: pipeline.clone(),
+                    count: 3
+                },
+                ConfigError::StencilWithoutDepth { pipeline },
+            ]
+        );
+    }
+
+    #[test]
+    fn compute_pipelines() {
+        let dir = std::env::temp_dir().join("d2game_compute_test");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join("particles.wgsl"),
+            r#"
+@group(0) @binding(0) var<storage, read_write> particles: array<vec4<f32>>;
+
+@compute @workgroup_size(64)
+fn cs_main(@builtin(global_invocation_id) id: vec3<u32>) {
+    particles[id.x] += vec4<f32>(0.0, -0.1, 0.0, 0.0);
+}
+"#,
+        )
+        .unwrap();
+
+        let text = r#"
+version = "1.0.0"
+name = "compute"
+
+[resources.shaders.particles]
+filename = "particles.wgsl"
+
+[resources.vertexbuffers]
+
+[resources.bindgroups.particles]
+
+[pipelines]
+
+[compute_pipelines.particles]
+shader = "particles"
+entry_point = "cs_main"
+bind_group_layouts = ["particles"]
+"#;
+        let config = GraphConfig::parse(text, &dir).unwrap();
+        let pipeline = &config.compute_pipelines["particles"];
+        assert_eq!(pipeline.workgroup_size, Some([64, 1, 1]));
+        let entries = &config.resources.bindgroups["particles"].entries;
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].ty, BindGroupEntryType::Storage);
+        assert_eq!(entries[0].visibility, BindGroupVisibilty::Compute);
+
+        let text = text.replace(
+            r#"bind_group_layouts = ["particles"]"#,
+            r#"bind_group_layouts = ["particles", "particels"]
+workgroup_size = [32, 1, 1]"#,
+        ) + r#"
+[pipelines.draw]
+shader = "particles"
+depth_texture = true
+vertex_buffer_layouts = []
+bind_group_layouts = []
+"#;
+        let errors = GraphConfig::parse(&text, &dir).unwrap_err().0;
+        let pipeline = "particles".to_string();
+        assert_eq!(
+            errors,
+            vec![
+                ConfigError::MissingEntryPoint {
+                    pipeline: "draw".to_string(),
+                    shader: "particles".to_string()
+                },
+                ConfigError::ComputePipeline(Box::new(ConfigError::UnknownBindGroup {
+                    pipeline: pipeline.clone(),
+                    bindgroup: "particels".to_string()
+                })),
+                ConfigError::ComputePipeline(Box::new(ConfigError::WorkgroupSize {
+                    pipeline,
+                    shader: [64, 1, 1]
+                })),
+            ]
+        );
+        assert!(
+            errors[2]
+                .to_string()
+                .starts_with("compute_pipelines.particles: ")
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn shadow_pipelines() {
+        let text = BACKGROUND
+            .replace(
+                r#"ty = "Sampler", visibility = "Fragment""#,
+                r#"ty = "Texture", visibility = "Fragment""#,
+            )
+            .replace(r#"visibility = "Vertex" }"#, r#"visibility = "ALL" }"#)
+            + r#"
+[resources.shaders.shadow]
+filename = "shadow.wgsl"
+vertex_entry = "vs_main"
+
+[resources.vertexbuffers.shadow_mesh]
+mode = "VERTEX"
+
+[resources.bindgroups.transform]
+
+[pipelines.shadow]
+shader = "shadow"
+depth_texture = true
+vertex_buffer_layouts = ["shadow_mesh"]
+bind_group_layouts = ["bg_texture", "camera", "transform"]
+"#;
+        let shadows = r#"
+[shadows]
+cascades = 2
+pipelines = { background = "shadow" }
+"#;
+        let config = GraphConfig::parse(&(text.clone() + shadows), Path::new(SHADER_DIR)).unwrap();
+        assert_eq!(config.shadows.cascades, 2);
+        assert_eq!(config.shadows.resolution, 2048);
+        assert!(config.is_shadow_pipeline("shadow"));
+        assert!(!config.is_shadow_pipeline("background"));
+        assert_eq!(
+            config.depth_format("shadow"),
+            Some(DepthFormat::Depth32Float)
+        );
+
+        let shadows = r#"
+[shadows]
+cascades = 5
+pipelines = { background = "background", item = "shadow", shadow = "bloom" }
+"#;
+        let errors = GraphConfig::parse(&(text + shadows), Path::new(SHADER_DIR))
+            .unwrap_err()
+            .0;
+        assert_eq!(
+            errors,
+            vec![
+                ConfigError::ShadowCascades { cascades: 5 },
+                ConfigError::ShadowPipelineDepth {
+                    pipeline: "background".to_string()
+                },
+                ConfigError::UnknownShadowPipeline {
+                    kind: "lit".to_string(),
+                    pipeline: "item".to_string()
+                },
+                ConfigError::UnknownShadowPipeline {
+                    kind: "shadow".to_string(),
+                    pipeline: "bloom".to_string()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn render_passes() {
+        let text = BACKGROUND
+            .replace(
+                r#"ty = "Sampler", visibility = "Fragment""#,
+                r#"ty = "Texture", visibility = "Fragment""#,
+            )
+            .replace(r#"visibility = "Vertex" }"#, r#"visibility = "ALL" }"#);
+        let passes = r#"
+[targets.depth]
+format = "Depth32Float"
+
+[targets.hdr]
+format = "Rgba16Float"
+scale = 0.5
+
+[[passes]]
+name = "main"
+pipelines = ["background"]
+color = [{ target = "surface", load = "Load" }]
+depth = { target = "depth" }
+"#;
+        let config = GraphConfig::parse(&(text.clone() + passes), Path::new(SHADER_DIR)).unwrap();
+        assert_eq!(
+            config.depth_format("background"),
+            Some(DepthFormat::Depth32Float)
+        );
+        assert_eq!(config.color_format("hdr"), Some(ColorFormat::Rgba16Float));
+        assert_eq!(config.passes[0].color[0].load, LoadOp::Load);
+        assert_eq!(config.targets["hdr"].scale, 0.5);
+
+        let passes = r#"
+[[passes]]
+name = "post"
+pipelines = ["background", "bloom"]
+color = [{ target = "hdr" }]
+depth = { target = "surface" }
+samples = { bindgroup = "bg_texture", group = 0, targets = ["hdr", "shadow"] }
+
+[[passes]]
+name = "main"
+pipelines = ["background"]
+color = [{ target = "depth" }]
+samples = { bindgroup = "post_inputs", group = 3, targets = ["hdr"] }
+"#;
+        let text = text.replace(
+            "[pipelines.background]",
+            "[targets.depth]\nformat = \"Depth32Float\"\n\n[targets.hdr]\nformat = \"Rgba16Float\"\n\n[pipelines.background]",
+        ) + passes;
+        let errors = GraphConfig::parse(&text, Path::new(SHADER_DIR))
+            .unwrap_err()
+            .0;
+        let post = "post".to_string();
+        let main = "main".to_string();
+        assert_eq!(
+            errors,
+            vec![
+                ConfigError::TargetKind {
+                    pass: post.clone(),
+                    target: "surface".to_string()
+                },
+                ConfigError::SampledTarget {
+                    pass: post.clone(),
+                    target: "hdr".to_string()
+                },
+                ConfigError::UnknownPassReference {
+                    pass: post.clone(),
+                    kind: "target".to_string(),
+                    name: "shadow".to_string()
+                },
+                ConfigError::PipelineTarget {
+                    pass: post.clone(),
+                    pipeline: "background".to_string()
+                },
+                ConfigError::UnknownPassReference {
+                    pass: post,
+                    kind: "pipeline".to_string(),
+                    name: "bloom".to_string()
+                },
+                ConfigError::TargetKind {
+                    pass: main.clone(),
+                    target: "depth".to_string()
+                },
+                ConfigError::UnknownPassReference {
+                    pass: main.clone(),
+                    kind: "bindgroup".to_string(),
+                    name: "post_inputs".to_string()
+                },
+                ConfigError::PipelineTarget {
+                    pass: main,
+                    pipeline: "background".to_string()
+                },
+            ]
+        );
+    }
 }
@@ -1,11 +1,49 @@
+use cgmath::SquareMatrix;
+
 use crate::engine::resources;
 
 #[allow(unused)]
 pub struct Entity {
     pub name: Option<String>,
     pub mesh_index: Option<usize>,
+    pub skin_index: Option<usize>,
+    pub camera_index: Option<usize>,
+    pub light_index: Option<usize>,
     pub children: Vec<usize>,
     pub transform: cgmath::Matrix4<f32>,
+    // parent world transform * transform, refreshed by `Model::update_world_transforms`
+    pub world_transform: cgmath::Matrix4<f32>,
+    pub translation: cgmath::Vector3<f32>,
+    pub rotation: cgmath::Quaternion<f32>,
+    pub scale: cgmath::Vector3<f32>,
+    pub weights: Vec<f32>,
+}
+
+#[allow(unused)]
+impl Entity {
+    pub fn new(name: Option<&str>, children: Vec<usize>) -> Self {
+        Self {
+            name: name.map(|val| val.to_string()),
+            mesh_index: None,
+            skin_index: None,
+            camera_index: None,
+            light_index: None,
+            children,
+            transform: cgmath::Matrix4::identity(),
+            world_transform: cgmath::Matrix4::identity(),
+            translation: cgmath::Vector3::new(0.0, 0.0, 0.0),
+            rotation: cgmath::Quaternion::new(1.0, 0.0, 0.0, 0.0),
+            scale: cgmath::Vector3::new(1.0, 1.0, 1.0),
+            weights: Vec::new(),
+        }
+    }
+
+    // T * R * S
+    pub fn update_transform(&mut self) {
+        self.transform = cgmath::Matrix4::from_translation(self.translation)
+            * cgmath::Matrix4::from(self.rotation)
+            * cgmath::Matrix4::from_nonuniform_scale(self.scale.x, self.scale.y, self.scale.z);
+    }
 }
 
 impl From<resources::Node> for Entity {
@@ -13,8 +51,21 @@ impl From<resources::Node> for Entity {
         Self {
             name: node.name,
             mesh_index: node.mesh,
+            skin_index: node.skin,
+            camera_index: node.camera,
+            light_index: node.light,
             children: node.children,
             transform: node.transform,
+            world_transform: node.transform,
+            translation: node.translation.into(),
+            rotation: cgmath::Quaternion::new(
+                node.rotation[3],
+                node.rotation[0],
+                node.rotation[1],
+                node.rotation[2],
+            ),
+            scale: node.scale.into(),
+            weights: Vec::new(),
         }
     }
 }
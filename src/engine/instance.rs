@@ -0,0 +1,255 @@
+use std::ops::Range;
+
+use anyhow::anyhow;
+
+type Vec3 = cgmath::Vector3<f32>;
+type Quat = cgmath::Quaternion<f32>;
+type Mat4 = cgmath::Matrix4<f32>;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Instance {
+    pub position: Vec3,
+    pub rotation: Quat,
+    pub scale: f32,
+}
+
+#[allow(unused)]
+impl Instance {
+    pub fn new(position: Vec3, rotation: Quat, scale: f32) -> Self {
+        Self {
+            position,
+            rotation,
+            scale,
+        }
+    }
+
+    pub fn as_model(&self) -> Mat4 {
+        Mat4::from_translation(self.position)
+            * Mat4::from(self.rotation)
+            * Mat4::from_scale(self.scale)
+    }
+}
+
+impl Default for Instance {
+    fn default() -> Self {
+        Self {
+            position: Vec3::new(0.0, 0.0, 0.0),
+            rotation: Quat::new(1.0, 0.0, 0.0, 0.0),
+            scale: 1.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct InstanceId(u64);
+
+// Instances of one model, packed as model matrices into a vertex buffer that grows on demand
+#[allow(unused)]
+#[derive(Debug)]
+pub struct InstanceSet {
+    instances: Vec<(InstanceId, Instance)>,
+    next_id: u64,
+    // created by the first `upload`
+    buffer: Option<wgpu::Buffer>,
+    capacity: usize,
+    dirty: bool,
+}
+
+impl Default for InstanceSet {
+    fn default() -> Self {
+        Self {
+            instances: Vec::new(),
+            next_id: 0,
+            buffer: None,
+            capacity: Self::INITIAL_CAPACITY,
+            dirty: false,
+        }
+    }
+}
+
+#[allow(unused)]
+impl InstanceSet {
+    const INSTANCE_SIZE: u64 = core::mem::size_of::<[[f32; 4]; 4]>() as u64;
+    const INITIAL_CAPACITY: usize = 16;
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn create_buffer(device: &wgpu::Device, capacity: usize) -> wgpu::Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Instances buffer"),
+            size: Self::INSTANCE_SIZE * capacity as u64,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+
+    pub fn add(&mut self, instance: Instance) -> InstanceId {
+        let id = InstanceId(self.next_id);
+        self.next_id += 1;
+        self.instances.push((id, instance));
+        self.dirty = true;
+        id
+    }
+
+    pub fn extend(&mut self, instances: impl IntoIterator<Item = Instance>) -> Vec<InstanceId> {
+        instances
+            .into_iter()
+            .map(|instance| self.add(instance))
+            .collect()
+    }
+
+    pub fn update(&mut self, id: InstanceId, instance: Instance) -> anyhow::Result<()> {
+        let entry = self
+            .instances
+            .iter_mut()
+            .find(|(val, _)| *val == id)
+            .ok_or_else(|| anyhow!("Instance not found: {id:?}"))?;
+        entry.1 = instance;
+        self.dirty = true;
+        Ok(())
+    }
+
+    pub fn remove(&mut self, id: InstanceId) -> Option<Instance> {
+        let idx = self.instances.iter().position(|(val, _)| *val == id)?;
+        self.dirty = true;
+        Some(self.instances.swap_remove(idx).1)
+    }
+
+    pub fn clear(&mut self) {
+        self.instances.clear();
+        self.dirty = true;
+    }
+
+    pub fn get(&self, id: InstanceId) -> Option<&Instance> {
+        self.instances
+            .iter()
+            .find(|(val, _)| *val == id)
+            .map(|(_, instance)| instance)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Instance> {
+        self.instances.iter().map(|(_, instance)| instance)
+    }
+
+    pub fn len(&self) -> usize {
+        self.instances.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.instances.is_empty()
+    }
+
+    pub fn range(&self) -> Range<u32> {
+        0..self.instances.len() as u32
+    }
+
+    // `None` until the first `upload`
+    pub fn buffer(&self) -> Option<&wgpu::Buffer> {
+        self.buffer.as_ref()
+    }
+
+    // Capacity holding `len` instances, grown to the next power of two and never shrunk
+    fn capacity_for(len: usize, capacity: usize) -> usize {
+        if len > capacity {
+            len.next_power_of_two()
+        } else {
+            capacity
+        }
+    }
+
+    pub fn upload(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) {
+        if !self.dirty {
+            return;
+        }
+
+        let capacity = Self::capacity_for(self.instances.len(), self.capacity);
+        if self.buffer.is_none() || capacity != self.capacity {
+            self.capacity = capacity;
+            self.buffer = Some(Self::create_buffer(device, capacity));
+        }
+
+        let data = self
+            .instances
+            .iter()
+            .map(|(_, instance)| instance.as_model().into())
+            .collect::<Vec<[[f32; 4]; 4]>>();
+        if let Some(buffer) = &self.buffer {
+            queue.write_buffer(buffer, 0, bytemuck::cast_slice(&data));
+        }
+        self.dirty = false;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn at(x: f32) -> Instance {
+        Instance {
+            position: Vec3::new(x, 0.0, 0.0),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn add_remove_readd() {
+        let mut set = InstanceSet::new();
+        let ids = set.extend([at(0.0), at(1.0), at(2.0)]);
+        assert_eq!(set.len(), 3);
+        assert_ne!(ids[0], ids[1]);
+        assert_ne!(ids[1], ids[2]);
+
+        let removed = set.remove(ids[1]).unwrap();
+        assert_eq!(removed.position.x, 1.0);
+        assert_eq!(set.len(), 2);
+        assert!(set.get(ids[1]).is_none());
+        assert!(set.remove(ids[1]).is_none());
+        assert!(set.update(ids[1], at(5.0)).is_err());
+        assert_eq!(set.get(ids[2]).unwrap().position.x, 2.0);
+
+        // ids are not reused
+        let readded = set.add(at(1.0));
+        assert!(!ids.contains(&readded));
+        assert_eq!(set.len(), 3);
+        set.update(readded, at(3.0)).unwrap();
+        assert_eq!(set.get(readded).unwrap().position.x, 3.0);
+    }
+
+    #[test]
+    fn instance_ranges() {
+        let mut set = InstanceSet::new();
+        assert!(set.is_empty());
+        assert_eq!(set.range(), 0..0);
+
+        let ids = set.extend((0..4).map(|x| at(x as f32)));
+        assert_eq!(set.range(), 0..4);
+
+        set.remove(ids[0]);
+        assert_eq!(set.range(), 0..3);
+        let mut positions = set.iter().map(|val| val.position.x).collect::<Vec<_>>();
+        positions.sort_by(f32::total_cmp);
+        assert_eq!(positions, vec![1.0, 2.0, 3.0]);
+
+        set.clear();
+        assert!(set.is_empty());
+        assert_eq!(set.range(), 0..0);
+    }
+
+    #[test]
+    fn capacity_growth() {
+        let initial = InstanceSet::INITIAL_CAPACITY;
+        assert_eq!(InstanceSet::capacity_for(0, initial), initial);
+        assert_eq!(InstanceSet::capacity_for(initial, initial), initial);
+        assert_eq!(InstanceSet::capacity_for(initial + 1, initial), initial * 2);
+        assert_eq!(InstanceSet::capacity_for(100, initial), 128);
+        // never shrinks
+        assert_eq!(InstanceSet::capacity_for(3, 128), 128);
+
+        let mut set = InstanceSet::new();
+        assert!(set.buffer().is_none());
+        set.extend((0..20).map(|x| at(x as f32)));
+        assert_eq!(InstanceSet::capacity_for(set.len(), set.capacity), 32);
+    }
+}
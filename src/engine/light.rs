@@ -0,0 +1,388 @@
+use cgmath::InnerSpace;
+use wgpu::util::DeviceExt;
+
+use crate::engine::{camera::CameraConfig, config::ShadowConfig, resources, shadow::ShadowMaps};
+
+type Vec3 = cgmath::Vector3<f32>;
+type Vec4 = cgmath::Vector4<f32>;
+type Mat4 = cgmath::Matrix4<f32>;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LightKind {
+    // shines along `direction` from infinitely far away, intensity in lux
+    Directional,
+    // intensity in candela
+    Point,
+    // cone angles in radians from `direction`, full intensity inside `inner_cone`
+    Spot { inner_cone: f32, outer_cone: f32 },
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Light {
+    pub kind: LightKind,
+    pub position: Vec3,
+    pub direction: Vec3,
+    pub color: Vec3,
+    pub intensity: f32,
+    // distance where the light reaches zero, `None` for the inverse square falloff only
+    pub range: Option<f32>,
+    // rendered into the shadow maps, see `ShadowMaps::assign_layers`
+    pub cast_shadows: bool,
+}
+
+#[allow(unused)]
+impl Light {
+    // Keep in sync with `LIGHT_*` in lighting.wgsl
+    const DIRECTIONAL: u32 = 0;
+    const POINT: u32 = 1;
+    const SPOT: u32 = 2;
+
+    pub fn directional(direction: Vec3, color: Vec3, intensity: f32) -> Self {
+        Self {
+            kind: LightKind::Directional,
+            position: Vec3::new(0.0, 0.0, 0.0),
+            direction,
+            color,
+            intensity,
+            range: None,
+            cast_shadows: false,
+        }
+    }
+
+    pub fn point(position: Vec3, color: Vec3, intensity: f32, range: Option<f32>) -> Self {
+        Self {
+            kind: LightKind::Point,
+            position,
+            direction: Vec3::new(0.0, 0.0, -1.0),
+            color,
+            intensity,
+            range,
+            cast_shadows: false,
+        }
+    }
+
+    pub fn spot(
+        position: Vec3,
+        direction: Vec3,
+        color: Vec3,
+        intensity: f32,
+        range: Option<f32>,
+        inner_cone: f32,
+        outer_cone: f32,
+    ) -> Self {
+        Self {
+            kind: LightKind::Spot {
+                inner_cone,
+                outer_cone,
+            },
+            position,
+            direction,
+            color,
+            intensity,
+            range,
+            cast_shadows: false,
+        }
+    }
+
+    pub fn with_shadows(mut self) -> Self {
+        self.cast_shadows = true;
+        self
+    }
+
+    // Light of an asset placed by the world transform of its node, shining along -Z
+    pub fn from_node(light: &resources::Light, world: Mat4) -> Self {
+        let position = (world * Vec4::new(0.0, 0.0, 0.0, 1.0)).truncate();
+        let direction = (world * Vec4::new(0.0, 0.0, -1.0, 0.0)).truncate();
+        let kind = match light.kind {
+            resources::LightKind::Directional => LightKind::Directional,
+            resources::LightKind::Point => LightKind::Point,
+            resources::LightKind::Spot {
+                inner_cone_angle,
+                outer_cone_angle,
+            } => LightKind::Spot {
+                inner_cone: inner_cone_angle,
+                outer_cone: outer_cone_angle,
+            },
+        };
+
+        Self {
+            kind,
+            position,
+            direction,
+            color: light.color.into(),
+            intensity: light.intensity,
+            range: light.range,
+            cast_shadows: false,
+        }
+    }
+
+    // `struct Light` in lighting.wgsl, std430 layout. `shadow_layer` is the first layer of
+    // the light in the shadow maps, -1 without shadows
+    pub fn as_raw(&self, shadow_layer: i32) -> [f32; 16] {
+        let (kind, inner_cos, outer_cos) = match self.kind {
+            LightKind::Directional => (Self::DIRECTIONAL, 1.0, 0.0),
+            LightKind::Point => (Self::POINT, 1.0, 0.0),
+            LightKind::Spot {
+                inner_cone,
+                outer_cone,
+            } => (Self::SPOT, inner_cone.cos(), outer_cone.cos()),
+        };
+        let direction = if self.direction.magnitude2() > 0.0 {
+            self.direction.normalize()
+        } else {
+            Vec3::new(0.0, 0.0, -1.0)
+        };
+
+        [
+            self.position.x,
+            self.position.y,
+            self.position.z,
+            // 0 disables the range cutoff
+            self.range.unwrap_or(0.0),
+            direction.x,
+            direction.y,
+            direction.z,
+            f32::from_bits(kind),
+            self.color.x,
+            self.color.y,
+            self.color.z,
+            self.intensity,
+            inner_cos,
+            outer_cos,
+            f32::from_bits(shadow_layer as u32),
+            0.0,
+        ]
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct LightId(u64);
+
+// Lights of a scene, packed into the read-only storage buffer of the `lights` bind group:
+// an ambient color and the light count followed by the array of lights. The same bind
+// group holds the shadow maps of the lights and their light cameras.
+#[allow(unused)]
+#[derive(Debug)]
+pub struct LightSet {
+    pub ambient: Vec3,
+    pub shadows: ShadowMaps,
+    lights: Vec<(LightId, Light)>,
+    // first shadow map layer of every light
+    layers: Vec<i32>,
+    next_id: u64,
+    layout: wgpu::BindGroupLayout,
+    buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+    dirty: bool,
+}
+
+#[allow(unused)]
+impl LightSet {
+    // TODO: from config.pipeline
+    pub const BIND_GROUP_INDEX: u32 = 3;
+    const HEADER_LEN: usize = 8;
+    const LIGHT_LEN: usize = 16;
+
+    pub fn new(
+        device: &wgpu::Device,
+        bind_group_layout: &wgpu::BindGroupLayout,
+        camera_layout: &wgpu::BindGroupLayout,
+        shadow_config: &ShadowConfig,
+    ) -> Self {
+        let ambient = Vec3::new(0.03, 0.03, 0.03);
+        let shadows = ShadowMaps::new(device, camera_layout, shadow_config);
+        let buffer = Self::create_buffer(device, &Self::pack(ambient, &[], &[]));
+        let bind_group = Self::create_bind_group(device, bind_group_layout, &buffer, &shadows);
+        Self {
+            ambient,
+            shadows,
+            lights: Vec::new(),
+            layers: Vec::new(),
+            next_id: 0,
+            layout: bind_group_layout.clone(),
+            buffer,
+            bind_group,
+            dirty: false,
+        }
+    }
+
+    fn create_buffer(device: &wgpu::Device, data: &[f32]) -> wgpu::Buffer {
+        device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Lights buffer"),
+            contents: bytemuck::cast_slice(data),
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+        })
+    }
+
+    fn create_bind_group(
+        device: &wgpu::Device,
+        bind_group_layout: &wgpu::BindGroupLayout,
+        buffer: &wgpu::Buffer,
+        shadows: &ShadowMaps,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Lights bind group"),
+            layout: bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(shadows.view()),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::Sampler(shadows.sampler()),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: shadows.buffer().as_entire_binding(),
+                },
+            ],
+        })
+    }
+
+    // The array always holds one element, a runtime sized binding can't be smaller
+    fn pack(ambient: Vec3, lights: &[Light], layers: &[i32]) -> Vec<f32> {
+        let count = lights.len();
+        let mut data = vec![0.0; Self::HEADER_LEN + Self::LIGHT_LEN * count.max(1)];
+        data[..4].copy_from_slice(&[ambient.x, ambient.y, ambient.z, 1.0]);
+        data[4] = f32::from_bits(count as u32);
+        for (idx, (light, layer)) in lights.iter().zip(layers.iter()).enumerate() {
+            let offset = Self::HEADER_LEN + Self::LIGHT_LEN * idx;
+            data[offset..offset + Self::LIGHT_LEN].copy_from_slice(&light.as_raw(*layer));
+        }
+        data
+    }
+
+    pub fn add(&mut self, light: Light) -> LightId {
+        let id = LightId(self.next_id);
+        self.next_id += 1;
+        self.lights.push((id, light));
+        self.dirty = true;
+        id
+    }
+
+    pub fn get(&self, id: LightId) -> Option<&Light> {
+        self.lights
+            .iter()
+            .find(|(light_id, _)| *light_id == id)
+            .map(|(_, light)| light)
+    }
+
+    pub fn get_mut(&mut self, id: LightId) -> Option<&mut Light> {
+        self.dirty = true;
+        self.lights
+            .iter_mut()
+            .find(|(light_id, _)| *light_id == id)
+            .map(|(_, light)| light)
+    }
+
+    pub fn remove(&mut self, id: LightId) -> Option<Light> {
+        let idx = self
+            .lights
+            .iter()
+            .position(|(light_id, _)| *light_id == id)?;
+        self.dirty = true;
+        Some(self.lights.remove(idx).1)
+    }
+
+    pub fn set_ambient(&mut self, ambient: Vec3) {
+        self.ambient = ambient;
+        self.dirty = true;
+    }
+
+    pub fn clear(&mut self) {
+        self.lights.clear();
+        self.dirty = true;
+    }
+
+    pub fn len(&self) -> usize {
+        self.lights.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lights.is_empty()
+    }
+
+    // Fits the shadow maps to `camera` every frame and writes the lights when they
+    // changed. Returns true when the bind group was recreated.
+    pub fn upload(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        camera: &CameraConfig,
+    ) -> bool {
+        let lights = self
+            .lights
+            .iter()
+            .map(|(_, light)| *light)
+            .collect::<Vec<_>>();
+        if self.dirty {
+            self.layers =
+                ShadowMaps::assign_layers(&lights, self.shadows.cascades, self.shadows.spot_lights);
+        }
+        self.shadows.update(queue, camera, &lights, &self.layers);
+
+        if !self.dirty {
+            return false;
+        }
+        self.dirty = false;
+
+        let data = Self::pack(self.ambient, &lights, &self.layers);
+        let bytes: &[u8] = bytemuck::cast_slice(&data);
+        if bytes.len() as u64 <= self.buffer.size() {
+            queue.write_buffer(&self.buffer, 0, bytes);
+            return false;
+        }
+        self.buffer = Self::create_buffer(device, &data);
+        self.bind_group =
+            Self::create_bind_group(device, &self.layout, &self.buffer, &self.shadows);
+        true
+    }
+
+    pub fn bind_group(&self) -> &wgpu::BindGroup {
+        &self.bind_group
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn pack_lights() {
+        let ambient = Vec3::new(0.1, 0.2, 0.3);
+        let empty = LightSet::pack(ambient, &[], &[]);
+        assert_eq!(empty.len(), LightSet::HEADER_LEN + LightSet::LIGHT_LEN);
+        assert_eq!(&empty[..3], &[0.1, 0.2, 0.3]);
+        assert_eq!(empty[4].to_bits(), 0);
+
+        let spot = Light::spot(
+            Vec3::new(1.0, 2.0, 3.0),
+            Vec3::new(0.0, -2.0, 0.0),
+            Vec3::new(1.0, 1.0, 1.0),
+            10.0,
+            Some(5.0),
+            0.0,
+            std::f32::consts::FRAC_PI_2,
+        );
+        let sun = Light::directional(Vec3::new(0.0, -1.0, 0.0), Vec3::new(1.0, 1.0, 1.0), 3.0);
+        let data = LightSet::pack(ambient, &[sun, spot], &[0, -1]);
+        assert_eq!(data.len(), LightSet::HEADER_LEN + 2 * LightSet::LIGHT_LEN);
+        assert_eq!(data[4].to_bits(), 2);
+
+        let raw = &data[LightSet::HEADER_LEN + LightSet::LIGHT_LEN..];
+        assert_eq!(&raw[..4], &[1.0, 2.0, 3.0, 5.0]);
+        assert_eq!(&raw[4..7], &[0.0, -1.0, 0.0]);
+        assert_eq!(raw[7].to_bits(), Light::SPOT);
+        assert_eq!(raw[11], 10.0);
+        assert_eq!(raw[12], 1.0);
+        assert!(raw[13].abs() < 1e-6);
+        assert_eq!(raw[14].to_bits() as i32, -1);
+        let sun = &data[LightSet::HEADER_LEN..];
+        assert_eq!(sun[14].to_bits(), 0);
+    }
+}
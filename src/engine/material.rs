@@ -1,15 +1,57 @@
+use std::collections::HashMap;
+
+use anyhow::anyhow;
 use wgpu::util::DeviceExt;
 
-use crate::engine::{config::BindGroupConfig, resources, texture::Texture};
+use crate::engine::{
+    config::{BindGroupConfig, BindGroupEntryType},
+    resources::{self, Phong},
+    texture::{ColorSpace, DefaultTextures, Texture},
+};
 
 #[allow(unused)]
 #[derive(Debug)]
 pub struct Material {
     pub name: Option<String>,
-    // pub factors: wgpu::Buffer,
+    // render pipeline tag from graph.toml, overrides the one of the model
+    pub pipeline: Option<String>,
+    // glTF factors, written to `factors` by the setters
+    pub uniform: MaterialUniform,
+    // the buffer of the `factors` slot when the bind group has one
+    pub factors: Option<wgpu::Buffer>,
     pub bind_group: wgpu::BindGroup,
 }
 
+// Layout of the WGSL material uniform:
+// `struct { base_color: vec4f, emissive: vec3f, metallic: f32, roughness: f32 }`
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct MaterialUniform {
+    pub base_color: [f32; 4],
+    // metallic fills the last lane of the vec3
+    pub emissive: [f32; 3],
+    pub metallic: f32,
+    pub roughness: f32,
+    // struct size rounds up to the 16 byte alignment of its vec4
+    pub _padding: [f32; 3],
+}
+
+impl From<&resources::Material> for MaterialUniform {
+    fn from(material: &resources::Material) -> Self {
+        Self {
+            base_color: material.base_color_factor,
+            emissive: material.emissive_factor,
+            metallic: material.metallic_factor[0],
+            roughness: material.roughness_factor[0],
+            _padding: [0.0; 3],
+        }
+    }
+}
+
+// Uploaded textures by image index and the color space they are sampled in, an image
+// used both as color and as data is uploaded once for each
+pub type MaterialTextures = HashMap<(usize, ColorSpace), Texture>;
+
 #[allow(unused)]
 enum MaterialEntry<'a> {
     Buffer(wgpu::Buffer),
@@ -45,156 +87,270 @@ impl<'a> MaterialEntry<'a> {
     }
 }
 
-// GLTF Material
+// Texture maps a material can sample, glTF and OBJ/MTL ones
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MaterialMap {
+    // also the diffuse map of OBJ materials
+    BaseColor,
+    Emissive,
+    MetallicRoughness,
+    Normal,
+    Occlusion,
+    Ambient,
+    Specular,
+    Shininess,
+    Dissolve,
+}
+
+impl MaterialMap {
+    pub const ALL: [Self; 9] = [
+        Self::BaseColor,
+        Self::Emissive,
+        Self::MetallicRoughness,
+        Self::Normal,
+        Self::Occlusion,
+        Self::Ambient,
+        Self::Specular,
+        Self::Shininess,
+        Self::Dissolve,
+    ];
+
+    fn from_name(name: &str) -> Option<Self> {
+        let map = match name {
+            "base_color" | "diffuse" => Self::BaseColor,
+            "emissive" => Self::Emissive,
+            "metallic_roughness" => Self::MetallicRoughness,
+            "normal" => Self::Normal,
+            "occlusion" => Self::Occlusion,
+            "ambient" => Self::Ambient,
+            "specular" => Self::Specular,
+            "shininess" => Self::Shininess,
+            "dissolve" => Self::Dissolve,
+            _ => return None,
+        };
+        Some(map)
+    }
+
+    pub fn texture_index(self, material: &resources::Material) -> Option<usize> {
+        let phong = material.phong.as_ref();
+        match self {
+            Self::BaseColor => material.base_color_texture_index,
+            Self::Emissive => material.emissive_texture_index,
+            Self::MetallicRoughness => material.metallic_roughness_texture_index,
+            Self::Normal => material.normal_texture_index,
+            Self::Occlusion => material.occlusion_texture_index,
+            Self::Ambient => phong.and_then(|phong| phong.ambient_texture_index),
+            Self::Specular => phong.and_then(|phong| phong.specular_texture_index),
+            Self::Shininess => phong.and_then(|phong| phong.shininess_texture_index),
+            Self::Dissolve => phong.and_then(|phong| phong.dissolve_texture_index),
+        }
+    }
+
+    // Color maps are decoded from sRGB, data maps are read as stored
+    pub fn color_space(self) -> ColorSpace {
+        match self {
+            Self::BaseColor | Self::Emissive | Self::Ambient | Self::Specular => ColorSpace::Srgb,
+            Self::MetallicRoughness
+            | Self::Normal
+            | Self::Occlusion
+            | Self::Shininess
+            | Self::Dissolve => ColorSpace::Linear,
+        }
+    }
+
+    // Bound when the material has no such map. Every other map, emissive included,
+    // scales its factor, so white leaves the factor as is.
+    pub fn fallback(self, defaults: &DefaultTextures) -> &Texture {
+        match self {
+            Self::Normal => &defaults.normal,
+            _ => &defaults.white,
+        }
+    }
+}
+
+// What a material bind group entry binds, from the entry name in graph.toml
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MaterialSlot {
+    BaseColor,
+    Metallic,
+    Roughness,
+    Emissive,
+    Ambient,
+    Diffuse,
+    Specular,
+    Shininess,
+    Dissolve,
+    OpticalDensity,
+    IlluminationModel,
+    // the glTF factors in one uniform struct, see `MaterialUniform`
+    Factors,
+    // `<map>_texture`
+    Texture(MaterialMap),
+    // `<map>_sampler`
+    Sampler(MaterialMap),
+}
+
+impl MaterialSlot {
+    pub fn from_name(name: &str) -> Option<Self> {
+        let slot = match name {
+            "base_color" => Self::BaseColor,
+            "metallic" => Self::Metallic,
+            "roughness" => Self::Roughness,
+            "emissive" | "emissive_factor" => Self::Emissive,
+            "ambient" => Self::Ambient,
+            "diffuse" => Self::Diffuse,
+            "specular" => Self::Specular,
+            "shininess" => Self::Shininess,
+            "dissolve" => Self::Dissolve,
+            "optical_density" => Self::OpticalDensity,
+            "illumination_model" => Self::IlluminationModel,
+            "factors" => Self::Factors,
+            _ => {
+                if let Some(map) = name.strip_suffix("_texture") {
+                    return MaterialMap::from_name(map).map(Self::Texture);
+                }
+                if let Some(map) = name.strip_suffix("_sampler") {
+                    return MaterialMap::from_name(map).map(Self::Sampler);
+                }
+                return None;
+            }
+        };
+        Some(slot)
+    }
+
+    // Entry type the slot binds to
+    pub fn ty(&self) -> BindGroupEntryType {
+        match self {
+            Self::Texture(_) => BindGroupEntryType::Texture,
+            Self::Sampler(_) => BindGroupEntryType::Sampler,
+            _ => BindGroupEntryType::Uniform,
+        }
+    }
+}
+
 #[allow(unused)]
 impl Material {
     // TODO: from config.pipeline
     pub const BIND_GROUP_INDEX: u32 = 0;
 
+    // Textures `material` samples, with the color space of the role each is used in
+    pub fn texture_roles(material: &resources::Material) -> Vec<(usize, ColorSpace)> {
+        MaterialMap::ALL
+            .iter()
+            .filter_map(|map| Some((map.texture_index(material)?, map.color_space())))
+            .collect()
+    }
+
+    // Binds every entry of `config` by its name, glTF and OBJ/MTL materials alike.
+    // Maps the material doesn't have are bound to `defaults`.
     pub fn new(
         material: &resources::Material,
-        textures: &[Texture],
+        textures: &MaterialTextures,
+        defaults: &DefaultTextures,
         device: &wgpu::Device,
-        // queue: &wgpu::Queue,
         bind_group_layout: &wgpu::BindGroupLayout,
         config: &BindGroupConfig,
-    ) -> Self {
+    ) -> anyhow::Result<Self> {
+        let phong = material.phong.clone().unwrap_or_default();
+        let uniform = MaterialUniform::from(material);
+        let usage = wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST;
+
         let mut buffers = Vec::new();
+        let mut factors = None;
 
         for entry in config.entries.iter() {
-            let mentry = match entry.binding {
-                0 => {
-                    // base color factor
-                    let label = Some(format!("Material base color factor"));
-                    let data = bytemuck::cast_slice(&material.base_color_factor);
-                    let usage = wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST;
-                    MaterialEntry::to_buffer(device, label.as_deref(), data, usage)
-                }
-                1 => {
-                    // metallic factor
-                    let label = Some(format!("Material metallic factor"));
-                    let data = bytemuck::cast_slice(&material.metallic_factor);
-                    let usage = wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST;
-                    MaterialEntry::to_buffer(device, label.as_deref(), data, usage)
-                }
-                2 => {
-                    // roughness factor
-                    let label = Some(format!("Material roughness factor"));
-                    let data = bytemuck::cast_slice(&material.roughness_factor);
-                    let usage = wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST;
-                    MaterialEntry::to_buffer(device, label.as_deref(), data, usage)
-                }
-                3 => {
-                    // base color texture
-                    // let format = Some(wgpu::TextureFormat::Rgba8UnormSrgb);
-                    // let label = Some(format!("Material base color texture"));
-                    let texture = material
-                        .base_color_texture_index
-                        .map(|idx| &textures[idx])
-                        .unwrap();
-                    MaterialEntry::to_texture_view(texture)
-                }
-                4 => {
-                    // base color sampler
-                    // let label = Some(format!("Material base color sampler"));
-                    let texture = material
-                        .base_color_texture_index
-                        .map(|idx| &textures[idx])
-                        .unwrap();
-                    MaterialEntry::to_texture_sampler(texture)
-                }
-                5 => {
-                    // metallic roughness texture
-                    // let format = Some(wgpu::TextureFormat::Rgba8UnormSrgb);
-                    // let label = Some(format!("Material metallic roughness texture"));
-                    let texture = material
-                        .metallic_roughness_texture_index
-                        .map(|idx| &textures[idx])
-                        .unwrap();
-                    MaterialEntry::to_texture_view(texture)
-                }
-                6 => {
-                    // metallic roughness sampler
-                    // let label = Some(format!("Material metallic roughness sampler"));
-                    let texture = material
-                        .base_color_texture_index
-                        .map(|idx| &textures[idx])
-                        .unwrap();
-                    MaterialEntry::to_texture_sampler(texture)
-                }
-                7 => {
-                    // normal texture
-                    // let format = Some(wgpu::TextureFormat::Rgba8UnormSrgb);
-                    // let label = Some(format!("Material normal texture"));
-                    let texture = material
-                        .normal_texture_index
-                        .map(|idx| &textures[idx])
-                        .unwrap();
-                    MaterialEntry::to_texture_view(texture)
-                }
-                8 => {
-                    // normal sampler
-                    // let label = Some(format!("Material normal sampler"));
-                    let texture = material
-                        .normal_texture_index
-                        .map(|idx| &textures[idx])
-                        .unwrap();
-                    MaterialEntry::to_texture_sampler(texture)
-                }
-                9 => {
-                    // occlusion texture
-                    // let format = Some(wgpu::TextureFormat::Rgba8UnormSrgb);
-                    // let label = Some(format!("Material occlusion texture"));
-                    let texture = material
-                        .occlusion_texture_index
-                        .map(|idx| &textures[idx])
-                        .unwrap();
+            let name = entry
+                .name
+                .as_deref()
+                .ok_or_else(|| anyhow!("Material binding {} has no name", entry.binding))?;
+            let slot = MaterialSlot::from_name(name).ok_or_else(|| {
+                anyhow!("Material binding {}: unknown slot `{name}`", entry.binding)
+            })?;
+            if slot.ty() != entry.ty {
+                return Err(anyhow!(
+                    "Material binding {}: `{name}` binds a {:?}, not a {:?}",
+                    entry.binding,
+                    slot.ty(),
+                    entry.ty
+                ));
+            }
+
+            let mentry = match slot {
+                MaterialSlot::Texture(map) => {
+                    let texture = Self::map_texture(material, map, textures, defaults);
                     MaterialEntry::to_texture_view(texture)
                 }
-                10 => {
-                    // occlusion sampler
-                    // let label = Some(format!("Material occlusion sampler"));
-                    let texture = material
-                        .occlusion_texture_index
-                        .map(|idx| &textures[idx])
-                        .unwrap();
+                MaterialSlot::Sampler(map) => {
+                    let texture = Self::map_texture(material, map, textures, defaults);
                     MaterialEntry::to_texture_sampler(texture)
                 }
-                11 => {
-                    // emissive factor
-                    let label = Some(format!("Material emissive factor"));
-                    let data = bytemuck::cast_slice(&material.emissive_factor);
-                    let usage = wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST;
-                    MaterialEntry::to_buffer(device, label.as_deref(), data, usage)
+                MaterialSlot::Factors => {
+                    let data = bytemuck::bytes_of(&uniform);
+                    let mentry =
+                        MaterialEntry::to_buffer(device, Some("Material factors"), data, usage);
+                    if let MaterialEntry::Buffer(buffer) = &mentry {
+                        factors.replace(buffer.clone());
+                    }
+                    mentry
                 }
-                12 => {
-                    // emissive texture
-                    // let format = Some(wgpu::TextureFormat::Rgba8UnormSrgb);
-                    // let label = Some(format!("Material emissive texture"));
-                    let texture = material
-                        .emissive_texture_index
-                        .map(|idx| &textures[idx])
-                        .unwrap();
-                    MaterialEntry::to_texture_view(texture)
-                }
-                13 => {
-                    // emissive sampler
-                    // let label = Some(format!("Material emissive sampler"));
-                    let texture = material
-                        .emissive_texture_index
-                        .map(|idx| &textures[idx])
-                        .unwrap();
-                    MaterialEntry::to_texture_sampler(texture)
-                }
-
-                _ => {
-                    unimplemented!()
+                slot => {
+                    // single factors too are bound in whole 16 byte rows
+                    let mut data = Self::factor_data(slot, material, &phong).to_vec();
+                    data.resize(wgpu::util::align_to(data.len(), 16), 0);
+                    let label = format!("Material {name}");
+                    MaterialEntry::to_buffer(device, Some(&label), &data, usage)
                 }
             };
 
             buffers.push((entry.binding as u32, mentry));
         }
 
+        let mut material =
+            Self::from_entries(material.name.clone(), buffers, device, bind_group_layout);
+        material.uniform = uniform;
+        material.factors = factors;
+        Ok(material)
+    }
+
+    // The texture of `map`, the default one when the material has none
+    fn map_texture<'a>(
+        material: &resources::Material,
+        map: MaterialMap,
+        textures: &'a MaterialTextures,
+        defaults: &'a DefaultTextures,
+    ) -> &'a Texture {
+        map.texture_index(material)
+            .and_then(|idx| textures.get(&(idx, map.color_space())))
+            .unwrap_or(map.fallback(defaults))
+    }
+
+    fn factor_data<'a>(
+        slot: MaterialSlot,
+        material: &'a resources::Material,
+        phong: &'a Phong,
+    ) -> &'a [u8] {
+        match slot {
+            MaterialSlot::BaseColor => bytemuck::cast_slice(&material.base_color_factor),
+            MaterialSlot::Metallic => bytemuck::cast_slice(&material.metallic_factor),
+            MaterialSlot::Roughness => bytemuck::cast_slice(&material.roughness_factor),
+            MaterialSlot::Emissive => bytemuck::cast_slice(&material.emissive_factor),
+            MaterialSlot::Ambient => bytemuck::cast_slice(&phong.ambient),
+            MaterialSlot::Diffuse => bytemuck::cast_slice(&phong.diffuse),
+            MaterialSlot::Specular => bytemuck::cast_slice(&phong.specular),
+            MaterialSlot::Shininess => bytemuck::bytes_of(&phong.shininess),
+            MaterialSlot::Dissolve => bytemuck::bytes_of(&phong.dissolve),
+            MaterialSlot::OpticalDensity => bytemuck::bytes_of(&phong.optical_density),
+            MaterialSlot::IlluminationModel => bytemuck::bytes_of(&phong.illumination_model),
+            MaterialSlot::Factors | MaterialSlot::Texture(_) | MaterialSlot::Sampler(_) => &[],
+        }
+    }
+
+    fn from_entries(
+        name: Option<String>,
+        buffers: Vec<(u32, MaterialEntry)>,
+        device: &wgpu::Device,
+        bind_group_layout: &wgpu::BindGroupLayout,
+    ) -> Self {
         let entries = buffers
             .iter()
             .map(|(idx, buffer)| match buffer {
@@ -220,8 +376,104 @@ impl Material {
         });
 
         Self {
-            name: material.name.clone(),
+            name,
+            pipeline: None,
+            uniform: MaterialUniform::default(),
+            factors: None,
             bind_group,
         }
     }
+
+    pub fn set_pipeline(&mut self, tag: &str) {
+        self.pipeline.replace(tag.to_string());
+    }
+
+    // Factor setters write the uniform in place, the bind group is kept
+    pub fn set_base_color(&mut self, queue: &wgpu::Queue, base_color: [f32; 4]) {
+        self.uniform.base_color = base_color;
+        self.write_factors(queue);
+    }
+
+    pub fn set_emissive(&mut self, queue: &wgpu::Queue, emissive: [f32; 3]) {
+        self.uniform.emissive = emissive;
+        self.write_factors(queue);
+    }
+
+    pub fn set_metallic(&mut self, queue: &wgpu::Queue, metallic: f32) {
+        self.uniform.metallic = metallic;
+        self.write_factors(queue);
+    }
+
+    pub fn set_roughness(&mut self, queue: &wgpu::Queue, roughness: f32) {
+        self.uniform.roughness = roughness;
+        self.write_factors(queue);
+    }
+
+    fn write_factors(&self, queue: &wgpu::Queue) {
+        if let Some(buffer) = self.factors.as_ref() {
+            queue.write_buffer(buffer, 0, bytemuck::bytes_of(&self.uniform));
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn texture_roles() {
+        // the same image as base color and occlusion is sampled in both spaces
+        let material = resources::Material {
+            name: None,
+            base_color_factor: [1.0; 4],
+            base_color_texture_index: Some(0),
+            normal_texture_index: Some(1),
+            metallic_factor: [1.0],
+            roughness_factor: [1.0],
+            metallic_roughness_texture_index: None,
+            occlusion_texture_index: Some(0),
+            emissive_factor: [0.0; 3],
+            emissive_texture_index: None,
+            phong: None,
+        };
+        assert_eq!(
+            Material::texture_roles(&material),
+            vec![
+                (0, ColorSpace::Srgb),
+                (1, ColorSpace::Linear),
+                (0, ColorSpace::Linear),
+            ]
+        );
+        assert_eq!(ColorSpace::Linear.format(), wgpu::TextureFormat::Rgba8Unorm);
+    }
+
+    #[test]
+    fn material_uniform_layout() {
+        assert_eq!(std::mem::size_of::<MaterialUniform>(), 48);
+        assert_eq!(std::mem::offset_of!(MaterialUniform, emissive), 16);
+        assert_eq!(std::mem::offset_of!(MaterialUniform, metallic), 28);
+        assert_eq!(std::mem::offset_of!(MaterialUniform, roughness), 32);
+    }
+
+    #[test]
+    fn material_slots() {
+        assert_eq!(
+            MaterialSlot::from_name("emissive_factor"),
+            Some(MaterialSlot::Emissive)
+        );
+        assert_eq!(
+            MaterialSlot::from_name("metallic_roughness_sampler"),
+            Some(MaterialSlot::Sampler(MaterialMap::MetallicRoughness))
+        );
+        assert_eq!(
+            MaterialSlot::from_name("diffuse_texture"),
+            Some(MaterialSlot::Texture(MaterialMap::BaseColor))
+        );
+        assert_eq!(
+            MaterialSlot::from_name("optical_density").map(|slot| slot.ty()),
+            Some(BindGroupEntryType::Uniform)
+        );
+        assert_eq!(MaterialSlot::from_name("roughness_texture"), None);
+        assert_eq!(MaterialSlot::from_name("albedo"), None);
+    }
 }
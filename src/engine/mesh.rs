@@ -37,6 +37,8 @@ pub struct Primitive {
     pub tex_coords: wgpu::Buffer,
     pub normals: wgpu::Buffer,
     // pub tangents: wgpu::Buffer,
+    pub joints: Option<wgpu::Buffer>,
+    pub weights: Option<wgpu::Buffer>,
     pub indices: wgpu::Buffer,
     pub indices_num: u32,
     pub material_index: usize,
@@ -46,6 +48,9 @@ impl Primitive {
     pub const POSITION_LOCATION: u32 = 0;
     pub const TEX_COORDS_LOCATION: u32 = 1;
     pub const NORMAL_LOCATION: u32 = 2;
+    pub const INSTANCE_LOCATION: u32 = 3;
+    pub const JOINTS_LOCATION: u32 = 4;
+    pub const WEIGHTS_LOCATION: u32 = 5;
 
     pub fn new(primative: &resources::Primitive, label: &str, device: &wgpu::Device) -> Self {
         let positions = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
@@ -72,6 +77,22 @@ impl Primitive {
         //     usage: wgpu::BufferUsages::VERTEX
         // });
 
+        let joints = primative.joints.as_ref().map(|joints| {
+            device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some(&format!("Joints buffer: {label}")),
+                contents: bytemuck::cast_slice(joints),
+                usage: wgpu::BufferUsages::VERTEX,
+            })
+        });
+
+        let weights = primative.weights.as_ref().map(|weights| {
+            device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some(&format!("Weights buffer: {label}")),
+                contents: bytemuck::cast_slice(weights),
+                usage: wgpu::BufferUsages::VERTEX,
+            })
+        });
+
         let indices = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some(&format!("Indices buffer: {label}")),
             contents: bytemuck::cast_slice(&primative.indices),
@@ -82,6 +103,8 @@ impl Primitive {
             positions,
             tex_coords,
             normals,
+            joints,
+            weights,
             indices,
             indices_num: primative.indices.len() as u32,
             material_index: primative.material,
@@ -0,0 +1,202 @@
+// Mip chains of color textures: filtered level by level on the GPU, or downscaled on
+// the CPU for formats that can't be rendered to
+
+use std::collections::HashMap;
+
+const MIPMAP_SHADER: &str = include_str!("../../shader/mipmap.wgsl");
+
+// Full chain down to 1x1
+pub fn mip_level_count(width: u32, height: u32) -> u32 {
+    32 - width.max(height).max(1).leading_zeros()
+}
+
+pub fn mip_size(width: u32, height: u32, level: u32) -> (u32, u32) {
+    ((width >> level).max(1), (height >> level).max(1))
+}
+
+// Whether `generate` can render into textures of `format`
+pub fn renderable(device: &wgpu::Device, format: wgpu::TextureFormat) -> bool {
+    let features = format.guaranteed_format_features(device.features());
+    features
+        .allowed_usages
+        .contains(wgpu::TextureUsages::RENDER_ATTACHMENT)
+        && features
+            .flags
+            .contains(wgpu::TextureFormatFeatureFlags::FILTERABLE)
+}
+
+// One pipeline per target format, created on first use and shared with the shader and
+// sampler by every texture after it
+#[allow(unused)]
+pub struct MipmapGenerator {
+    shader: wgpu::ShaderModule,
+    sampler: wgpu::Sampler,
+    pipelines: HashMap<wgpu::TextureFormat, wgpu::RenderPipeline>,
+}
+
+#[allow(unused)]
+impl MipmapGenerator {
+    pub fn new(device: &wgpu::Device) -> Self {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Mipmap shader"),
+            source: wgpu::ShaderSource::Wgsl(MIPMAP_SHADER.into()),
+        });
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Mipmap sampler"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+        Self {
+            shader,
+            sampler,
+            pipelines: HashMap::new(),
+        }
+    }
+
+    fn create_pipeline(
+        device: &wgpu::Device,
+        shader: &wgpu::ShaderModule,
+        format: wgpu::TextureFormat,
+    ) -> wgpu::RenderPipeline {
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Mipmap pipeline"),
+            layout: None,
+            vertex: wgpu::VertexState {
+                module: shader,
+                entry_point: Some("vs_main"),
+                compilation_options: Default::default(),
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: shader,
+                entry_point: Some("fs_main"),
+                compilation_options: Default::default(),
+                targets: &[Some(format.into())],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        })
+    }
+
+    pub fn pipeline_count(&self) -> usize {
+        self.pipelines.len()
+    }
+
+    // Fills every level after the first from the one before. The texture needs
+    // `RENDER_ATTACHMENT` and `TEXTURE_BINDING`, see `renderable`.
+    pub fn generate(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        texture: &wgpu::Texture,
+    ) {
+        if texture.mip_level_count() < 2 {
+            return;
+        }
+
+        let pipeline = self
+            .pipelines
+            .entry(texture.format())
+            .or_insert_with(|| Self::create_pipeline(device, &self.shader, texture.format()));
+        let layout = pipeline.get_bind_group_layout(0);
+
+        let views = (0..texture.mip_level_count())
+            .map(|level| {
+                texture.create_view(&wgpu::TextureViewDescriptor {
+                    label: Some("Mip level"),
+                    base_mip_level: level,
+                    mip_level_count: Some(1),
+                    ..Default::default()
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Mipmap encoder"),
+        });
+        for level in 1..views.len() {
+            let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Mipmap bind group"),
+                layout: &layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(&views[level - 1]),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::Sampler(&self.sampler),
+                    },
+                ],
+            });
+
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Mipmap pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &views[level],
+                    depth_slice: None,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                ..Default::default()
+            });
+            render_pass.set_pipeline(pipeline);
+            render_pass.set_bind_group(0, &bind_group, &[]);
+            render_pass.draw(0..3, 0..1);
+        }
+        queue.submit(Some(encoder.finish()));
+    }
+}
+
+// Every level after the first, for uploads of formats `generate` can't handle
+pub fn cpu_chain(image: &image::RgbaImage, levels: u32) -> Vec<image::RgbaImage> {
+    let mut chain: Vec<image::RgbaImage> = Vec::new();
+    for level in 1..levels {
+        let (width, height) = mip_size(image.width(), image.height(), level);
+        let source = chain.last().unwrap_or(image);
+        chain.push(image::imageops::resize(
+            source,
+            width,
+            height,
+            image::imageops::FilterType::Triangle,
+        ));
+    }
+    chain
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn mip_chain() {
+        assert_eq!(mip_level_count(1, 1), 1);
+        assert_eq!(mip_level_count(256, 256), 9);
+        assert_eq!(mip_level_count(300, 20), 9);
+        assert_eq!(mip_size(300, 20, 5), (9, 1));
+
+        let image = image::RgbaImage::from_fn(8, 4, |x, _| {
+            if x % 2 == 0 {
+                image::Rgba([255, 255, 255, 255])
+            } else {
+                image::Rgba([0, 0, 0, 255])
+            }
+        });
+        let chain = cpu_chain(&image, mip_level_count(8, 4));
+        let sizes = chain
+            .iter()
+            .map(|level| level.dimensions())
+            .collect::<Vec<_>>();
+        assert_eq!(sizes, vec![(4, 2), (2, 1), (1, 1)]);
+        let gray = chain[2].get_pixel(0, 0).0[0];
+        assert!((100..=155).contains(&gray));
+    }
+}
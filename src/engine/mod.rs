@@ -1,20 +1,33 @@
+pub mod animation;
 pub mod bindgroup;
 pub mod camera;
+pub mod capture;
+pub mod compute_pipeline;
 pub mod config;
 pub mod model;
+pub mod plugin;
+pub mod reload;
+pub mod render_graph;
 pub mod render_pipeline;
 pub mod shader;
+pub mod skin;
+pub mod storage;
 pub mod texture;
+pub mod transform;
 pub mod vertex;
 
 pub mod controller;
 
 pub mod entity;
+pub mod instance;
+pub mod light;
 pub mod material;
 pub mod mesh;
+pub mod mipmap;
 pub mod renderer;
 pub mod resources;
 pub mod scene;
+pub mod shadow;
 
 use std::sync::Arc;
 
@@ -24,15 +37,26 @@ use winit::{dpi::PhysicalSize, window::Window};
 
 use crate::engine::{
     bindgroup::BindGroupInfo,
+    capture::FrameCapture,
+    compute_pipeline::ComputePipelineInfo,
     config::GraphConfig,
     controller::Controller,
+    plugin::{Plugin, PluginContext},
+    reload::GraphWatcher,
+    render_graph::RenderGraph,
     render_pipeline::RenderPipelineInfo,
     renderer::Renderer,
     shader::ShaderInfo,
-    texture::{Texture, TextureInfo},
+    texture::TextureInfo,
     vertex::VertexBufferInfo,
 };
 
+const GRAPH_CONFIG_PATH: &str = "./src/config/graph.toml";
+const CAPTURE_DIR: &str = "./captures";
+// F9 records a clip of this many frames at 30 frames per second
+const RECORD_FRAMES: u32 = 120;
+const RECORD_TIMESTEP: std::time::Duration = std::time::Duration::from_nanos(33_333_333);
+
 #[allow(unused)]
 pub struct WgpuAppGraphResource {
     pub graph_config: GraphConfig,
@@ -41,6 +65,64 @@ pub struct WgpuAppGraphResource {
     pub vertex_buffer_info: VertexBufferInfo,
     pub bind_group_info: BindGroupInfo,
     pub render_pipeline_info: RenderPipelineInfo,
+    pub compute_pipeline_info: ComputePipelineInfo,
+    pub render_graph: RenderGraph,
+}
+
+impl WgpuAppGraphResource {
+    // Build every layout, shader and pipeline of the graph, wgpu validation errors included
+    pub async fn new(app_surface: &AppSurface, graph_config: GraphConfig) -> anyhow::Result<Self> {
+        let device = &app_surface.device;
+        device.push_error_scope(wgpu::ErrorFilter::Validation);
+
+        let mut vertex_buffer_info = VertexBufferInfo::new();
+        vertex_buffer_info.setup_config(&graph_config);
+        let mut bind_group_info = BindGroupInfo::new();
+        bind_group_info.setup(&graph_config, device);
+
+        let mut texture = TextureInfo::new();
+        texture.sample_count = graph_config.sample_count();
+        texture.setup(device, &app_surface.config);
+
+        let mut shader = ShaderInfo::new();
+        let mut render_pipeline_info = RenderPipelineInfo::new();
+        let mut compute_pipeline_info = ComputePipelineInfo::new();
+        let mut render_graph = RenderGraph::new();
+        let result = shader
+            .load_config(device, &graph_config)
+            .and_then(|_| {
+                render_pipeline_info.setup(
+                    device,
+                    &app_surface.config,
+                    &graph_config,
+                    &shader,
+                    &vertex_buffer_info,
+                    &bind_group_info,
+                )
+            })
+            .and_then(|_| {
+                compute_pipeline_info.setup(device, &graph_config, &shader, &bind_group_info)
+            })
+            .and_then(|_| {
+                render_graph.setup(device, &app_surface.config, &graph_config, &bind_group_info)
+            });
+
+        if let Some(err) = device.pop_error_scope().await {
+            return Err(anyhow::anyhow!("{err}"));
+        }
+        result?;
+
+        Ok(Self {
+            graph_config,
+            texture,
+            shader,
+            vertex_buffer_info,
+            bind_group_info,
+            render_pipeline_info,
+            compute_pipeline_info,
+            render_graph,
+        })
+    }
 }
 
 pub struct WgpuApp {
@@ -50,38 +132,149 @@ pub struct WgpuApp {
     pub controller: Controller,
     pub graph_resource: WgpuAppGraphResource,
     pub renderer: Renderer,
+    plugins: Vec<Box<dyn Plugin>>,
+    graph_watcher: GraphWatcher,
+    reload_requested: bool,
     timer: std::time::Duration,
+    capture: FrameCapture,
 }
 
+#[allow(unused)]
 impl WgpuApp {
+    pub fn register_plugin(&mut self, mut plugin: Box<dyn Plugin>) -> anyhow::Result<()> {
+        self.register_plugin_impl(&mut plugin)?;
+        self.plugins.push(plugin);
+        Ok(())
+    }
+
+    pub fn unregister_plugin(&mut self, name: &str) -> bool {
+        let Some(idx) = self.plugins.iter().position(|plugin| plugin.name() == name) else {
+            return false;
+        };
+        let mut plugin = self.plugins.remove(idx);
+        let mut ctx = PluginContext {
+            app_surface: &self.app_surface,
+            graph_resource: &self.graph_resource,
+            renderer: &mut self.renderer,
+        };
+        plugin.teardown(&mut ctx);
+        true
+    }
+
+    // Swap in a freshly built graph, the current one stays in use if anything fails
+    pub fn reload_graph(&mut self) {
+        let graph_config = match GraphConfig::new(GRAPH_CONFIG_PATH) {
+            Ok(config) => config,
+            Err(errors) => {
+                eprintln!("{GRAPH_CONFIG_PATH}: {errors}");
+                return;
+            }
+        };
+        self.graph_watcher.watch(GRAPH_CONFIG_PATH, &graph_config);
+
+        // Bind groups held by models and the camera only stay valid for identical layouts,
+        // the shadow maps of the lights are sized by `[shadows]`
+        let layouts_changed = graph_config.resources.bindgroups
+            != self.graph_resource.graph_config.resources.bindgroups
+            || graph_config.shadows != self.graph_resource.graph_config.shadows;
+
+        let graph_resource = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .map_err(anyhow::Error::from)
+            .and_then(|rt| rt.block_on(WgpuAppGraphResource::new(&self.app_surface, graph_config)));
+        let graph_resource = match graph_resource {
+            Ok(graph_resource) => graph_resource,
+            Err(err) => {
+                eprintln!("Reload failed, keeping the current pipelines: {err:?}");
+                return;
+            }
+        };
+
+        if !layouts_changed {
+            self.graph_resource = graph_resource;
+            println!("Reloaded {GRAPH_CONFIG_PATH}");
+            return;
+        }
+
+        // Plugins rebuild their models against the new layouts
+        let mut plugins = std::mem::take(&mut self.plugins);
+        for plugin in plugins.iter_mut().rev() {
+            plugin.teardown(&mut PluginContext {
+                app_surface: &self.app_surface,
+                graph_resource: &self.graph_resource,
+                renderer: &mut self.renderer,
+            });
+        }
+
+        self.graph_resource = graph_resource;
+        self.renderer = Renderer::new(&self.app_surface, &self.graph_resource);
+
+        for mut plugin in plugins {
+            if let Err(err) = self.register_plugin_impl(&mut plugin) {
+                eprintln!("Plugin {:?} reload failed: {err:?}", plugin.name());
+            }
+            self.plugins.push(plugin);
+        }
+        println!("Reloaded {GRAPH_CONFIG_PATH} and rebuilt the scene");
+    }
+
+    // Saves the next frame as a PNG, by default into `CAPTURE_DIR`
+    pub fn screenshot(&mut self, path: Option<&std::path::Path>) {
+        self.capture.screenshot(path);
+    }
+
+    // Saves the next `count` frames as a numbered PNG sequence, the scene advancing by
+    // `timestep` every frame
+    pub fn record_frames(&mut self, count: u32, timestep: std::time::Duration) {
+        self.capture.record(count, timestep);
+    }
+
+    pub fn stop_recording(&mut self) {
+        self.capture.stop_recording();
+    }
+
+    fn register_plugin_impl(&mut self, plugin: &mut Box<dyn Plugin>) -> anyhow::Result<()> {
+        let mut ctx = PluginContext {
+            app_surface: &self.app_surface,
+            graph_resource: &self.graph_resource,
+            renderer: &mut self.renderer,
+        };
+        plugin.probe(&mut ctx)
+    }
+
     fn resize_surface_if_needed(&mut self) {
         if self.size_changed {
-            self.app_surface.config.width = self.size.width;
-            self.app_surface.config.height = self.size.height;
             self.app_surface
-                .surface
-                .configure(&self.app_surface.device, &self.app_surface.config);
+                .resize(self.size.width, self.size.height);
             self.size_changed = false;
-            self.graph_resource
+            let graph_resource = &mut self.graph_resource;
+            graph_resource
                 .texture
-                .depth_texture
-                .replace(Texture::create_depth_texture(
-                    &self.app_surface.device,
-                    &self.app_surface.config,
-                ));
+                .setup(&self.app_surface.device, &self.app_surface.config);
+            if let Err(err) = graph_resource.render_graph.setup(
+                &self.app_surface.device,
+                &self.app_surface.config,
+                &graph_resource.graph_config,
+                &graph_resource.bind_group_info,
+            ) {
+                eprintln!("Failed to resize the render graph targets: {err:?}");
+            }
         }
     }
 }
 
 impl WgpuAppAction for WgpuApp {
     async fn new(window: std::sync::Arc<Window>) -> Arc<std::sync::Mutex<Self>> {
-        let app_surface = AppSurface::new(window).await.unwrap();
+        // Validate the graph before any GPU object is created
+        let graph_config = match GraphConfig::new(GRAPH_CONFIG_PATH) {
+            Ok(config) => config,
+            Err(errors) => {
+                eprintln!("{GRAPH_CONFIG_PATH}: {errors}");
+                std::process::exit(1);
+            }
+        };
 
-        let graph_config = GraphConfig::new("./src/config/graph.toml");
-        let mut vertex_buffer_info = VertexBufferInfo::new();
-        vertex_buffer_info.setup_config(&graph_config);
-        let mut bind_group_info = BindGroupInfo::new();
-        bind_group_info.setup(&graph_config, &app_surface.device);
+        let app_surface = AppSurface::new(window).await.unwrap();
 
         let size = PhysicalSize {
             width: app_surface.config.width,
@@ -90,33 +283,11 @@ impl WgpuAppAction for WgpuApp {
 
         let controller = Controller::new();
 
-        let mut texture = TextureInfo::new();
-        texture.setup(&app_surface.device, &app_surface.config);
-
-        let mut shader = ShaderInfo::new();
-        shader.load_config(&app_surface.device, &graph_config);
-
-        let mut render_pipeline_info = RenderPipelineInfo::new();
-        render_pipeline_info
-            .setup(
-                &app_surface.device,
-                &app_surface.config,
-                &graph_config,
-                &shader,
-                &vertex_buffer_info,
-                &bind_group_info,
-            )
+        let graph_watcher = GraphWatcher::new(GRAPH_CONFIG_PATH, &graph_config);
+        let graph_resource = WgpuAppGraphResource::new(&app_surface, graph_config)
+            .await
             .unwrap();
 
-        let graph_resource = WgpuAppGraphResource {
-            graph_config,
-            texture,
-            shader,
-            vertex_buffer_info,
-            bind_group_info,
-            render_pipeline_info,
-        };
-
         let renderer = Renderer::new(&app_surface, &graph_resource);
 
         let app = Self {
@@ -126,7 +297,11 @@ impl WgpuAppAction for WgpuApp {
             controller,
             graph_resource,
             renderer,
+            plugins: Vec::new(),
+            graph_watcher,
+            reload_requested: false,
             timer: std::time::Duration::ZERO,
+            capture: FrameCapture::new(CAPTURE_DIR),
         };
 
         let arc_app = Arc::new(std::sync::Mutex::new(app));
@@ -149,6 +324,32 @@ impl WgpuAppAction for WgpuApp {
     }
 
     fn keyboard_input(&mut self, event: &winit::event::KeyEvent, _is_synthetic: bool) -> bool {
+        // F5 reloads the render graph and shaders
+        if event.state == winit::event::ElementState::Pressed
+            && event.physical_key
+                == winit::keyboard::PhysicalKey::Code(winit::keyboard::KeyCode::F5)
+        {
+            self.reload_requested = true;
+            return true;
+        }
+        // F12 saves a screenshot, F9 starts or stops recording a frame sequence
+        if event.state == winit::event::ElementState::Pressed && !event.repeat {
+            match event.physical_key {
+                winit::keyboard::PhysicalKey::Code(winit::keyboard::KeyCode::F12) => {
+                    self.screenshot(None);
+                    return true;
+                }
+                winit::keyboard::PhysicalKey::Code(winit::keyboard::KeyCode::F9) => {
+                    if self.capture.is_recording() {
+                        self.stop_recording();
+                    } else {
+                        self.record_frames(RECORD_FRAMES, RECORD_TIMESTEP);
+                    }
+                    return true;
+                }
+                _ => {}
+            }
+        }
         self.controller.parse_key_event(event)
         // self.camera.controller.process_event(event)
 
@@ -158,8 +359,27 @@ impl WgpuAppAction for WgpuApp {
     fn update(&mut self, dt: std::time::Duration) {
         // self.camera.controller.process_event(&self.controller);
         // self.camera.update();
+        let dt = self.capture.frame_time(dt);
         self.timer += dt;
 
+        if self.graph_watcher.poll(dt) || self.reload_requested {
+            self.reload_requested = false;
+            self.reload_graph();
+        }
+
+        let mut ctx = PluginContext {
+            app_surface: &self.app_surface,
+            graph_resource: &self.graph_resource,
+            renderer: &mut self.renderer,
+        };
+        for plugin in self.plugins.iter_mut() {
+            if let Err(err) = plugin.update(&mut ctx, dt) {
+                eprintln!("Plugin {:?} update failed: {err:?}", plugin.name());
+            }
+        }
+
+        self.renderer.update(&self.app_surface, dt);
+
         // self.app_surface.queue.write_buffer(
         //     self.camera.buffer.as_ref().unwrap(),
         //     0,
@@ -172,8 +392,35 @@ impl WgpuAppAction for WgpuApp {
             return Ok(());
         }
         self.resize_surface_if_needed();
-        self.renderer
-            .render(&self.app_surface, &self.graph_resource);
+        let frame = self
+            .renderer
+            .render_frame(&self.app_surface, &self.graph_resource);
+        match self.capture.capture(
+            &self.app_surface.device,
+            &self.app_surface.queue,
+            frame.texture(),
+        ) {
+            Ok(paths) => {
+                for path in paths {
+                    println!("Saved {}", path.display());
+                }
+            }
+            Err(err) => eprintln!("Frame capture failed: {err:?}"),
+        }
+        frame.present();
         Ok(())
     }
 }
+
+impl Drop for WgpuApp {
+    fn drop(&mut self) {
+        let mut ctx = PluginContext {
+            app_surface: &self.app_surface,
+            graph_resource: &self.graph_resource,
+            renderer: &mut self.renderer,
+        };
+        for plugin in self.plugins.iter_mut().rev() {
+            plugin.teardown(&mut ctx);
+        }
+    }
+}
@@ -1,73 +1,280 @@
+use anyhow::anyhow;
+
 use crate::engine::{
+    animation::{AnimationPlayer, apply_animation},
     entity::Entity,
+    instance::InstanceSet,
     material::Material,
     mesh::{Mesh, Primitive},
+    resources::{self, Animation},
+    skin::Skin,
+    transform::EntityTransforms,
 };
 
 pub struct Model {
+    // render pipeline tag from graph.toml, the scene name is used when unset
+    pub pipeline: Option<String>,
     pub root_entity: Entity,
     pub entities: Vec<Entity>,
     pub meshes: Vec<Mesh>,
     pub materials: Vec<Material>,
+    pub skins: Vec<Skin>,
+    pub transforms: EntityTransforms,
+    pub instances: InstanceSet,
+    pub animations: Vec<Animation>,
+    pub players: Vec<AnimationPlayer>,
+    // attached to entities through `camera_index` and `light_index`
+    pub cameras: Vec<resources::Camera>,
+    pub lights: Vec<resources::Light>,
 }
 
 #[allow(unused)]
 impl Model {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         meshes: Vec<Mesh>,
         materials: Vec<Material>,
         entities: Vec<Entity>,
         root_entity: Entity,
+        skins: Vec<Skin>,
+        transforms: EntityTransforms,
+        instances: InstanceSet,
+        animations: Vec<Animation>,
     ) -> Self {
-        Self {
+        let mut model = Self {
+            pipeline: None,
             root_entity,
             entities,
             meshes,
             materials,
+            skins,
+            transforms,
+            instances,
+            animations,
+            players: Vec::new(),
+            cameras: Vec::new(),
+            lights: Vec::new(),
+        };
+        model.update_world_transforms();
+        model
+    }
+
+    pub fn set_pipeline(&mut self, tag: &str) {
+        self.pipeline.replace(tag.to_string());
+    }
+
+    pub fn play_animation(&mut self, name: &str, looping: bool) -> anyhow::Result<()> {
+        let index = self
+            .animations
+            .iter()
+            .position(|animation| animation.name.as_deref() == Some(name))
+            .ok_or_else(|| anyhow!("Animation not found: {name}"))?;
+
+        self.players
+            .retain(|player| player.animation_index != index);
+        self.players.push(AnimationPlayer::new(index, looping));
+        Ok(())
+    }
+
+    pub fn stop_animation(&mut self, name: &str) {
+        let animations = &self.animations;
+        self.players
+            .retain(|player| animations[player.animation_index].name.as_deref() != Some(name));
+    }
+
+    pub fn seek_animation(&mut self, name: &str, time: f32) -> anyhow::Result<()> {
+        let player = self
+            .players
+            .iter_mut()
+            .find(|player| self.animations[player.animation_index].name.as_deref() == Some(name))
+            .ok_or_else(|| anyhow!("Animation is not playing: {name}"))?;
+        player.time = time;
+        Ok(())
+    }
+
+    pub fn update_world_transforms(&mut self) {
+        self.root_entity.world_transform = self.root_entity.transform;
+
+        let mut stack = self
+            .root_entity
+            .children
+            .iter()
+            .map(|&idx| (idx, self.root_entity.world_transform))
+            .collect::<Vec<_>>();
+        while let Some((idx, parent)) = stack.pop() {
+            let entity = &mut self.entities[idx];
+            entity.world_transform = parent * entity.transform;
+            stack.extend(
+                entity
+                    .children
+                    .iter()
+                    .map(|&child| (child, entity.world_transform)),
+            );
         }
     }
 
-    pub fn render<'a>(&self, render_pass: &mut wgpu::RenderPass<'a>) {
-        self.render_impl(&self.root_entity, render_pass);
+    // World transform of an entity placed by the first instance
+    pub fn world_transform(&self, entity_index: usize) -> cgmath::Matrix4<f32> {
+        let instance = self
+            .instances
+            .iter()
+            .next()
+            .map(|instance| instance.as_model())
+            .unwrap_or(cgmath::SquareMatrix::identity());
+        instance * self.entities[entity_index].world_transform
     }
 
-    pub fn render_impl<'a>(&self, entity: &Entity, render_pass: &mut wgpu::RenderPass<'a>) {
-        // Scene -> Model -> entity -> mesh -> primative -> [material, vertex]
-        for &eidx in entity.children.iter() {
-            let entity = &self.entities[eidx];
+    // (entity index, camera) of every entity carrying a camera
+    pub fn camera_entities(&self) -> Vec<(usize, &resources::Camera)> {
+        self.entities
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, entity)| Some((idx, self.cameras.get(entity.camera_index?)?)))
+            .collect()
+    }
+
+    // (entity index, light) of every entity carrying a light
+    pub fn light_entities(&self) -> Vec<(usize, &resources::Light)> {
+        self.entities
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, entity)| Some((idx, self.lights.get(entity.light_index?)?)))
+            .collect()
+    }
+
+    pub fn update(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, dt: std::time::Duration) {
+        let dt = dt.as_secs_f32();
+        for player in self.players.iter_mut() {
+            let animation = &self.animations[player.animation_index];
+            player.advance(dt, animation.duration);
+            apply_animation(animation, player.time, &mut self.entities);
+        }
+
+        let animations = &self.animations;
+        self.players
+            .retain(|player| !player.is_finished(animations[player.animation_index].duration));
+
+        self.update_world_transforms();
+        self.upload(device, queue);
+    }
 
+    pub fn upload(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) {
+        self.instances.upload(device, queue);
+        self.transforms.update(queue, &self.entities);
+        for skin in self.skins.iter() {
+            skin.update(queue, &self.entities);
+        }
+    }
+
+    // Every drawable primitive of the hierarchy, in depth first order
+    pub fn primitives(&self) -> Vec<PrimitiveRef> {
+        let mut primitives = Vec::new();
+        let mut stack = self
+            .root_entity
+            .children
+            .iter()
+            .rev()
+            .copied()
+            .collect::<Vec<_>>();
+        while let Some(eidx) = stack.pop() {
+            let entity = &self.entities[eidx];
             if let Some(mesh_idx) = entity.mesh_index {
-                let mesh = &self.meshes[mesh_idx];
-                for primative in mesh.primitives.iter() {
-                    // material
-                    let material = &self.materials[primative.material_index];
-                    render_pass.set_bind_group(
-                        Material::BIND_GROUP_INDEX,
-                        &material.bind_group,
-                        &[],
-                    );
-
-                    // Vertex
-                    render_pass.set_vertex_buffer(
-                        Primitive::POSITION_LOCATION,
-                        primative.positions.slice(..),
-                    );
-                    render_pass.set_vertex_buffer(
-                        Primitive::TEX_COORDS_LOCATION,
-                        primative.tex_coords.slice(..),
-                    );
-                    render_pass
-                        .set_vertex_buffer(Primitive::NORMAL_LOCATION, primative.normals.slice(..));
-
-                    // Indices
-                    render_pass
-                        .set_index_buffer(primative.indices.slice(..), wgpu::IndexFormat::Uint32);
-
-                    render_pass.draw_indexed(0..primative.indices_num, 0, 0..1);
+                for (pidx, primative) in self.meshes[mesh_idx].primitives.iter().enumerate() {
+                    primitives.push(PrimitiveRef {
+                        entity: eidx,
+                        mesh: mesh_idx,
+                        primitive: pidx,
+                        material: primative.material_index,
+                    });
                 }
             }
+            stack.extend(entity.children.iter().rev());
+        }
+        primitives
+    }
+
+    // Pipeline tag of a material, falling back to the one of the model
+    pub fn pipeline_of(&self, material_index: usize) -> Option<&str> {
+        self.materials
+            .get(material_index)
+            .and_then(|material| material.pipeline.as_deref())
+            .or(self.pipeline.as_deref())
+    }
+
+    pub fn render<'a>(&self, render_pass: &mut wgpu::RenderPass<'a>) {
+        if self.instances.is_empty() || self.instances.buffer().is_none() {
+            return;
+        }
+        self.bind_instances(render_pass);
+        for primitive in self.primitives() {
+            self.bind_transform(primitive.entity, render_pass);
+            self.bind_material(primitive.material, render_pass);
+            self.draw_primitive(&primitive, render_pass);
+        }
+    }
+
+    pub fn bind_instances<'a>(&self, render_pass: &mut wgpu::RenderPass<'a>) {
+        if let Some(buffer) = self.instances.buffer() {
+            render_pass.set_vertex_buffer(Primitive::INSTANCE_LOCATION, buffer.slice(..));
+        }
+    }
+
+    pub fn bind_transform<'a>(&self, entity_index: usize, render_pass: &mut wgpu::RenderPass<'a>) {
+        // skinned meshes take their placement from the joints only
+        if let Some(skin_idx) = self.entities[entity_index].skin_index {
+            render_pass.set_bind_group(
+                Skin::BIND_GROUP_INDEX,
+                &self.skins[skin_idx].bind_group,
+                &[],
+            );
+        } else {
+            render_pass.set_bind_group(
+                EntityTransforms::BIND_GROUP_INDEX,
+                &self.transforms.bind_group,
+                &[self.transforms.offset(entity_index)],
+            );
+        }
+    }
+
+    pub fn bind_material<'a>(&self, material_index: usize, render_pass: &mut wgpu::RenderPass<'a>) {
+        render_pass.set_bind_group(
+            Material::BIND_GROUP_INDEX,
+            &self.materials[material_index].bind_group,
+            &[],
+        );
+    }
+
+    pub fn draw_primitive<'a>(
+        &self,
+        primitive: &PrimitiveRef,
+        render_pass: &mut wgpu::RenderPass<'a>,
+    ) {
+        let primative = &self.meshes[primitive.mesh].primitives[primitive.primitive];
 
-            self.render_impl(entity, render_pass);
+        // Vertex
+        render_pass.set_vertex_buffer(Primitive::POSITION_LOCATION, primative.positions.slice(..));
+        render_pass.set_vertex_buffer(
+            Primitive::TEX_COORDS_LOCATION,
+            primative.tex_coords.slice(..),
+        );
+        render_pass.set_vertex_buffer(Primitive::NORMAL_LOCATION, primative.normals.slice(..));
+        if let (Some(joints), Some(weights)) = (&primative.joints, &primative.weights) {
+            render_pass.set_vertex_buffer(Primitive::JOINTS_LOCATION, joints.slice(..));
+            render_pass.set_vertex_buffer(Primitive::WEIGHTS_LOCATION, weights.slice(..));
         }
+
+        // Indices
+        render_pass.set_index_buffer(primative.indices.slice(..), wgpu::IndexFormat::Uint32);
+
+        render_pass.draw_indexed(0..primative.indices_num, 0, self.instances.range());
     }
 }
+
+// Scene -> Model -> entity -> mesh -> primative -> [material, vertex]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PrimitiveRef {
+    pub entity: usize,
+    pub mesh: usize,
+    pub primitive: usize,
+    pub material: usize,
+}
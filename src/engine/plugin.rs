@@ -0,0 +1,28 @@
+use std::time::Duration;
+
+use wgpu_util::hal::AppSurface;
+
+use crate::engine::{WgpuAppGraphResource, renderer::Renderer};
+
+pub struct PluginContext<'a> {
+    pub app_surface: &'a AppSurface,
+    pub graph_resource: &'a WgpuAppGraphResource,
+    pub renderer: &'a mut Renderer,
+}
+
+// Game modules hooked into `WgpuApp`
+#[allow(unused)]
+pub trait Plugin: Send {
+    fn name(&self) -> &str;
+
+    // Called once when registered, load resources and add models here
+    fn probe(&mut self, ctx: &mut PluginContext) -> anyhow::Result<()>;
+
+    // Called every frame before the scene is updated
+    fn update(&mut self, ctx: &mut PluginContext, dt: Duration) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    // Called when unregistered or when the app is dropped
+    fn teardown(&mut self, ctx: &mut PluginContext) {}
+}
@@ -0,0 +1,102 @@
+use std::{
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use crate::engine::{config::GraphConfig, shader::SHADER_DIR};
+
+// Polls the modification time of graph.toml and every shader it references
+#[allow(unused)]
+pub struct GraphWatcher {
+    files: Vec<(PathBuf, Option<SystemTime>)>,
+    elapsed: Duration,
+}
+
+#[allow(unused)]
+impl GraphWatcher {
+    pub const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+    pub fn new(config_path: &str, graph_config: &GraphConfig) -> Self {
+        let mut watcher = Self {
+            files: Vec::new(),
+            elapsed: Duration::ZERO,
+        };
+        watcher.watch(config_path, graph_config);
+        watcher
+    }
+
+    pub fn watch(&mut self, config_path: &str, graph_config: &GraphConfig) {
+        let mut paths = vec![PathBuf::from(config_path)];
+        paths.extend(
+            graph_config
+                .resources
+                .shaders
+                .values()
+                .map(|shader| Path::new(SHADER_DIR).join(&shader.filename)),
+        );
+        paths.sort();
+        paths.dedup();
+
+        self.files = paths
+            .into_iter()
+            .map(|path| {
+                let modified = Self::modified(&path);
+                (path, modified)
+            })
+            .collect();
+    }
+
+    // True once per change, checked at most every POLL_INTERVAL
+    pub fn poll(&mut self, dt: Duration) -> bool {
+        self.elapsed += dt;
+        if self.elapsed < Self::POLL_INTERVAL {
+            return false;
+        }
+        self.elapsed = Duration::ZERO;
+
+        let mut changed = false;
+        for (path, modified) in self.files.iter_mut() {
+            let current = Self::modified(path);
+            if current != *modified {
+                *modified = current;
+                changed = true;
+            }
+        }
+        changed
+    }
+
+    fn modified(path: &Path) -> Option<SystemTime> {
+        std::fs::metadata(path)
+            .and_then(|meta| meta.modified())
+            .ok()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn poll_detects_change() {
+        let path = std::env::temp_dir().join("d2game_reload_test.toml");
+        std::fs::write(&path, "").unwrap();
+
+        let mut watcher = GraphWatcher {
+            files: Vec::new(),
+            elapsed: Duration::ZERO,
+        };
+        watcher
+            .files
+            .push((path.clone(), GraphWatcher::modified(&path)));
+        assert!(!watcher.poll(GraphWatcher::POLL_INTERVAL));
+
+        let file = std::fs::File::options().write(true).open(&path).unwrap();
+        file.set_modified(SystemTime::now() + Duration::from_secs(10))
+            .unwrap();
+
+        assert!(!watcher.poll(Duration::from_millis(1)));
+        assert!(watcher.poll(GraphWatcher::POLL_INTERVAL));
+        assert!(!watcher.poll(GraphWatcher::POLL_INTERVAL));
+        std::fs::remove_file(&path).unwrap();
+    }
+}
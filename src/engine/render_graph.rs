@@ -0,0 +1,284 @@
+use std::collections::HashMap;
+
+use anyhow::anyhow;
+
+use crate::engine::{
+    bindgroup::BindGroupInfo,
+    config::{
+        BindGroupEntryType, GraphConfig, LoadOp, PassConfig, SURFACE_TARGET, StoreOp, TargetConfig,
+        TargetFormat,
+    },
+    render_pipeline::RenderPipelineInfo,
+    scene::Scene,
+    texture::Texture,
+};
+
+// Named targets of `graph.toml` and the bind groups passes sample them through,
+// recreated whenever the surface is resized
+#[allow(unused)]
+pub struct RenderGraph {
+    pub targets: HashMap<String, Texture>,
+    bind_groups: HashMap<String, wgpu::BindGroup>,
+}
+
+#[allow(unused)]
+impl RenderGraph {
+    pub fn new() -> Self {
+        Self {
+            targets: HashMap::new(),
+            bind_groups: HashMap::new(),
+        }
+    }
+
+    fn get_size(target: &TargetConfig, config: &wgpu::SurfaceConfiguration) -> (u32, u32) {
+        match target.size {
+            Some([width, height]) => (width, height),
+            None => (
+                (config.width as f32 * target.scale) as u32,
+                (config.height as f32 * target.scale) as u32,
+            ),
+        }
+    }
+
+    fn get_format(
+        target: &TargetConfig,
+        config: &wgpu::SurfaceConfiguration,
+    ) -> wgpu::TextureFormat {
+        match target.format {
+            TargetFormat::Color(format) => RenderPipelineInfo::get_color_format(format, config),
+            TargetFormat::Depth(format) => RenderPipelineInfo::get_depth_format(Some(format)),
+        }
+    }
+
+    pub fn setup(
+        &mut self,
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        graph_config: &GraphConfig,
+        bind_group_info: &BindGroupInfo,
+    ) -> anyhow::Result<()> {
+        self.targets.clear();
+        for (name, target) in graph_config.targets.iter() {
+            let texture = Texture::create_render_target(
+                device,
+                name,
+                Self::get_size(target, config),
+                Self::get_format(target, config),
+                target.sample_count,
+            );
+            self.targets.insert(name.clone(), texture);
+        }
+
+        self.bind_groups.clear();
+        for pass in graph_config.passes.iter() {
+            let Some(samples) = pass.samples.as_ref() else {
+                continue;
+            };
+            let layout = bind_group_info.get(&samples.bindgroup).ok_or_else(|| {
+                anyhow!("{}: unknown bindgroup `{}`", pass.name, samples.bindgroup)
+            })?;
+
+            let mut entries = graph_config.resources.bindgroups[&samples.bindgroup]
+                .entries
+                .iter()
+                .collect::<Vec<_>>();
+            entries.sort_by_key(|entry| entry.binding);
+
+            // depth targets are sampled through their depth aspect only
+            let views = samples
+                .targets
+                .iter()
+                .map(|name| {
+                    let target = &self.targets[name];
+                    target
+                        .texture
+                        .create_view(&wgpu::wgt::TextureViewDescriptor {
+                            aspect: if target.texture.format().is_depth_stencil_format() {
+                                wgpu::TextureAspect::DepthOnly
+                            } else {
+                                wgpu::TextureAspect::All
+                            },
+                            ..Default::default()
+                        })
+                })
+                .collect::<Vec<_>>();
+            let mut views = views.iter();
+            let sampler = device.create_sampler(&wgpu::wgt::SamplerDescriptor {
+                mag_filter: wgpu::FilterMode::Linear,
+                min_filter: wgpu::FilterMode::Linear,
+                ..Default::default()
+            });
+            let compare_sampler = device.create_sampler(&wgpu::wgt::SamplerDescriptor {
+                mag_filter: wgpu::FilterMode::Linear,
+                min_filter: wgpu::FilterMode::Linear,
+                compare: Some(wgpu::CompareFunction::LessEqual),
+                ..Default::default()
+            });
+
+            let mut bind_group_entries = Vec::new();
+            for entry in entries {
+                let resource = match entry.ty {
+                    BindGroupEntryType::Texture | BindGroupEntryType::DepthTexture => {
+                        let view = views.next().ok_or_else(|| {
+                            anyhow!(
+                                "{}: more textures in `{}` than sampled targets",
+                                pass.name,
+                                samples.bindgroup
+                            )
+                        })?;
+                        wgpu::BindingResource::TextureView(view)
+                    }
+                    BindGroupEntryType::Sampler => wgpu::BindingResource::Sampler(&sampler),
+                    BindGroupEntryType::ComparisonSampler => {
+                        wgpu::BindingResource::Sampler(&compare_sampler)
+                    }
+                    _ => {
+                        return Err(anyhow!(
+                            "{}: `{}` may only hold textures and samplers",
+                            pass.name,
+                            samples.bindgroup
+                        ));
+                    }
+                };
+                bind_group_entries.push(wgpu::BindGroupEntry {
+                    binding: entry.binding as u32,
+                    resource,
+                });
+            }
+            if views.next().is_some() {
+                return Err(anyhow!(
+                    "{}: `{}` has fewer textures than sampled targets",
+                    pass.name,
+                    samples.bindgroup
+                ));
+            }
+
+            let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some(&pass.name),
+                layout,
+                entries: &bind_group_entries,
+            });
+            self.bind_groups.insert(pass.name.clone(), bind_group);
+        }
+        Ok(())
+    }
+
+    fn get_view<'a>(
+        &'a self,
+        target: &str,
+        surface: &'a wgpu::TextureView,
+    ) -> &'a wgpu::TextureView {
+        if target == SURFACE_TARGET {
+            surface
+        } else {
+            &self.targets[target].view
+        }
+    }
+
+    #[inline]
+    fn get_store(store: StoreOp) -> wgpu::StoreOp {
+        match store {
+            StoreOp::Store => wgpu::StoreOp::Store,
+            StoreOp::Discard => wgpu::StoreOp::Discard,
+        }
+    }
+
+    // Records every pass of `graph.toml` in order, `surface` is the view of the frame
+    pub fn render(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        surface: &wgpu::TextureView,
+        graph_config: &GraphConfig,
+        render_pipeline_info: &RenderPipelineInfo,
+        scene: &Scene,
+    ) {
+        for pass in graph_config.passes.iter() {
+            self.render_pass(encoder, surface, pass, render_pipeline_info, scene);
+        }
+    }
+
+    fn render_pass(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        surface: &wgpu::TextureView,
+        pass: &PassConfig,
+        render_pipeline_info: &RenderPipelineInfo,
+        scene: &Scene,
+    ) {
+        let color_attachments = pass
+            .color
+            .iter()
+            .map(|color| {
+                let [r, g, b, a] = color.clear;
+                Some(wgpu::RenderPassColorAttachment {
+                    view: self.get_view(&color.target, surface),
+                    depth_slice: None,
+                    resolve_target: color
+                        .resolve
+                        .as_ref()
+                        .map(|target| self.get_view(target, surface)),
+                    ops: wgpu::Operations {
+                        load: match color.load {
+                            LoadOp::Clear => wgpu::LoadOp::Clear(wgpu::Color { r, g, b, a }),
+                            LoadOp::Load => wgpu::LoadOp::Load,
+                        },
+                        store: Self::get_store(color.store),
+                    },
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let depth_stencil_attachment = pass.depth.as_ref().map(|depth| {
+            let texture = &self.targets[&depth.target];
+            let stencil_ops =
+                texture
+                    .texture
+                    .format()
+                    .has_stencil_aspect()
+                    .then(|| wgpu::Operations {
+                        load: match depth.load {
+                            LoadOp::Clear => wgpu::LoadOp::Clear(depth.clear_stencil),
+                            LoadOp::Load => wgpu::LoadOp::Load,
+                        },
+                        store: Self::get_store(depth.store),
+                    });
+            wgpu::RenderPassDepthStencilAttachment {
+                view: &texture.view,
+                depth_ops: Some(wgpu::Operations {
+                    load: match depth.load {
+                        LoadOp::Clear => wgpu::LoadOp::Clear(depth.clear),
+                        LoadOp::Load => wgpu::LoadOp::Load,
+                    },
+                    store: Self::get_store(depth.store),
+                }),
+                stencil_ops,
+            }
+        });
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some(&pass.name),
+            color_attachments: &color_attachments,
+            depth_stencil_attachment,
+            ..Default::default()
+        });
+
+        if let (Some(samples), Some(bind_group)) =
+            (pass.samples.as_ref(), self.bind_groups.get(&pass.name))
+        {
+            render_pass.set_bind_group(samples.group, bind_group, &[]);
+        }
+
+        if pass.fullscreen {
+            for name in pass.pipelines.iter() {
+                let Some(pipeline) = render_pipeline_info.get(name) else {
+                    continue;
+                };
+                render_pass.set_pipeline(pipeline);
+                render_pass.draw(0..3, 0..1);
+            }
+        } else {
+            let draws = scene.draws(&pass.pipelines);
+            scene.render(&mut render_pass, render_pipeline_info, &draws);
+        }
+    }
+}
@@ -1,7 +1,15 @@
 use std::collections::HashMap;
 
+use anyhow::anyhow;
+
 use crate::engine::{
-    bindgroup::BindGroupInfo, config::GraphConfig, shader::ShaderInfo, texture::Texture,
+    bindgroup::BindGroupInfo,
+    config::{
+        BlendPreset, ColorFormat, CompareFunction, CullMode, DepthFormat, FrontFace, GraphConfig,
+        PipelineConfig, PolygonMode, PrimitiveTopology, StencilFaceConfig, StencilOperation,
+    },
+    shader::ShaderInfo,
+    texture::Texture,
     vertex::VertexBufferInfo,
 };
 
@@ -9,6 +17,9 @@ use crate::engine::{
 pub struct RenderPipelineInfoInner {
     layout: wgpu::PipelineLayout,
     pipeline: wgpu::RenderPipeline,
+    pub color_format: wgpu::TextureFormat,
+    pub depth: bool,
+    pub sample_count: u32,
 }
 
 pub struct RenderPipelineInfo {
@@ -22,6 +33,164 @@ impl RenderPipelineInfo {
             map: HashMap::new(),
         }
     }
+    #[inline]
+    fn get_topology(topology: PrimitiveTopology) -> wgpu::PrimitiveTopology {
+        match topology {
+            PrimitiveTopology::PointList => wgpu::PrimitiveTopology::PointList,
+            PrimitiveTopology::LineList => wgpu::PrimitiveTopology::LineList,
+            PrimitiveTopology::LineStrip => wgpu::PrimitiveTopology::LineStrip,
+            PrimitiveTopology::TriangleList => wgpu::PrimitiveTopology::TriangleList,
+            PrimitiveTopology::TriangleStrip => wgpu::PrimitiveTopology::TriangleStrip,
+        }
+    }
+
+    #[inline]
+    fn get_cull_mode(cull_mode: CullMode) -> Option<wgpu::Face> {
+        match cull_mode {
+            CullMode::None => None,
+            CullMode::Front => Some(wgpu::Face::Front),
+            CullMode::Back => Some(wgpu::Face::Back),
+        }
+    }
+
+    #[inline]
+    fn get_front_face(front_face: FrontFace) -> wgpu::FrontFace {
+        match front_face {
+            FrontFace::Ccw => wgpu::FrontFace::Ccw,
+            FrontFace::Cw => wgpu::FrontFace::Cw,
+        }
+    }
+
+    #[inline]
+    fn get_polygon_mode(polygon_mode: PolygonMode) -> wgpu::PolygonMode {
+        match polygon_mode {
+            PolygonMode::Fill => wgpu::PolygonMode::Fill,
+            PolygonMode::Line => wgpu::PolygonMode::Line,
+            PolygonMode::Point => wgpu::PolygonMode::Point,
+        }
+    }
+
+    #[inline]
+    fn get_blend(blend: BlendPreset) -> wgpu::BlendState {
+        match blend {
+            BlendPreset::Replace => wgpu::BlendState::REPLACE,
+            BlendPreset::Alpha => wgpu::BlendState::ALPHA_BLENDING,
+            BlendPreset::Premultiplied => wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING,
+            BlendPreset::Additive => wgpu::BlendState {
+                color: wgpu::BlendComponent {
+                    src_factor: wgpu::BlendFactor::SrcAlpha,
+                    dst_factor: wgpu::BlendFactor::One,
+                    operation: wgpu::BlendOperation::Add,
+                },
+                alpha: wgpu::BlendComponent {
+                    src_factor: wgpu::BlendFactor::One,
+                    dst_factor: wgpu::BlendFactor::One,
+                    operation: wgpu::BlendOperation::Add,
+                },
+            },
+        }
+    }
+
+    #[inline]
+    pub fn get_color_format(
+        format: ColorFormat,
+        config: &wgpu::SurfaceConfiguration,
+    ) -> wgpu::TextureFormat {
+        match format {
+            ColorFormat::Surface => wgpu_util::hal::color_format(config),
+            ColorFormat::Rgba8Unorm => wgpu::TextureFormat::Rgba8Unorm,
+            ColorFormat::Rgba8UnormSrgb => wgpu::TextureFormat::Rgba8UnormSrgb,
+            ColorFormat::Bgra8Unorm => wgpu::TextureFormat::Bgra8Unorm,
+            ColorFormat::Bgra8UnormSrgb => wgpu::TextureFormat::Bgra8UnormSrgb,
+            ColorFormat::Rgba16Float => wgpu::TextureFormat::Rgba16Float,
+            ColorFormat::Rgba32Float => wgpu::TextureFormat::Rgba32Float,
+        }
+    }
+
+    #[inline]
+    fn get_compare(compare: CompareFunction) -> wgpu::CompareFunction {
+        match compare {
+            CompareFunction::Never => wgpu::CompareFunction::Never,
+            CompareFunction::Less => wgpu::CompareFunction::Less,
+            CompareFunction::Equal => wgpu::CompareFunction::Equal,
+            CompareFunction::LessEqual => wgpu::CompareFunction::LessEqual,
+            CompareFunction::Greater => wgpu::CompareFunction::Greater,
+            CompareFunction::NotEqual => wgpu::CompareFunction::NotEqual,
+            CompareFunction::GreaterEqual => wgpu::CompareFunction::GreaterEqual,
+            CompareFunction::Always => wgpu::CompareFunction::Always,
+        }
+    }
+
+    #[inline]
+    fn get_stencil_operation(operation: StencilOperation) -> wgpu::StencilOperation {
+        match operation {
+            StencilOperation::Keep => wgpu::StencilOperation::Keep,
+            StencilOperation::Zero => wgpu::StencilOperation::Zero,
+            StencilOperation::Replace => wgpu::StencilOperation::Replace,
+            StencilOperation::Invert => wgpu::StencilOperation::Invert,
+            StencilOperation::IncrementClamp => wgpu::StencilOperation::IncrementClamp,
+            StencilOperation::DecrementClamp => wgpu::StencilOperation::DecrementClamp,
+            StencilOperation::IncrementWrap => wgpu::StencilOperation::IncrementWrap,
+            StencilOperation::DecrementWrap => wgpu::StencilOperation::DecrementWrap,
+        }
+    }
+
+    #[inline]
+    fn get_stencil_face(face: &StencilFaceConfig) -> wgpu::StencilFaceState {
+        wgpu::StencilFaceState {
+            compare: Self::get_compare(face.compare),
+            fail_op: Self::get_stencil_operation(face.fail_op),
+            depth_fail_op: Self::get_stencil_operation(face.depth_fail_op),
+            pass_op: Self::get_stencil_operation(face.pass_op),
+        }
+    }
+
+    pub fn get_depth_format(format: Option<DepthFormat>) -> wgpu::TextureFormat {
+        match format {
+            None => Texture::DEPTH_FORMAT,
+            Some(DepthFormat::Depth32Float) => wgpu::TextureFormat::Depth32Float,
+            Some(DepthFormat::Depth24Plus) => wgpu::TextureFormat::Depth24Plus,
+            Some(DepthFormat::Depth24PlusStencil8) => wgpu::TextureFormat::Depth24PlusStencil8,
+        }
+    }
+
+    fn get_depth_stencil(
+        pipeline: &PipelineConfig,
+        format: wgpu::TextureFormat,
+    ) -> Option<wgpu::DepthStencilState> {
+        if !pipeline.depth_texture {
+            return None;
+        }
+
+        let depth = &pipeline.depth;
+        let stencil = pipeline
+            .stencil
+            .as_ref()
+            .map(|stencil| wgpu::StencilState {
+                front: Self::get_stencil_face(&stencil.front),
+                back: Self::get_stencil_face(&stencil.back),
+                read_mask: stencil.read_mask,
+                write_mask: stencil.write_mask,
+            })
+            .unwrap_or_default();
+
+        Some(wgpu::DepthStencilState {
+            format,
+            depth_write_enabled: depth.write,
+            depth_compare: if depth.test {
+                Self::get_compare(depth.compare)
+            } else {
+                wgpu::CompareFunction::Always
+            },
+            stencil,
+            bias: wgpu::DepthBiasState {
+                constant: depth.bias.constant,
+                slope_scale: depth.bias.slope_scale,
+                clamp: depth.bias.clamp,
+            },
+        })
+    }
+
     pub fn setup(
         &mut self,
         device: &wgpu::Device,
@@ -37,65 +206,80 @@ impl RenderPipelineInfo {
 
             let mut bind_group_layouts = Vec::new();
             for label in &pl.1.bind_group_layouts {
-                bind_group_layouts.push(bind_group_info.get(label).unwrap());
+                bind_group_layouts.push(
+                    bind_group_info
+                        .get(label)
+                        .ok_or_else(|| anyhow!("{nametag}: unknown bindgroup `{label}`"))?,
+                );
             }
 
             let mut vertex_buffer_layouts = Vec::new();
             for layout in &pl.1.vertex_buffer_layouts {
-                vertex_buffer_layouts.push(vertex_buffer_info.get_desc(&layout).unwrap())
+                vertex_buffer_layouts.push(
+                    vertex_buffer_info
+                        .get_desc(layout)
+                        .ok_or_else(|| anyhow!("{nametag}: unknown vertexbuffer `{layout}`"))?,
+                )
             }
 
             let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
                 label: Some(nametag),
-                bind_group_layouts: &bind_group_layouts.as_slice(),
+                bind_group_layouts: bind_group_layouts.as_slice(),
                 push_constant_ranges: &[],
             });
 
-            let shader_config = graph_config.resources.shaders.get(&pl.1.shader).unwrap();
+            let shader_name = &pl.1.shader;
+            let shader_config = graph_config
+                .resources
+                .shaders
+                .get(shader_name)
+                .ok_or_else(|| anyhow!("{nametag}: unknown shader `{shader_name}`"))?;
+            let shader_module = shader
+                .map
+                .get(shader_name)
+                .ok_or_else(|| anyhow!("{nametag}: shader `{shader_name}` is not loaded"))?;
+
+            let color_format = Self::get_color_format(pl.1.color_format, config);
+            let color_targets = [Some(wgpu::ColorTargetState {
+                format: color_format,
+                blend: Some(Self::get_blend(pl.1.blend)),
+                write_mask: wgpu::ColorWrites::ALL,
+            })];
 
             let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
                 label: Some(nametag),
                 layout: Some(&pipeline_layout),
                 vertex: wgpu::VertexState {
-                    module: shader.map.get(&pl.1.shader).unwrap(),
+                    module: shader_module,
                     entry_point: Some(&shader_config.vertex_entry),
                     compilation_options: Default::default(),
                     buffers: &vertex_buffer_layouts,
                 },
-                fragment: Some(wgpu::FragmentState {
-                    module: shader.map.get(&pl.1.shader).unwrap(),
+                // depth only without a fragment entry
+                fragment: (!shader_config.fragment_entry.is_empty()).then(|| wgpu::FragmentState {
+                    module: shader_module,
                     entry_point: Some(&shader_config.fragment_entry),
                     compilation_options: Default::default(),
-                    targets: &[Some(wgpu::ColorTargetState {
-                        format: config.format.add_srgb_suffix(),
-                        blend: Some(wgpu::BlendState {
-                            color: wgpu::BlendComponent::REPLACE,
-                            alpha: wgpu::BlendComponent::REPLACE,
-                        }),
-                        write_mask: wgpu::ColorWrites::ALL,
-                    })],
+                    targets: &color_targets,
                 }),
 
                 primitive: wgpu::PrimitiveState {
-                    topology: wgpu::PrimitiveTopology::TriangleList,
+                    topology: Self::get_topology(pl.1.topology),
                     strip_index_format: None,
-                    front_face: wgpu::FrontFace::Ccw,
-                    cull_mode: Some(wgpu::Face::Back),
+                    front_face: Self::get_front_face(pl.1.front_face),
+                    cull_mode: Self::get_cull_mode(pl.1.cull_mode),
                     unclipped_depth: false,
-                    polygon_mode: wgpu::PolygonMode::Fill,
+                    polygon_mode: Self::get_polygon_mode(pl.1.polygon_mode),
                     conservative: false,
                     // unclipped_depth: false,
                     // conservative: false,
                 },
-                depth_stencil: Some(wgpu::DepthStencilState {
-                    format: Texture::DEPTH_FORMAT,
-                    depth_write_enabled: true,
-                    depth_compare: wgpu::CompareFunction::Less,
-                    stencil: wgpu::StencilState::default(),
-                    bias: wgpu::DepthBiasState::default(),
-                }),
+                depth_stencil: Self::get_depth_stencil(
+                    pl.1,
+                    Self::get_depth_format(graph_config.depth_format(nametag)),
+                ),
                 multisample: wgpu::MultisampleState {
-                    count: 1,
+                    count: pl.1.sample_count,
                     mask: !0,
                     alpha_to_coverage_enabled: false,
                 },
@@ -106,6 +290,9 @@ impl RenderPipelineInfo {
             let inner = RenderPipelineInfoInner {
                 layout: pipeline_layout,
                 pipeline: render_pipeline,
+                color_format,
+                depth: pl.1.depth_texture,
+                sample_count: pl.1.sample_count,
             };
 
             self.map.insert(nametag.to_string(), inner);
@@ -113,6 +300,10 @@ impl RenderPipelineInfo {
         Ok(())
     }
 
+    pub fn get_info(&self, label: &str) -> Option<&RenderPipelineInfoInner> {
+        self.map.get(label)
+    }
+
     pub fn get(&self, label: &str) -> Option<&wgpu::RenderPipeline> {
         if let Some(info) = self.map.get(label) {
             Some(&info.pipeline)
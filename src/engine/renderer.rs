@@ -1,20 +1,34 @@
-use cgmath::SquareMatrix;
-use wgpu_util::hal::AppSurface;
+use anyhow::anyhow;
+use wgpu_util::hal::{AppSurface, Frame};
 
 use crate::engine::{
     WgpuAppGraphResource,
-    camera::{Camera, CameraConfig},
+    camera::{Camera, CameraConfig, Projection},
+    compute_pipeline::ComputeQueue,
     entity::Entity,
-    material::Material,
+    instance::{Instance, InstanceSet},
+    light::{Light, LightSet},
+    material::{Material, MaterialTextures},
     mesh::Mesh,
+    mipmap::MipmapGenerator,
     model::Model,
     resources,
-    scene::Scene,
-    texture::Texture,
+    scene::{DrawPass, ModelId, Scene},
+    skin::Skin,
+    texture::{DefaultTextures, SamplerCache, Texture},
+    transform::EntityTransforms,
 };
 
 pub struct Renderer {
     pub scene: Scene,
+    // dispatched before the render passes of every frame
+    pub computes: ComputeQueue,
+    // shared by the textures of every loaded resource
+    pub samplers: SamplerCache,
+    // mipmap pipelines, one per texture format
+    pub mipmaps: MipmapGenerator,
+    // bound for the maps a material doesn't have
+    pub default_textures: DefaultTextures,
 }
 
 #[allow(unused)]
@@ -35,76 +49,193 @@ impl Renderer {
             fovy: 45.0,
             znear: 0.1,
             zfar: 200.0,
+            projection: Projection::Perspective,
         };
         let camera = Camera::new(app_surface, graph_resource, camera_config);
 
-        let scene = Scene::new(camera);
-        Self { scene }
+        let mut lights = LightSet::new(
+            &app_surface.device,
+            graph_resource.bind_group_info.get("lights").unwrap(),
+            graph_resource.bind_group_info.get("camera").unwrap(),
+            &graph_resource.graph_config.shadows,
+        );
+        // sun over the plane
+        lights.add(
+            Light::directional((-0.3, -1.0, -0.5).into(), (1.0, 0.98, 0.95).into(), 3.0)
+                .with_shadows(),
+        );
+
+        let mut samplers = SamplerCache::new();
+        let mut mipmaps = MipmapGenerator::new(&app_surface.device);
+        let default_textures = DefaultTextures::new(
+            &app_surface.device,
+            &app_surface.queue,
+            &mut samplers,
+            &mut mipmaps,
+        )
+        .expect("Failed to create default textures");
+
+        let scene = Scene::new(camera, lights);
+        Self {
+            scene,
+            computes: ComputeQueue::new(),
+            samplers,
+            mipmaps,
+            default_textures,
+        }
     }
 
-    pub fn add_model(&mut self, model: Model) {
-        self.scene.add_model(model);
+    pub fn add_model(&mut self, model: Model) -> ModelId {
+        self.scene.add_model(model)
     }
 
     pub fn clear_model(&mut self) {
         self.scene.clear_model();
     }
 
+    pub fn update(&mut self, app_surface: &AppSurface, dt: std::time::Duration) {
+        self.scene
+            .update(&app_surface.device, &app_surface.queue, dt);
+    }
+
     pub fn render(&self, app_surface: &AppSurface, graph_resource: &WgpuAppGraphResource) {
+        self.render_frame(app_surface, graph_resource).present();
+    }
+
+    // Renders without presenting, the frame can still be read back, e.g. for screenshots
+    pub fn render_frame(
+        &self,
+        app_surface: &AppSurface,
+        graph_resource: &WgpuAppGraphResource,
+    ) -> Frame {
         // println!("{:}({:})::render()", file!(), line!());
 
         // get previous frame information
         let frame = app_surface
-            .surface
-            .get_current_texture()
+            .get_current_frame()
             .expect("Failed to fetch current texture.");
+        let color_format = wgpu_util::hal::color_format(&app_surface.config);
         let view = frame
-            .texture
+            .texture()
             .create_view(&wgpu::wgt::TextureViewDescriptor {
-                // format: Some(wgpu::TextureFormat::Rgba8UnormSrgb),
+                format: Some(color_format),
                 ..Default::default()
             });
 
         let mut encoder = app_surface
             .device
             .create_command_encoder(&wgpu::wgt::CommandEncoderDescriptor { label: None });
-        {
+        self.computes
+            .record(&mut encoder, &graph_resource.compute_pipeline_info);
+
+        let graph_config = &graph_resource.graph_config;
+        self.scene.lights.shadows.render(
+            &mut encoder,
+            &self.scene,
+            &graph_resource.render_pipeline_info,
+            &graph_config.shadows.pipelines,
+        );
+        if graph_config.passes.is_empty() {
+            self.render_default(&mut encoder, &view, color_format, graph_resource);
+        } else {
+            graph_resource.render_graph.render(
+                &mut encoder,
+                &view,
+                graph_config,
+                &graph_resource.render_pipeline_info,
+                &self.scene,
+            );
+        }
+
+        app_surface.queue.submit(Some(encoder.finish()));
+        frame
+    }
+
+    // Without `passes` in graph.toml: models are drawn into the surface, split into passes
+    // with and without depth
+    fn render_default(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        view: &wgpu::TextureView,
+        color_format: wgpu::TextureFormat,
+        graph_resource: &WgpuAppGraphResource,
+    ) {
+        let textures = &graph_resource.texture;
+        let (color_view, resolve_target) = match textures.msaa_texture.as_ref() {
+            Some(msaa) => (&msaa.view, Some(view)),
+            None => (view, None),
+        };
+        let depth_view = &textures.depth_texture.as_ref().unwrap().view;
+
+        let mut passes = self
+            .scene
+            .draw_list(&graph_resource.render_pipeline_info, color_format);
+        if passes.is_empty() {
+            // still clear the frame
+            passes.push(DrawPass {
+                depth: true,
+                draws: Vec::new(),
+            });
+        }
+
+        let mut depth_cleared = false;
+        for (idx, pass) in passes.iter().enumerate() {
+            let color_load = if idx == 0 {
+                wgpu::LoadOp::Clear(wgpu::Color {
+                    r: 0.1,
+                    g: 0.1,
+                    b: 0.1,
+                    a: 1.0,
+                })
+            } else {
+                wgpu::LoadOp::Load
+            };
+
+            let depth_stencil_attachment = pass.depth.then(|| {
+                let clear = !depth_cleared;
+                depth_cleared = true;
+                wgpu::RenderPassDepthStencilAttachment {
+                    view: depth_view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: if clear {
+                            wgpu::LoadOp::Clear(1.0)
+                        } else {
+                            wgpu::LoadOp::Load
+                        },
+                        store: wgpu::StoreOp::Store,
+                    }),
+                    stencil_ops: Some(wgpu::Operations {
+                        load: if clear {
+                            wgpu::LoadOp::Clear(0)
+                        } else {
+                            wgpu::LoadOp::Load
+                        },
+                        store: wgpu::StoreOp::Store,
+                    }),
+                }
+            });
+
             let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
                 label: Some("Render Pass"),
                 color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &view,
+                    view: color_view,
                     depth_slice: None,
-                    resolve_target: None,
+                    resolve_target,
                     ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color {
-                            r: 0.1,
-                            g: 0.1,
-                            b: 0.1,
-                            a: 1.0,
-                        }),
+                        load: color_load,
                         store: wgpu::StoreOp::Store,
                     },
                 })],
-                // TODO: setup depth texture
-                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
-                    view: &graph_resource.texture.depth_texture.as_ref().unwrap().view,
-                    depth_ops: Some(wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(1.0),
-                        store: wgpu::StoreOp::Store,
-                    }),
-                    stencil_ops: None,
-                }),
+                depth_stencil_attachment,
                 ..Default::default()
             });
 
-            let tag = self.scene.name.as_deref().unwrap_or("default");
-            let pipeline = graph_resource.render_pipeline_info.get(tag).unwrap();
-
-            render_pass.set_pipeline(pipeline);
-            self.scene.render(&mut render_pass);
+            self.scene.render(
+                &mut render_pass,
+                &graph_resource.render_pipeline_info,
+                &pass.draws,
+            );
         }
-        app_surface.queue.submit(Some(encoder.finish()));
-        frame.present();
     }
 
     // consume resource here
@@ -114,29 +245,41 @@ impl Renderer {
         graph_resource: &WgpuAppGraphResource,
         resource: resources::Resource,
         tag: Option<&str>,
-    ) {
-        let textures = resource
-            .textures
-            .iter()
-            .map(|texture| {
-                Texture::load_texture_from_bytes(
+    ) -> anyhow::Result<ModelId> {
+        // each texture once per color space the materials sample it in
+        let mut textures = MaterialTextures::new();
+        for material in resource.materials.iter() {
+            for role in Material::texture_roles(material) {
+                if textures.contains_key(&role) {
+                    continue;
+                }
+                let (idx, color_space) = role;
+                let texture = &resource.textures[idx];
+                let uploaded = Texture::load_texture_from_image(
                     &app_surface.device,
                     &app_surface.queue,
                     texture.name.as_deref(),
                     &resource.images[texture.image_index],
-                )
-                .unwrap()
-            })
-            .collect::<Vec<_>>();
+                    color_space,
+                    &texture.sampler,
+                    &mut self.samplers,
+                    &mut self.mipmaps,
+                )?;
+                textures.insert(role, uploaded);
+            }
+        }
 
         let (bind_group_layout, bind_group_config) = if let Some(tag) = tag {
-            let layout = graph_resource.bind_group_info.get(tag).unwrap();
+            let layout = graph_resource
+                .bind_group_info
+                .get(tag)
+                .ok_or_else(|| anyhow!("Missing bind group layout: {tag}"))?;
             let config = graph_resource
                 .graph_config
                 .resources
                 .bindgroups
                 .get(tag)
-                .unwrap();
+                .ok_or_else(|| anyhow!("Missing bind group config: {tag}"))?;
             (layout, config)
         } else {
             // self.graph_resource.bind_group_info.get("default").unwrap()
@@ -150,36 +293,325 @@ impl Renderer {
                 Material::new(
                     material,
                     &textures,
+                    &self.default_textures,
                     &app_surface.device,
                     bind_group_layout,
                     bind_group_config,
                 )
             })
-            .collect::<Vec<_>>();
+            .collect::<anyhow::Result<Vec<_>>>()?;
 
+        self.load_resources_with_materials(app_surface, graph_resource, resource, materials)
+    }
+
+    // For materials whose bind group is built by the caller
+    pub fn load_resources_with_materials(
+        &mut self,
+        app_surface: &AppSurface,
+        graph_resource: &WgpuAppGraphResource,
+        resource: resources::Resource,
+        materials: Vec<Material>,
+    ) -> anyhow::Result<ModelId> {
         let meshes = resource
             .meshes
             .iter()
             .map(|mesh| Mesh::new(mesh, &app_surface.device))
             .collect::<Vec<_>>();
 
+        let skins = resource
+            .skins
+            .iter()
+            .map(|skin| {
+                let layout = graph_resource
+                    .bind_group_info
+                    .get("skin")
+                    .ok_or_else(|| anyhow!("Missing bind group layout: skin"))?;
+                Skin::new(skin, &app_surface.device, layout)
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
         let entities = resource
             .nodes
             .into_iter()
             .map(|node| node.into())
             .collect::<Vec<Entity>>();
 
+        let entity_count = entities.len();
+
         let root_entity_indices = resource.scenes[resource.default_scene_index].nodes.clone();
 
-        let root_entity = Entity {
-            name: Some("Root".to_string()),
-            mesh_index: None,
-            children: root_entity_indices,
-            transform: cgmath::Matrix4::from_diagonal(cgmath::Vector4::new(-1.0, 1.0, 1.0, 1.0)),
+        let mut root_entity = Entity::new(Some("Root"), root_entity_indices);
+        root_entity.scale = cgmath::Vector3::new(-1.0, 1.0, 1.0);
+        root_entity.update_transform();
+
+        let transforms = EntityTransforms::new(
+            &app_surface.device,
+            graph_resource
+                .bind_group_info
+                .get("player_transform")
+                .ok_or_else(|| anyhow!("Missing bind group layout: player_transform"))?,
+            entity_count,
+        );
+
+        let mut instances = InstanceSet::new();
+        instances.add(Instance::default());
+
+        let mut model = Model::new(
+            meshes,
+            materials,
+            entities,
+            root_entity,
+            skins,
+            transforms,
+            instances,
+            resource.animations,
+        );
+        model.cameras = resource.cameras;
+        model.lights = resource.lights;
+        model.upload(&app_surface.device, &app_surface.queue);
+
+        Ok(self.add_model(model))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        background::BackgroundModule,
+        engine::{
+            GRAPH_CONFIG_PATH, capture,
+            config::{
+                BindGroupConfig, BindGroupEntry, BindGroupEntryType, BindGroupVisibilty,
+                GraphConfig,
+            },
+            plugin::{Plugin, PluginContext},
+            texture::ColorSpace,
+        },
+        prop::PropModule,
+    };
+
+    const GOLDEN_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/res/golden");
+    // per channel, leaves room for rasterizer differences between adapters
+    const GOLDEN_TOLERANCE: u8 = 8;
+    const GOLDEN_MISMATCHED: f32 = 0.005;
+
+    // Compares `image` with `res/golden/<name>.png`, UPDATE_GOLDEN=1 writes the reference
+    // instead
+    fn assert_golden(name: &str, image: &image::RgbaImage) {
+        let path = std::path::Path::new(GOLDEN_DIR).join(format!("{name}.png"));
+        if std::env::var_os("UPDATE_GOLDEN").is_some() {
+            std::fs::create_dir_all(GOLDEN_DIR).unwrap();
+            image.save(&path).unwrap();
+            eprintln!("Wrote golden image {}", path.display());
+            return;
+        }
+        if !path.is_file() {
+            panic!(
+                "Missing golden image {}, run with UPDATE_GOLDEN=1 to write it",
+                path.display()
+            );
+        }
+
+        let expected = image::open(&path).unwrap().to_rgba8();
+        let diff = capture::compare_images(image, &expected, GOLDEN_TOLERANCE).unwrap();
+        if !diff.within(image, GOLDEN_MISMATCHED) {
+            let actual = std::env::temp_dir().join(format!("{name}.actual.png"));
+            image.save(&actual).unwrap();
+            panic!(
+                "{name} differs from {}: {diff:?}, rendered image at {}",
+                path.display(),
+                actual.display()
+            );
+        }
+    }
+
+    // 320x200 target. Without any adapter the test fails, or is skipped (`None`) when
+    // ALLOW_NO_GPU=1 is set.
+    fn offscreen_surface(rt: &tokio::runtime::Runtime) -> Option<AppSurface> {
+        // the software adapter gives the most stable images, any adapter will do otherwise
+        let app_surface = rt
+            .block_on(AppSurface::new_offscreen(320, 200, true))
+            .or_else(|_| rt.block_on(AppSurface::new_offscreen(320, 200, false)));
+        match app_surface {
+            Ok(app_surface) => Some(app_surface),
+            Err(err) if std::env::var_os("ALLOW_NO_GPU").is_some() => {
+                eprintln!("Skipping GPU test, no adapter: {err}");
+                None
+            }
+            Err(err) => panic!("No adapter for GPU tests, set ALLOW_NO_GPU=1 to skip: {err}"),
+        }
+    }
+
+    #[test]
+    fn render_golden_background() {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let Some(app_surface) = offscreen_surface(&rt) else {
+            return;
+        };
+
+        let graph_config = GraphConfig::new(GRAPH_CONFIG_PATH).unwrap();
+        let graph_resource = rt
+            .block_on(WgpuAppGraphResource::new(&app_surface, graph_config))
+            .unwrap();
+        let mut renderer = Renderer::new(&app_surface, &graph_resource);
+
+        let mut background = BackgroundModule::new();
+        background
+            .probe(&mut PluginContext {
+                app_surface: &app_surface,
+                graph_resource: &graph_resource,
+                renderer: &mut renderer,
+            })
+            .unwrap();
+
+        renderer.update(&app_surface, std::time::Duration::ZERO);
+        renderer.render(&app_surface, &graph_resource);
+        let image = capture::read_texture(
+            &app_surface.device,
+            &app_surface.queue,
+            app_surface.offscreen.as_ref().unwrap(),
+        )
+        .unwrap();
+
+        assert_golden("background", &image);
+    }
+
+    #[test]
+    fn material_without_maps() {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let Some(app_surface) = offscreen_surface(&rt) else {
+            return;
         };
+        let graph_config = GraphConfig::new(GRAPH_CONFIG_PATH).unwrap();
+        let graph_resource = rt
+            .block_on(WgpuAppGraphResource::new(&app_surface, graph_config))
+            .unwrap();
+        let renderer = Renderer::new(&app_surface, &graph_resource);
 
-        let model = Model::new(meshes, materials, entities, root_entity);
+        // a plain material still binds every entry of the glTF and OBJ groups
+        let material = resources::Material {
+            name: Some("plain".to_string()),
+            base_color_factor: [0.8, 0.2, 0.2, 1.0],
+            base_color_texture_index: None,
+            normal_texture_index: None,
+            metallic_factor: [0.0],
+            roughness_factor: [0.5],
+            metallic_roughness_texture_index: None,
+            occlusion_texture_index: None,
+            emissive_factor: [0.0; 3],
+            emissive_texture_index: None,
+            phong: None,
+        };
+        let bindgroups = &graph_resource.graph_config.resources.bindgroups;
+        for tag in [
+            "gltf_texture",
+            "player_gltf_texture",
+            "obj_texture",
+            "player_texture",
+        ] {
+            app_surface
+                .device
+                .push_error_scope(wgpu::ErrorFilter::Validation);
+            let mut bound = Material::new(
+                &material,
+                &MaterialTextures::new(),
+                &renderer.default_textures,
+                &app_surface.device,
+                graph_resource.bind_group_info.get(tag).unwrap(),
+                &bindgroups[tag],
+            )
+            .unwrap();
+            // the glTF groups take their factors from one uniform buffer
+            assert_eq!(bound.factors.is_some(), tag.contains("gltf"), "{tag}");
+            bound.set_roughness(&app_surface.queue, 0.25);
+            let error = rt.block_on(app_surface.device.pop_error_scope());
+            assert!(error.is_none(), "{tag}: {error:?}");
+            assert_eq!(bound.name.as_deref(), Some("plain"), "{tag}");
+            assert_eq!(bound.uniform.roughness, 0.25);
+        }
 
-        self.add_model(model);
+        let unknown = BindGroupConfig {
+            entries: vec![BindGroupEntry {
+                name: Some("albedo".to_string()),
+                binding: 0,
+                ty: BindGroupEntryType::Uniform,
+                visibility: BindGroupVisibilty::Fragment,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            }],
+        };
+        let err = Material::new(
+            &material,
+            &MaterialTextures::new(),
+            &renderer.default_textures,
+            &app_surface.device,
+            graph_resource.bind_group_info.get("gltf_texture").unwrap(),
+            &unknown,
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("albedo"), "{err}");
+    }
+
+    #[test]
+    fn render_pbr_prop() {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let Some(app_surface) = offscreen_surface(&rt) else {
+            return;
+        };
+        let graph_config = GraphConfig::new(GRAPH_CONFIG_PATH).unwrap();
+        let graph_resource = rt
+            .block_on(WgpuAppGraphResource::new(&app_surface, graph_config))
+            .unwrap();
+        let mut renderer = Renderer::new(&app_surface, &graph_resource);
+
+        app_surface
+            .device
+            .push_error_scope(wgpu::ErrorFilter::Validation);
+        let mut prop = PropModule::new();
+        prop.probe(&mut PluginContext {
+            app_surface: &app_surface,
+            graph_resource: &graph_resource,
+            renderer: &mut renderer,
+        })
+        .unwrap();
+        renderer.update(&app_surface, std::time::Duration::ZERO);
+        renderer.render(&app_surface, &graph_resource);
+        let error = rt.block_on(app_surface.device.pop_error_scope());
+        assert!(error.is_none(), "{error:?}");
+    }
+
+    #[test]
+    fn mipmap_pipeline_per_format() {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let Some(app_surface) = offscreen_surface(&rt) else {
+            return;
+        };
+        let mut samplers = SamplerCache::new();
+        let mut mipmaps = MipmapGenerator::new(&app_surface.device);
+
+        let image = image::RgbaImage::from_pixel(8, 8, image::Rgba([255, 0, 0, 255]));
+        for color_space in [ColorSpace::Srgb, ColorSpace::Srgb, ColorSpace::Linear] {
+            Texture::load_texture_from_image(
+                &app_surface.device,
+                &app_surface.queue,
+                None,
+                &image,
+                color_space,
+                &resources::TextureSampler::default(),
+                &mut samplers,
+                &mut mipmaps,
+            )
+            .unwrap();
+        }
+        assert_eq!(mipmaps.pipeline_count(), 2);
     }
 }
@@ -0,0 +1,134 @@
+use std::collections::HashMap;
+
+#[allow(unused)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Interpolation {
+    Linear,
+    Step,
+    CubicSpline,
+}
+
+impl From<gltf::animation::Interpolation> for Interpolation {
+    fn from(value: gltf::animation::Interpolation) -> Self {
+        match value {
+            gltf::animation::Interpolation::Linear => Self::Linear,
+            gltf::animation::Interpolation::Step => Self::Step,
+            gltf::animation::Interpolation::CubicSpline => Self::CubicSpline,
+        }
+    }
+}
+
+// For CUBICSPLINE every keyframe stores [in_tangent, value, out_tangent]
+#[allow(unused)]
+#[derive(Debug, Clone)]
+pub enum Outputs {
+    Translations(Vec<[f32; 3]>),
+    Rotations(Vec<[f32; 4]>),
+    Scales(Vec<[f32; 3]>),
+    MorphTargetWeights(Vec<f32>),
+}
+
+#[allow(unused)]
+#[derive(Debug, Clone)]
+pub struct Sampler {
+    pub interpolation: Interpolation,
+    pub inputs: Vec<f32>,
+    pub outputs: Outputs,
+}
+
+#[allow(unused)]
+#[derive(Debug, Clone)]
+pub struct Channel {
+    pub node: usize,
+    pub sampler: usize,
+}
+
+#[allow(unused)]
+#[derive(Debug, Clone)]
+pub struct Animation {
+    pub name: Option<String>,
+    pub channels: Vec<Channel>,
+    pub samplers: Vec<Sampler>,
+    pub duration: f32,
+}
+
+impl Animation {
+    pub fn parse(
+        animation: &gltf::animation::Animation,
+        buffers: &[Vec<u8>],
+    ) -> Result<Self, Box<dyn std::error::Error + 'static>> {
+        let name = animation.name().map(|val| val.to_string());
+
+        // only samplers a channel reads are kept, a channel whose sampler can't be read
+        // is skipped on its own
+        let mut samplers: Vec<Sampler> = Vec::new();
+        let mut sampler_indices: HashMap<usize, usize> = HashMap::new();
+        let mut channels = Vec::new();
+
+        for channel in animation.channels() {
+            let node = channel.target().node().index();
+            let gltf_index = channel.sampler().index();
+            let sampler = match sampler_indices.get(&gltf_index) {
+                Some(idx) => *idx,
+                None => match Self::read_sampler(&channel, buffers) {
+                    Ok(sampler) => {
+                        samplers.push(sampler);
+                        sampler_indices.insert(gltf_index, samplers.len() - 1);
+                        samplers.len() - 1
+                    }
+                    Err(err) => {
+                        eprintln!("Animation {name:?}: skipping channel of node {node}: {err}");
+                        continue;
+                    }
+                },
+            };
+            channels.push(Channel { node, sampler });
+        }
+
+        let duration = samplers
+            .iter()
+            .filter_map(|sampler| sampler.inputs.last().copied())
+            .fold(0.0, f32::max);
+
+        Ok(Self {
+            name,
+            channels,
+            samplers,
+            duration,
+        })
+    }
+
+    fn read_sampler(
+        channel: &gltf::animation::Channel,
+        buffers: &[Vec<u8>],
+    ) -> Result<Sampler, String> {
+        let reader = channel.reader(|buffer| Some(&buffers[buffer.index()]));
+
+        let inputs = reader
+            .read_inputs()
+            .map(|iter| iter.collect::<Vec<f32>>())
+            .ok_or_else(|| "No animation inputs.".to_string())?;
+
+        let outputs = match reader
+            .read_outputs()
+            .ok_or_else(|| "No animation outputs.".to_string())?
+        {
+            gltf::animation::util::ReadOutputs::Translations(iter) => {
+                Outputs::Translations(iter.collect())
+            }
+            gltf::animation::util::ReadOutputs::Rotations(iter) => {
+                Outputs::Rotations(iter.into_f32().collect())
+            }
+            gltf::animation::util::ReadOutputs::Scales(iter) => Outputs::Scales(iter.collect()),
+            gltf::animation::util::ReadOutputs::MorphTargetWeights(iter) => {
+                Outputs::MorphTargetWeights(iter.into_f32().collect())
+            }
+        };
+
+        Ok(Sampler {
+            interpolation: channel.sampler().interpolation().into(),
+            inputs,
+            outputs,
+        })
+    }
+}
@@ -0,0 +1,48 @@
+#[allow(unused)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Projection {
+    // yfov in radians, an infinite projection without zfar
+    Perspective {
+        aspect_ratio: Option<f32>,
+        yfov: f32,
+        znear: f32,
+        zfar: Option<f32>,
+    },
+    Orthographic {
+        xmag: f32,
+        ymag: f32,
+        znear: f32,
+        zfar: f32,
+    },
+}
+
+// Looks along -Z of the node it is attached to, +Y up
+#[allow(unused)]
+#[derive(Debug, Clone)]
+pub struct Camera {
+    pub name: Option<String>,
+    pub projection: Projection,
+}
+
+impl From<gltf::Camera<'_>> for Camera {
+    fn from(value: gltf::Camera) -> Self {
+        let name = value.name().map(|val| val.to_string());
+
+        let projection = match value.projection() {
+            gltf::camera::Projection::Perspective(perspective) => Projection::Perspective {
+                aspect_ratio: perspective.aspect_ratio(),
+                yfov: perspective.yfov(),
+                znear: perspective.znear(),
+                zfar: perspective.zfar(),
+            },
+            gltf::camera::Projection::Orthographic(orthographic) => Projection::Orthographic {
+                xmag: orthographic.xmag(),
+                ymag: orthographic.ymag(),
+                znear: orthographic.znear(),
+                zfar: orthographic.zfar(),
+            },
+        };
+
+        Self { name, projection }
+    }
+}
@@ -27,6 +27,14 @@ pub async fn load_string(file_name: &str) -> Result<String, Box<dyn Error + 'sta
     Ok(txt)
 }
     
+#[allow(unused)]
+#[cfg(not(target_arch = "wasm32"))]
+pub fn resource_path(file_name: &str) -> std::path::PathBuf {
+    std::path::Path::new(env!("OUT_DIR"))
+        .join("res")
+        .join(file_name)
+}
+
 #[allow(unused)]
 #[cfg(not(target_arch = "wasm32"))]
 pub fn load_string(file_name: &str) -> Result<String, Box<dyn Error + 'static>>
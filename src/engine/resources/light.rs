@@ -0,0 +1,49 @@
+#[allow(unused)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LightKind {
+    Directional,
+    Point,
+    // cone angles in radians
+    Spot {
+        inner_cone_angle: f32,
+        outer_cone_angle: f32,
+    },
+}
+
+// KHR_lights_punctual light, shines along -Z of the node it is attached to
+#[allow(unused)]
+#[derive(Debug, Clone)]
+pub struct Light {
+    pub name: Option<String>,
+    pub kind: LightKind,
+    pub color: [f32; 3],
+    // lux for directional lights, candela otherwise
+    pub intensity: f32,
+    pub range: Option<f32>,
+}
+
+impl From<gltf::khr_lights_punctual::Light<'_>> for Light {
+    fn from(value: gltf::khr_lights_punctual::Light) -> Self {
+        let name = value.name().map(|val| val.to_string());
+
+        let kind = match value.kind() {
+            gltf::khr_lights_punctual::Kind::Directional => LightKind::Directional,
+            gltf::khr_lights_punctual::Kind::Point => LightKind::Point,
+            gltf::khr_lights_punctual::Kind::Spot {
+                inner_cone_angle,
+                outer_cone_angle,
+            } => LightKind::Spot {
+                inner_cone_angle,
+                outer_cone_angle,
+            },
+        };
+
+        Self {
+            name,
+            kind,
+            color: value.color(),
+            intensity: value.intensity(),
+            range: value.range(),
+        }
+    }
+}
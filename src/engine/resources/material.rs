@@ -1,3 +1,20 @@
+// Phong parameters of OBJ/MTL materials, carried next to the PBR mapping
+#[allow(unused)]
+#[derive(Debug, Clone, Default)]
+pub struct Phong {
+    pub ambient: [f32; 3],
+    pub diffuse: [f32; 3],
+    pub specular: [f32; 3],
+    pub shininess: f32,
+    pub dissolve: f32,
+    pub optical_density: f32,
+    pub illumination_model: u32,
+    pub ambient_texture_index: Option<usize>,
+    pub specular_texture_index: Option<usize>,
+    pub shininess_texture_index: Option<usize>,
+    pub dissolve_texture_index: Option<usize>,
+}
+
 #[allow(unused)]
 pub struct Material {
     pub name: Option<String>,
@@ -10,6 +27,67 @@ pub struct Material {
     pub occlusion_texture_index: Option<usize>,
     pub emissive_factor: [f32; 3],
     pub emissive_texture_index: Option<usize>,
+    pub phong: Option<Phong>,
+}
+
+impl Material {
+    pub fn parse_obj(
+        material: &tobj::Material,
+        mut texture_index: impl FnMut(&str) -> Option<usize>,
+    ) -> Self {
+        let diffuse = material.diffuse.unwrap_or([0.8, 0.8, 0.8]);
+        let dissolve = material.dissolve.unwrap_or(1.0);
+        let shininess = material.shininess.unwrap_or(0.0);
+
+        let phong = Phong {
+            ambient: material.ambient.unwrap_or([1.0, 1.0, 1.0]),
+            diffuse,
+            specular: material.specular.unwrap_or([0.0, 0.0, 0.0]),
+            shininess,
+            dissolve,
+            optical_density: material.optical_density.unwrap_or(1.0),
+            illumination_model: material.illumination_model.unwrap_or(2) as u32,
+            ambient_texture_index: material
+                .ambient_texture
+                .as_deref()
+                .and_then(&mut texture_index),
+            specular_texture_index: material
+                .specular_texture
+                .as_deref()
+                .and_then(&mut texture_index),
+            shininess_texture_index: material
+                .shininess_texture
+                .as_deref()
+                .and_then(&mut texture_index),
+            dissolve_texture_index: material
+                .dissolve_texture
+                .as_deref()
+                .and_then(&mut texture_index),
+        };
+
+        // Blinn-Phong exponent to roughness
+        let roughness = (2.0 / (shininess + 2.0)).sqrt();
+
+        Self {
+            name: Some(material.name.clone()),
+            base_color_factor: [diffuse[0], diffuse[1], diffuse[2], dissolve],
+            base_color_texture_index: material
+                .diffuse_texture
+                .as_deref()
+                .and_then(&mut texture_index),
+            normal_texture_index: material
+                .normal_texture
+                .as_deref()
+                .and_then(&mut texture_index),
+            metallic_factor: [0.0],
+            roughness_factor: [roughness],
+            metallic_roughness_texture_index: None,
+            occlusion_texture_index: None,
+            emissive_factor: [0.0, 0.0, 0.0],
+            emissive_texture_index: None,
+            phong: Some(phong),
+        }
+    }
 }
 
 impl From<gltf::material::Material<'_>> for Material {
@@ -40,6 +118,7 @@ impl From<gltf::material::Material<'_>> for Material {
             occlusion_texture_index,
             emissive_factor,
             emissive_texture_index,
+            phong: None,
         }
     }
 }
@@ -18,6 +18,13 @@ impl Mesh {
 
         Ok(Self { name, primitives })
     }
+
+    pub fn parse_obj(model: &tobj::Model) -> Self {
+        Self {
+            name: Some(model.name.clone()),
+            primitives: vec![Primitive::parse_obj(&model.mesh)],
+        }
+    }
 }
 
 #[allow(unused)]
@@ -26,6 +33,8 @@ pub struct Primitive {
     pub positions: Vec<[f32; 3]>,
     pub tex_coords: Vec<[f32; 2]>,
     pub normals: Vec<[f32; 3]>,
+    pub joints: Option<Vec<[u32; 4]>>,
+    pub weights: Option<Vec<[f32; 4]>>,
     pub indices: Vec<u32>,
     pub material: usize,
 }
@@ -56,6 +65,17 @@ impl Primitive {
             .map(|iter| iter.collect::<Vec<[f32; 3]>>())
             .ok_or_else(|| format!("No normal."))?;
 
+        // Skinning attributes, only the first set is supported
+        let joints = reader.read_joints(0).map(|iter| {
+            iter.into_u16()
+                .map(|joint| joint.map(|val| val as u32))
+                .collect::<Vec<[u32; 4]>>()
+        });
+
+        let weights = reader
+            .read_weights(0)
+            .map(|iter| iter.into_f32().collect::<Vec<[f32; 4]>>());
+
         let indices = reader
             .read_indices()
             .map(|iter| iter.into_u32().collect::<Vec<u32>>())
@@ -67,8 +87,75 @@ impl Primitive {
             positions,
             tex_coords,
             normals,
+            joints,
+            weights,
             indices,
             material,
         })
     }
+
+    // Expects a mesh loaded with `single_index` and `triangulate`
+    pub fn parse_obj(mesh: &tobj::Mesh) -> Self {
+        let positions = mesh
+            .positions
+            .chunks_exact(3)
+            .map(|val| [val[0], val[1], val[2]])
+            .collect::<Vec<[f32; 3]>>();
+
+        // OBJ puts the texture origin at the bottom left
+        let tex_coords = if mesh.texcoords.is_empty() {
+            vec![[0.0, 0.0]; positions.len()]
+        } else {
+            mesh.texcoords
+                .chunks_exact(2)
+                .map(|val| [val[0], 1.0 - val[1]])
+                .collect::<Vec<[f32; 2]>>()
+        };
+
+        let normals = if mesh.normals.is_empty() {
+            Self::compute_normals(&positions, &mesh.indices)
+        } else {
+            mesh.normals
+                .chunks_exact(3)
+                .map(|val| [val[0], val[1], val[2]])
+                .collect::<Vec<[f32; 3]>>()
+        };
+
+        Self {
+            positions,
+            tex_coords,
+            normals,
+            joints: None,
+            weights: None,
+            indices: mesh.indices.clone(),
+            material: mesh.material_id.unwrap_or(0),
+        }
+    }
+
+    fn compute_normals(positions: &[[f32; 3]], indices: &[u32]) -> Vec<[f32; 3]> {
+        use cgmath::InnerSpace;
+
+        let mut normals = vec![cgmath::Vector3::new(0.0f32, 0.0, 0.0); positions.len()];
+        for face in indices.chunks_exact(3) {
+            let [a, b, c] = [face[0], face[1], face[2]].map(|idx| idx as usize);
+            let pa = cgmath::Vector3::from(positions[a]);
+            let pb = cgmath::Vector3::from(positions[b]);
+            let pc = cgmath::Vector3::from(positions[c]);
+            let normal = (pb - pa).cross(pc - pa);
+            normals[a] += normal;
+            normals[b] += normal;
+            normals[c] += normal;
+        }
+
+        normals
+            .into_iter()
+            .map(|normal| {
+                if normal.magnitude2() > 0.0 {
+                    normal.normalize().into()
+                } else {
+                    [0.0, 1.0, 0.0]
+                }
+            })
+            .collect()
+    }
 }
@@ -1,17 +1,27 @@
+mod animation;
+mod camera;
 mod file;
+mod light;
 mod material;
 mod mesh;
 mod node;
 mod scene;
+mod skin;
 mod texture;
 
-pub use material::Material;
+pub use animation::{Animation, Interpolation, Outputs, Sampler};
+pub use camera::{Camera, Projection};
+pub use file::{load_binary, load_string};
+pub use light::{Light, LightKind};
+pub use material::{Material, Phong};
 pub use mesh::{Mesh, Primitive};
 pub use node::Node;
+pub use skin::Skin;
+pub use texture::{Sampler as TextureSampler, Texture};
 
 use image::buffer::ConvertBuffer;
 
-use crate::engine::resources::{file::load_binary, scene::Scene, texture::Texture};
+use crate::engine::resources::{file::resource_path, scene::Scene};
 
 #[cfg(target_arch = "wasm32")]
 pub type Image = web_sys::ImageBitmap;
@@ -28,6 +38,10 @@ pub struct Resource {
     pub nodes: Vec<Node>,
     pub meshes: Vec<Mesh>,
     pub materials: Vec<Material>,
+    pub animations: Vec<Animation>,
+    pub skins: Vec<Skin>,
+    pub cameras: Vec<Camera>,
+    pub lights: Vec<Light>,
     pub textures: Vec<Texture>,
     pub buffers: Vec<Buffer>,
     pub images: Vec<Image>,
@@ -41,7 +55,16 @@ impl Resource {
     pub fn load_gltf(file_name: &str) -> Result<Self, Box<dyn std::error::Error + 'static>> {
         let binary = load_binary(file_name)?;
 
-        let (doc, buffers, images) = gltf::import_slice(binary)?;
+        // External buffers and images are resolved next to the glTF file
+        let gltf::Gltf {
+            document: doc,
+            blob,
+        } = gltf::Gltf::from_slice(&binary)?;
+        let base_dir = resource_path(file_name)
+            .parent()
+            .map(|dir| dir.to_path_buf());
+        let buffers = gltf::import_buffers(&doc, base_dir.as_deref(), blob)?;
+        let images = gltf::import_images(&doc, base_dir.as_deref(), &buffers)?;
         let buffers = buffers
             .into_iter()
             .map(|buffer| buffer.0)
@@ -55,7 +78,7 @@ impl Resource {
                         .ok_or_else(|| format!("Image convertion failed"))
                 }
                 gltf::image::Format::R8G8B8 => {
-                    if let Some(rgb) = image::RgbaImage::from_raw(img.width, img.height, img.pixels)
+                    if let Some(rgb) = image::RgbImage::from_raw(img.width, img.height, img.pixels)
                     {
                         Ok(rgb.convert())
                     } else {
@@ -82,6 +105,26 @@ impl Resource {
 
         let materials = doc.materials().map(|val| val.into()).collect::<Vec<_>>();
 
+        let mut animations = Vec::new();
+        for animation in doc.animations() {
+            match Animation::parse(&animation, &buffers) {
+                Ok(parsed) => animations.push(parsed),
+                Err(err) => eprintln!("Skipping animation {:?}: {err}", animation.name()),
+            }
+        }
+
+        let mut skins = Vec::new();
+        for skin in doc.skins() {
+            skins.push(Skin::parse(&skin, &buffers)?);
+        }
+
+        let cameras = doc.cameras().map(|val| val.into()).collect::<Vec<_>>();
+
+        let lights = doc
+            .lights()
+            .map(|lights| lights.map(|val| val.into()).collect::<Vec<_>>())
+            .unwrap_or_default();
+
         let textures = doc.textures().map(|val| val.into()).collect::<Vec<_>>();
 
         let default_scene_index = doc.default_scene().map(|sence| sence.index()).unwrap_or(0);
@@ -92,10 +135,251 @@ impl Resource {
             nodes,
             meshes,
             materials,
+            animations,
+            skins,
+            cameras,
+            lights,
             textures,
             buffers,
             images: imgs,
             default_scene_index,
         })
     }
+
+    pub fn load_obj(file_name: &str) -> Result<Self, Box<dyn std::error::Error + 'static>> {
+        let obj_text = load_string(file_name)?;
+        let base_dir = std::path::Path::new(file_name)
+            .parent()
+            .map(|dir| dir.to_path_buf())
+            .unwrap_or_default();
+
+        let (models, obj_materials) = tobj::load_obj_buf(
+            &mut std::io::BufReader::new(std::io::Cursor::new(obj_text)),
+            &tobj::LoadOptions {
+                single_index: true,
+                triangulate: true,
+                ..Default::default()
+            },
+            |path| {
+                let path = base_dir.join(path);
+                let mtl_text = load_string(&path.to_string_lossy())
+                    .map_err(|_| tobj::LoadError::OpenFileFailed)?;
+                tobj::load_mtl_buf(&mut std::io::BufReader::new(std::io::Cursor::new(mtl_text)))
+            },
+        )?;
+        let obj_materials = obj_materials?;
+
+        // Each texture file is loaded once and shared between materials
+        let mut images = Vec::new();
+        let mut textures = Vec::new();
+        let mut texture_names: Vec<String> = Vec::new();
+        let mut texture_index = |name: &str| {
+            if let Some(idx) = texture_names.iter().position(|val| val == name) {
+                return Some(idx);
+            }
+            let path = base_dir.join(name);
+            let binary = load_binary(&path.to_string_lossy()).ok()?;
+            let img = image::load_from_memory(&binary).ok()?.to_rgba8();
+
+            images.push(img);
+            textures.push(Texture::new(Some(name), images.len() - 1));
+            texture_names.push(name.to_string());
+            Some(textures.len() - 1)
+        };
+
+        let mut materials = obj_materials
+            .iter()
+            .map(|material| Material::parse_obj(material, &mut texture_index))
+            .collect::<Vec<_>>();
+        // meshes without a material point at material 0, the MTL defaults without a `.mtl`
+        if materials.is_empty() {
+            materials.push(Material::parse_obj(&tobj::Material::default(), &mut texture_index));
+        }
+
+        let meshes = models.iter().map(Mesh::parse_obj).collect::<Vec<_>>();
+
+        Ok(Self {
+            materials,
+            textures,
+            images,
+            ..Self::from_meshes(file_name, meshes)
+        })
+    }
+
+    // One node per mesh under a single scene, without materials
+    pub fn from_meshes(name: &str, meshes: Vec<Mesh>) -> Self {
+        let nodes = meshes
+            .iter()
+            .enumerate()
+            .map(|(idx, mesh)| Node::with_mesh(mesh.name.as_deref(), idx))
+            .collect::<Vec<_>>();
+
+        let scenes = vec![Scene::new(Some(name), (0..nodes.len()).collect())];
+
+        Self {
+            name: Some(name.to_string()),
+            scenes,
+            nodes,
+            meshes,
+            materials: Vec::new(),
+            animations: Vec::new(),
+            skins: Vec::new(),
+            cameras: Vec::new(),
+            lights: Vec::new(),
+            textures: Vec::new(),
+            buffers: Vec::new(),
+            images: Vec::new(),
+            default_scene_index: 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn load_obj_cube() {
+        let resource = Resource::load_obj("cube.obj").unwrap();
+        assert_eq!(resource.meshes.len(), resource.nodes.len());
+        assert_eq!(resource.materials.len(), 1);
+
+        let material = &resource.materials[0];
+        assert!(material.phong.is_some());
+        assert!(material.base_color_texture_index.is_some());
+        assert!(material.normal_texture_index.is_some());
+        assert_eq!(resource.textures.len(), resource.images.len());
+
+        let primitive = &resource.meshes[0].primitives[0];
+        assert_eq!(primitive.positions.len(), primitive.normals.len());
+        assert_eq!(primitive.indices.len() % 3, 0);
+    }
+
+    #[test]
+    fn load_obj_without_materials() {
+        let resource = Resource::load_obj("plane.obj").unwrap();
+        assert_eq!(resource.materials.len(), 1);
+        assert!(resource.materials[0].phong.is_some());
+        assert!(resource.textures.is_empty());
+
+        let primitive = &resource.meshes[0].primitives[0];
+        assert!(primitive.material < resource.materials.len());
+        assert_eq!(primitive.indices.len(), 6);
+    }
+
+    #[test]
+    fn load_gltf_with_external_buffer() {
+        let resource = Resource::load_gltf("player_skeleton_walk.gltf").unwrap();
+        assert_eq!(resource.skins.len(), 1);
+        assert!(
+            resource
+                .animations
+                .iter()
+                .any(|animation| animation.name.as_deref() == Some("ArmatureAction-walk"))
+        );
+        assert!(resource.meshes[0].primitives[0].joints.is_some());
+    }
+
+    #[test]
+    fn parse_animation_with_unused_sampler() {
+        // sampler 1 isn't read by any channel, the clip keeps its one channel
+        let json = r#"{
+            "asset": { "version": "2.0" },
+            "buffers": [{
+                "byteLength": 32,
+                "uri": "data:application/octet-stream;base64,AAAAAAAAgD8AAAAAAAAAAAAAAAAAAIA/AAAAQAAAQEA="
+            }],
+            "bufferViews": [
+                { "buffer": 0, "byteOffset": 0, "byteLength": 8 },
+                { "buffer": 0, "byteOffset": 8, "byteLength": 24 }
+            ],
+            "accessors": [
+                { "bufferView": 0, "componentType": 5126, "count": 2, "type": "SCALAR", "min": [0.0], "max": [1.0] },
+                { "bufferView": 1, "componentType": 5126, "count": 2, "type": "VEC3" }
+            ],
+            "nodes": [{ "name": "Box" }],
+            "scenes": [{ "nodes": [0] }],
+            "animations": [{
+                "name": "Slide",
+                "samplers": [
+                    { "input": 0, "output": 1 },
+                    { "input": 0, "output": 1, "interpolation": "STEP" }
+                ],
+                "channels": [{ "sampler": 0, "target": { "node": 0, "path": "translation" } }]
+            }]
+        }"#;
+        let doc = gltf::Gltf::from_slice(json.as_bytes()).unwrap().document;
+        let buffers = gltf::import_buffers(&doc, None, None)
+            .unwrap()
+            .into_iter()
+            .map(|buffer| buffer.0)
+            .collect::<Vec<Buffer>>();
+
+        let animation = Animation::parse(&doc.animations().next().unwrap(), &buffers).unwrap();
+        assert_eq!(animation.name.as_deref(), Some("Slide"));
+        assert_eq!(animation.channels.len(), 1);
+        assert_eq!(animation.samplers.len(), 1);
+        assert_eq!(animation.channels[0].sampler, 0);
+        assert_eq!(animation.duration, 1.0);
+    }
+
+    #[test]
+    fn parse_gltf_cameras_and_lights() {
+        let json = r#"{
+            "asset": { "version": "2.0" },
+            "extensionsUsed": ["KHR_lights_punctual"],
+            "extensions": {
+                "KHR_lights_punctual": {
+                    "lights": [{
+                        "name": "Lamp",
+                        "type": "spot",
+                        "color": [1.0, 0.5, 0.25],
+                        "intensity": 20.0,
+                        "range": 8.0,
+                        "spot": { "innerConeAngle": 0.1, "outerConeAngle": 0.5 }
+                    }]
+                }
+            },
+            "cameras": [{
+                "name": "View",
+                "type": "perspective",
+                "perspective": { "yfov": 0.8, "znear": 0.1 }
+            }],
+            "nodes": [
+                { "name": "Camera", "camera": 0, "translation": [0.0, 2.0, 10.0] },
+                { "name": "Light", "extensions": { "KHR_lights_punctual": { "light": 0 } } }
+            ],
+            "scenes": [{ "nodes": [0, 1] }]
+        }"#;
+        let doc = gltf::Gltf::from_slice(json.as_bytes()).unwrap().document;
+
+        let nodes = doc.nodes().map(Node::from).collect::<Vec<_>>();
+        assert_eq!(nodes[0].camera, Some(0));
+        assert_eq!(nodes[0].light, None);
+        assert_eq!(nodes[1].light, Some(0));
+
+        let camera = Camera::from(doc.cameras().next().unwrap());
+        assert_eq!(camera.name.as_deref(), Some("View"));
+        assert_eq!(
+            camera.projection,
+            Projection::Perspective {
+                aspect_ratio: None,
+                yfov: 0.8,
+                znear: 0.1,
+                zfar: None,
+            }
+        );
+
+        let light = Light::from(doc.lights().unwrap().next().unwrap());
+        assert_eq!(light.color, [1.0, 0.5, 0.25]);
+        assert_eq!(light.intensity, 20.0);
+        assert_eq!(light.range, Some(8.0));
+        assert_eq!(
+            light.kind,
+            LightKind::Spot {
+                inner_cone_angle: 0.1,
+                outer_cone_angle: 0.5,
+            }
+        );
+    }
 }
@@ -1,9 +1,33 @@
+#[allow(unused)]
 #[derive(Debug)]
 pub struct Node {
     pub name: Option<String>,
     pub mesh: Option<usize>,
+    pub skin: Option<usize>,
+    pub camera: Option<usize>,
+    pub light: Option<usize>,
     pub children: Vec<usize>,
     pub transform: cgmath::Matrix4<f32>,
+    pub translation: [f32; 3],
+    pub rotation: [f32; 4],
+    pub scale: [f32; 3],
+}
+
+impl Node {
+    pub fn with_mesh(name: Option<&str>, mesh: usize) -> Self {
+        Self {
+            name: name.map(|val| val.to_string()),
+            mesh: Some(mesh),
+            skin: None,
+            camera: None,
+            light: None,
+            children: Vec::new(),
+            transform: cgmath::SquareMatrix::identity(),
+            translation: [0.0, 0.0, 0.0],
+            rotation: [0.0, 0.0, 0.0, 1.0],
+            scale: [1.0, 1.0, 1.0],
+        }
+    }
 }
 
 impl From<gltf::scene::Node<'_>> for Node {
@@ -12,6 +36,12 @@ impl From<gltf::scene::Node<'_>> for Node {
 
         let mesh = value.mesh().map(|mesh| mesh.index());
 
+        let skin = value.skin().map(|skin| skin.index());
+
+        let camera = value.camera().map(|camera| camera.index());
+
+        let light = value.light().map(|light| light.index());
+
         let children = value
             .children()
             .map(|child| child.index())
@@ -20,11 +50,20 @@ impl From<gltf::scene::Node<'_>> for Node {
         let transform = value.transform().matrix();
         let transform = cgmath::Matrix4::from(transform);
 
+        // rotation: [x, y, z, w]
+        let (translation, rotation, scale) = value.transform().decomposed();
+
         Self {
             name,
             mesh,
+            skin,
+            camera,
+            light,
             children,
             transform,
+            translation,
+            rotation,
+            scale,
         }
     }
 }
@@ -5,6 +5,15 @@ pub struct Scene {
     pub nodes: Vec<usize>,
 }
 
+impl Scene {
+    pub fn new(name: Option<&str>, nodes: Vec<usize>) -> Self {
+        Self {
+            name: name.map(|val| val.to_string()),
+            nodes,
+        }
+    }
+}
+
 impl From<gltf::scene::Scene<'_>> for Scene {
     fn from(value: gltf::scene::Scene) -> Self {
         let name = value.name().map(|val| val.to_string());
@@ -0,0 +1,50 @@
+#[allow(unused)]
+#[derive(Debug)]
+pub struct Skin {
+    pub name: Option<String>,
+    pub joints: Vec<usize>,
+    pub inverse_bind_matrices: Vec<[[f32; 4]; 4]>,
+    pub skeleton: Option<usize>,
+}
+
+impl Skin {
+    pub fn parse(
+        skin: &gltf::skin::Skin,
+        buffers: &[Vec<u8>],
+    ) -> Result<Self, Box<dyn std::error::Error + 'static>> {
+        let name = skin.name().map(|val| val.to_string());
+        let joints = skin
+            .joints()
+            .map(|joint| joint.index())
+            .collect::<Vec<usize>>();
+
+        let reader = skin.reader(|buffer| Some(&buffers[buffer.index()]));
+
+        // Identity matrices are implied when the accessor is missing
+        let inverse_bind_matrices = reader
+            .read_inverse_bind_matrices()
+            .map(|iter| iter.collect::<Vec<[[f32; 4]; 4]>>())
+            .unwrap_or_else(|| {
+                let identity = [
+                    [1.0, 0.0, 0.0, 0.0],
+                    [0.0, 1.0, 0.0, 0.0],
+                    [0.0, 0.0, 1.0, 0.0],
+                    [0.0, 0.0, 0.0, 1.0],
+                ];
+                vec![identity; joints.len()]
+            });
+
+        if inverse_bind_matrices.len() < joints.len() {
+            return Err("Not enough inverse bind matrices.".into());
+        }
+
+        let skeleton = skin.skeleton().map(|node| node.index());
+
+        Ok(Self {
+            name,
+            joints,
+            inverse_bind_matrices,
+            skeleton,
+        })
+    }
+}
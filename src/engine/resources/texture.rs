@@ -1,7 +1,7 @@
 use gltf::texture::{MagFilter, MinFilter, WrappingMode};
 
 #[allow(unused)]
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Sampler {
     pub mag_filter: Option<MagFilter>,
     pub min_filter: Option<MinFilter>,
@@ -9,6 +9,18 @@ pub struct Sampler {
     pub wrap_t: WrappingMode,
 }
 
+// OBJ/MTL has no sampler description, textures are tiled
+impl Default for Sampler {
+    fn default() -> Self {
+        Sampler {
+            mag_filter: Some(MagFilter::Linear),
+            min_filter: Some(MinFilter::LinearMipmapLinear),
+            wrap_s: WrappingMode::Repeat,
+            wrap_t: WrappingMode::Repeat,
+        }
+    }
+}
+
 impl From<gltf::texture::Sampler<'_>> for Sampler {
     fn from(value: gltf::texture::Sampler<'_>) -> Self {
         Sampler {
@@ -28,10 +40,20 @@ pub struct Texture {
     pub sampler: Sampler,
 }
 
+impl Texture {
+    pub fn new(name: Option<&str>, image_index: usize) -> Self {
+        Self {
+            name: name.map(|val| val.to_string()),
+            image_index,
+            sampler: Sampler::default(),
+        }
+    }
+}
+
 impl From<gltf::texture::Texture<'_>> for Texture {
     fn from(value: gltf::texture::Texture<'_>) -> Self {
         let name = value.name().map(|val| val.to_string());
-    
+
         Self {
             name,
             image_index: value.source().index(),
@@ -1,18 +1,39 @@
-use crate::engine::{camera::Camera, model::Model};
+use std::collections::{BTreeMap, HashMap};
+
+use anyhow::anyhow;
+
+use crate::engine::{
+    camera::{Camera, CameraConfig},
+    light::{Light, LightId, LightSet},
+    model::{Model, PrimitiveRef},
+    render_pipeline::RenderPipelineInfo,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ModelId(u64);
 
 pub struct Scene {
     pub name: Option<String>,
-    pub models: Vec<Model>,
+    pub models: BTreeMap<ModelId, Model>,
     pub camera: Camera,
+    pub lights: LightSet,
+    // set at the start of every pass next to the camera, e.g. storage buffers
+    pub bind_groups: BTreeMap<u32, wgpu::BindGroup>,
+    next_model_id: u64,
 }
 
 #[allow(unused)]
 impl Scene {
-    pub fn new(camera: Camera) -> Self {
+    pub fn new(camera: Camera, lights: LightSet) -> Self {
+        let mut bind_groups = BTreeMap::new();
+        bind_groups.insert(LightSet::BIND_GROUP_INDEX, lights.bind_group().clone());
         Self {
             name: None,
-            models: Vec::new(),
+            models: BTreeMap::new(),
             camera,
+            lights,
+            bind_groups,
+            next_model_id: 0,
         }
     }
 
@@ -20,23 +41,302 @@ impl Scene {
         self.name.replace(name.to_string());
     }
 
-    pub fn add_model(&mut self, model: Model) {
-        self.models.push(model);
+    pub fn set_bind_group(&mut self, index: u32, bind_group: &wgpu::BindGroup) {
+        self.bind_groups.insert(index, bind_group.clone());
+    }
+
+    pub fn remove_bind_group(&mut self, index: u32) -> Option<wgpu::BindGroup> {
+        self.bind_groups.remove(&index)
+    }
+
+    pub fn add_model(&mut self, model: Model) -> ModelId {
+        let id = ModelId(self.next_model_id);
+        self.next_model_id += 1;
+        self.models.insert(id, model);
+        id
+    }
+
+    pub fn get_model(&self, id: ModelId) -> Option<&Model> {
+        self.models.get(&id)
+    }
+
+    pub fn get_model_mut(&mut self, id: ModelId) -> Option<&mut Model> {
+        self.models.get_mut(&id)
+    }
+
+    pub fn remove_model(&mut self, id: ModelId) -> Option<Model> {
+        self.models.remove(&id)
     }
 
     pub fn clear_model(&mut self) {
         self.models.clear();
     }
 
-    pub fn render<'a>(&self, render_pass: &mut wgpu::RenderPass<'a>) {
+    // Views the scene through a camera of a model, the first one when `name` is None
+    pub fn activate_camera(&mut self, id: ModelId, name: Option<&str>) -> anyhow::Result<()> {
+        let model = self
+            .models
+            .get(&id)
+            .ok_or_else(|| anyhow!("Unknown model {id:?}"))?;
+        let (entity, camera) = model
+            .camera_entities()
+            .into_iter()
+            .find(|(_, camera)| name.is_none() || camera.name.as_deref() == name)
+            .ok_or_else(|| anyhow!("Model {id:?} has no camera {name:?}"))?;
+
+        let aspect = self.camera.info.config().aspect;
+        let config = CameraConfig::from_node(camera, model.world_transform(entity), aspect);
+        self.camera.info.set_config(config);
+        Ok(())
+    }
+
+    // Adds the lights of a model at their current world transforms
+    pub fn spawn_lights(&mut self, id: ModelId) -> anyhow::Result<Vec<LightId>> {
+        let model = self
+            .models
+            .get(&id)
+            .ok_or_else(|| anyhow!("Unknown model {id:?}"))?;
+        let lights = model
+            .light_entities()
+            .into_iter()
+            .map(|(entity, light)| Light::from_node(light, model.world_transform(entity)))
+            .collect::<Vec<_>>();
+        Ok(lights
+            .into_iter()
+            .map(|light| self.lights.add(light))
+            .collect())
+    }
+
+    pub fn update(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        dt: std::time::Duration,
+    ) {
+        for model in self.models.values_mut() {
+            model.update(device, queue, dt);
+        }
+
+        self.camera.info.upload(queue);
+        if self.lights.upload(device, queue, self.camera.info.config()) {
+            self.bind_groups.insert(
+                LightSet::BIND_GROUP_INDEX,
+                self.lights.bind_group().clone(),
+            );
+        }
+    }
+
+    // Every draw of the scene in model order
+    fn draw_calls(&self) -> Vec<DrawCall<'_>> {
+        let scene_tag = self.name.as_deref().unwrap_or("default");
+
+        let mut draws = Vec::new();
+        for (&id, model) in self.models.iter() {
+            // not uploaded yet
+            if model.instances.is_empty() || model.instances.buffer().is_none() {
+                continue;
+            }
+            for primitive in model.primitives() {
+                draws.push(DrawCall {
+                    pipeline: model.pipeline_of(primitive.material).unwrap_or(scene_tag),
+                    model: id,
+                    primitive,
+                });
+            }
+        }
+        draws
+    }
+
+    // Draws of one frame, split into runs with and without depth and grouped by pipeline
+    // and material inside each run
+    pub fn draw_list<'a>(
+        &'a self,
+        render_pipeline_info: &RenderPipelineInfo,
+        color_format: wgpu::TextureFormat,
+    ) -> Vec<DrawPass<'a>> {
+        let mut passes: Vec<DrawPass<'a>> = Vec::new();
+        for draw in self.draw_calls() {
+            let Some(info) = render_pipeline_info.get_info(draw.pipeline) else {
+                continue;
+            };
+            if info.color_format != color_format {
+                continue;
+            }
+
+            match passes.last_mut() {
+                Some(pass) if pass.depth == info.depth => pass.draws.push(draw),
+                _ => passes.push(DrawPass {
+                    depth: info.depth,
+                    draws: vec![draw],
+                }),
+            }
+        }
+
+        for pass in passes.iter_mut() {
+            group_by_pipeline(&mut pass.draws);
+        }
+        passes
+    }
+
+    // Draws using one of `pipelines`, in the order of that list
+    pub fn draws(&self, pipelines: &[String]) -> Vec<DrawCall<'_>> {
+        let mut draws = self
+            .draw_calls()
+            .into_iter()
+            .filter_map(|draw| {
+                let order = pipelines.iter().position(|name| name == draw.pipeline)?;
+                Some((order, draw))
+            })
+            .collect::<Vec<_>>();
+        draws.sort_by_key(|(order, draw)| (*order, draw.model, draw.primitive.material));
+        draws.into_iter().map(|(_, draw)| draw).collect()
+    }
+
+    // Draws of the lit pipelines in `pipelines` switched to their depth only pipeline
+    pub fn shadow_draws<'a>(&'a self, pipelines: &'a HashMap<String, String>) -> Vec<DrawCall<'a>> {
+        let mut draws = self
+            .draw_calls()
+            .into_iter()
+            .filter_map(|draw| {
+                let shadow = pipelines.get(draw.pipeline)?;
+                Some(DrawCall {
+                    pipeline: shadow.as_str(),
+                    ..draw
+                })
+            })
+            .collect::<Vec<_>>();
+        group_by_pipeline(&mut draws);
+        draws
+    }
+
+    pub fn render<'a>(
+        &self,
+        render_pass: &mut wgpu::RenderPass<'a>,
+        render_pipeline_info: &RenderPipelineInfo,
+        draws: &[DrawCall],
+    ) {
         render_pass.set_bind_group(
             Camera::BIND_GROUP_INDEX,
             &self.camera.info.bind_group,
             &[],
         );
+        for (&index, bind_group) in self.bind_groups.iter() {
+            render_pass.set_bind_group(index, bind_group, &[]);
+        }
+        self.render_draws(render_pass, render_pipeline_info, draws);
+    }
+
+    // Draws without binding the camera and scene bind groups, e.g. from a light camera
+    pub fn render_draws<'a>(
+        &self,
+        render_pass: &mut wgpu::RenderPass<'a>,
+        render_pipeline_info: &RenderPipelineInfo,
+        draws: &[DrawCall],
+    ) {
+        // only rebind what changed since the previous draw
+        let mut pipeline = None;
+        let mut model_id = None;
+        let mut transform = None;
+        let mut material = None;
+        for draw in draws {
+            let Some(model) = self.models.get(&draw.model) else {
+                continue;
+            };
+
+            if pipeline != Some(draw.pipeline) {
+                let Some(render_pipeline) = render_pipeline_info.get(draw.pipeline) else {
+                    continue;
+                };
+                render_pass.set_pipeline(render_pipeline);
+                pipeline = Some(draw.pipeline);
+                transform = None;
+                material = None;
+            }
+            if model_id != Some(draw.model) {
+                model.bind_instances(render_pass);
+                model_id = Some(draw.model);
+            }
+            if transform != Some((draw.model, draw.primitive.entity)) {
+                model.bind_transform(draw.primitive.entity, render_pass);
+                transform = Some((draw.model, draw.primitive.entity));
+            }
+            if material != Some((draw.model, draw.primitive.material)) {
+                model.bind_material(draw.primitive.material, render_pass);
+                material = Some((draw.model, draw.primitive.material));
+            }
+
+            model.draw_primitive(&draw.primitive, render_pass);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct DrawCall<'a> {
+    pub pipeline: &'a str,
+    pub model: ModelId,
+    pub primitive: PrimitiveRef,
+}
+
+// Draws sharing one render pass
+#[derive(Debug)]
+pub struct DrawPass<'a> {
+    pub depth: bool,
+    pub draws: Vec<DrawCall<'a>>,
+}
+
+// Sorts by pipeline in the order each one is first submitted, then by model and material,
+// so blended pipelines stay after the opaque ones submitted before them
+fn group_by_pipeline(draws: &mut [DrawCall]) {
+    let mut order: Vec<&str> = Vec::new();
+    for draw in draws.iter() {
+        if !order.contains(&draw.pipeline) {
+            order.push(draw.pipeline);
+        }
+    }
+    draws.sort_by_key(|draw| {
+        let pipeline = order.iter().position(|name| *name == draw.pipeline);
+        (pipeline, draw.model, draw.primitive.material)
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
 
-        for model in self.models.iter() {
-            model.render(render_pass);
+    fn draw(pipeline: &str, model: u64, material: usize) -> DrawCall<'_> {
+        DrawCall {
+            pipeline,
+            model: ModelId(model),
+            primitive: PrimitiveRef {
+                entity: 0,
+                mesh: 0,
+                primitive: 0,
+                material,
+            },
         }
     }
+
+    #[test]
+    fn draws_keep_pipeline_submission_order() {
+        let mut draws = vec![
+            draw("z_opaque", 0, 1),
+            draw("a_blend", 1, 0),
+            draw("z_opaque", 2, 0),
+            draw("z_opaque", 0, 0),
+        ];
+        group_by_pipeline(&mut draws);
+        let order = draws
+            .iter()
+            .map(|draw| (draw.pipeline, draw.model.0, draw.primitive.material))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            order,
+            vec![
+                ("z_opaque", 0, 0),
+                ("z_opaque", 0, 1),
+                ("z_opaque", 2, 0),
+                ("a_blend", 1, 0),
+            ]
+        );
+    }
 }
@@ -1,6 +1,37 @@
-use std::{collections::HashMap, io::Read};
+use std::{collections::HashMap, io::Read, path::Path};
 
-use crate::engine::config::GraphConfig;
+use anyhow::{Context, anyhow};
+
+use crate::engine::config::{
+    BindGroupEntryType, BindGroupVisibilty, GraphConfig, VertexBufferLayoutFormat,
+};
+
+pub const SHADER_DIR: &str = "./shader";
+
+// A `@group/@binding` resource used by the reflected entry points
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReflectedBinding {
+    pub group: usize,
+    pub binding: usize,
+    pub name: Option<String>,
+    pub ty: BindGroupEntryType,
+    pub visibility: BindGroupVisibilty,
+}
+
+// A `@location` input of the vertex entry point
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReflectedVertexInput {
+    pub location: usize,
+    pub format: VertexBufferLayoutFormat,
+}
+
+#[derive(Debug, Default)]
+pub struct ShaderReflection {
+    pub bindings: Vec<ReflectedBinding>,
+    pub vertex_inputs: Vec<ReflectedVertexInput>,
+    // `@workgroup_size` of a compute entry point
+    pub workgroup_size: Option<[u32; 3]>,
+}
 
 #[allow(unused)]
 pub struct ShaderInfo {
@@ -19,7 +50,11 @@ impl ShaderInfo {
         }
     }
 
-    pub fn load_config(&mut self, device: &wgpu::Device, config: &GraphConfig) {
+    pub fn load_config(
+        &mut self,
+        device: &wgpu::Device,
+        config: &GraphConfig,
+    ) -> anyhow::Result<()> {
         for shader in &config.resources.shaders {
             // println!(
             //     "[Debug] {:?}({:?}) {:?}",
@@ -27,14 +62,16 @@ impl ShaderInfo {
             //     line!(),
             //     shader.1.filename
             // );
-            let module = Self::load_shader(
-                device,
-                Some(&shader.0),
-                &format!("./shader/{:}", shader.1.filename),
-            )
-            .unwrap();
+            let source = shader
+                .1
+                .read_source(Path::new(SHADER_DIR))
+                .with_context(|| {
+                    format!("Failed to load shader `{}` from {SHADER_DIR}", shader.0)
+                })?;
+            let module = Self::create_module(device, Some(shader.0), source);
             self.map.insert(shader.0.clone(), module);
         }
+        Ok(())
     }
 
     pub fn setup_vertex_shader(&mut self, device: &wgpu::Device, path: &str) -> anyhow::Result<()> {
@@ -53,6 +90,187 @@ impl ShaderInfo {
         Ok(())
     }
 
+    fn parse_module(source: &str) -> anyhow::Result<(naga::Module, naga::valid::ModuleInfo)> {
+        let module = naga::front::wgsl::parse_str(source)
+            .map_err(|err| anyhow!(err.emit_to_string(source)))?;
+        let info = naga::valid::Validator::new(
+            naga::valid::ValidationFlags::all(),
+            naga::valid::Capabilities::all(),
+        )
+        .validate(&module)
+        .map_err(|err| anyhow!(err.emit_to_string(source)))?;
+        Ok((module, info))
+    }
+
+    fn find_entry(
+        module: &naga::Module,
+        name: &str,
+        stage: naga::ShaderStage,
+    ) -> anyhow::Result<usize> {
+        module
+            .entry_points
+            .iter()
+            .position(|entry| entry.name == name && entry.stage == stage)
+            .ok_or_else(|| anyhow!("Missing {stage:?} entry point `{name}`"))
+    }
+
+    // Resources used by any of `entries`, visible to the stages listed with them
+    fn reflect_bindings(
+        module: &naga::Module,
+        info: &naga::valid::ModuleInfo,
+        entries: &[(usize, BindGroupVisibilty)],
+    ) -> anyhow::Result<Vec<ReflectedBinding>> {
+        let mut bindings = Vec::new();
+        for (handle, var) in module.global_variables.iter() {
+            let Some(resource) = var.binding.as_ref() else {
+                continue;
+            };
+
+            let used = entries
+                .iter()
+                .filter(|(entry, _)| !info.get_entry_point(*entry)[handle].is_empty())
+                .map(|(_, visibility)| visibility)
+                .collect::<Vec<_>>();
+            let visibility = match used.as_slice() {
+                // declared but never used, wgpu does not require it
+                [] => continue,
+                [visibility] => (*visibility).clone(),
+                _ => BindGroupVisibilty::ALL,
+            };
+
+            let ty = match (var.space, &module.types[var.ty].inner) {
+                (naga::AddressSpace::Uniform, _) => BindGroupEntryType::Uniform,
+                (naga::AddressSpace::Storage { access }, _) => {
+                    if access.contains(naga::StorageAccess::STORE) {
+                        BindGroupEntryType::Storage
+                    } else {
+                        BindGroupEntryType::StorageRo
+                    }
+                }
+                (
+                    naga::AddressSpace::Handle,
+                    naga::TypeInner::Image {
+                        class: naga::ImageClass::Depth { .. },
+                        arrayed,
+                        ..
+                    },
+                ) => {
+                    if *arrayed {
+                        BindGroupEntryType::DepthTextureArray
+                    } else {
+                        BindGroupEntryType::DepthTexture
+                    }
+                }
+                (naga::AddressSpace::Handle, naga::TypeInner::Image { .. }) => {
+                    BindGroupEntryType::Texture
+                }
+                (naga::AddressSpace::Handle, naga::TypeInner::Sampler { comparison }) => {
+                    if *comparison {
+                        BindGroupEntryType::ComparisonSampler
+                    } else {
+                        BindGroupEntryType::Sampler
+                    }
+                }
+                (space, inner) => {
+                    return Err(anyhow!(
+                        "Unsupported resource @group({}) @binding({}): {space:?} {inner:?}",
+                        resource.group,
+                        resource.binding
+                    ));
+                }
+            };
+
+            bindings.push(ReflectedBinding {
+                group: resource.group as usize,
+                binding: resource.binding as usize,
+                name: var.name.clone(),
+                ty,
+                visibility,
+            });
+        }
+        bindings.sort_by_key(|binding| (binding.group, binding.binding));
+        Ok(bindings)
+    }
+
+    pub fn reflect_compute(source: &str, entry: &str) -> anyhow::Result<ShaderReflection> {
+        let (module, info) = Self::parse_module(source)?;
+        let compute = Self::find_entry(&module, entry, naga::ShaderStage::Compute)?;
+        let bindings =
+            Self::reflect_bindings(&module, &info, &[(compute, BindGroupVisibilty::Compute)])?;
+
+        Ok(ShaderReflection {
+            bindings,
+            vertex_inputs: Vec::new(),
+            workgroup_size: Some(module.entry_points[compute].workgroup_size),
+        })
+    }
+
+    // An empty `fragment_entry` reflects a depth only shader
+    pub fn reflect(
+        source: &str,
+        vertex_entry: &str,
+        fragment_entry: &str,
+    ) -> anyhow::Result<ShaderReflection> {
+        let (module, info) = Self::parse_module(source)?;
+        let vertex = Self::find_entry(&module, vertex_entry, naga::ShaderStage::Vertex)?;
+        let mut entries = vec![(vertex, BindGroupVisibilty::Vertex)];
+        if !fragment_entry.is_empty() {
+            let fragment = Self::find_entry(&module, fragment_entry, naga::ShaderStage::Fragment)?;
+            entries.push((fragment, BindGroupVisibilty::Fragment));
+        }
+        let bindings = Self::reflect_bindings(&module, &info, &entries)?;
+
+        // Arguments are either bound directly or through the members of a struct
+        let mut inputs = Vec::new();
+        for argument in module.entry_points[vertex].function.arguments.iter() {
+            match &module.types[argument.ty].inner {
+                naga::TypeInner::Struct { members, .. } => {
+                    for member in members.iter() {
+                        inputs.push((member.binding.as_ref(), member.ty));
+                    }
+                }
+                _ => inputs.push((argument.binding.as_ref(), argument.ty)),
+            }
+        }
+
+        let mut vertex_inputs = Vec::new();
+        for (binding, ty) in inputs {
+            let Some(naga::Binding::Location { location, .. }) = binding else {
+                continue;
+            };
+            let format = Self::vertex_format(&module.types[ty].inner).ok_or_else(|| {
+                anyhow!("Unsupported vertex input format at @location({location})")
+            })?;
+            vertex_inputs.push(ReflectedVertexInput {
+                location: *location as usize,
+                format,
+            });
+        }
+        vertex_inputs.sort_by_key(|input| input.location);
+
+        Ok(ShaderReflection {
+            bindings,
+            vertex_inputs,
+            workgroup_size: None,
+        })
+    }
+
+    fn vertex_format(inner: &naga::TypeInner) -> Option<VertexBufferLayoutFormat> {
+        let (size, scalar) = match inner {
+            naga::TypeInner::Scalar(scalar) => (1, scalar),
+            naga::TypeInner::Vector { size, scalar } => (*size as u8, scalar),
+            _ => return None,
+        };
+        match (scalar.kind, scalar.width, size) {
+            (naga::ScalarKind::Float, 4, 1) => Some(VertexBufferLayoutFormat::Float32),
+            (naga::ScalarKind::Float, 4, 2) => Some(VertexBufferLayoutFormat::Float32x2),
+            (naga::ScalarKind::Float, 4, 3) => Some(VertexBufferLayoutFormat::Float32x3),
+            (naga::ScalarKind::Float, 4, 4) => Some(VertexBufferLayoutFormat::Float32x4),
+            (naga::ScalarKind::Uint, 4, 4) => Some(VertexBufferLayoutFormat::Uint32x4),
+            _ => None,
+        }
+    }
+
     fn load_shader(
         device: &wgpu::Device,
         label: Option<&str>,
@@ -62,9 +280,17 @@ impl ShaderInfo {
         let mut contents = String::new();
         file.read_to_string(&mut contents)?;
 
-        Ok(device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        Ok(Self::create_module(device, label, contents))
+    }
+
+    fn create_module(
+        device: &wgpu::Device,
+        label: Option<&str>,
+        source: String,
+    ) -> wgpu::ShaderModule {
+        device.create_shader_module(wgpu::ShaderModuleDescriptor {
             label,
-            source: wgpu::ShaderSource::Wgsl(contents.into()),
-        }))
+            source: wgpu::ShaderSource::Wgsl(source.into()),
+        })
     }
 }
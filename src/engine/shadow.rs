@@ -0,0 +1,411 @@
+use std::collections::HashMap;
+
+use cgmath::{EuclideanSpace, InnerSpace, SquareMatrix};
+
+use crate::engine::{
+    camera::{Camera, CameraConfig, Projection},
+    config::ShadowConfig,
+    light::{Light, LightKind},
+    render_pipeline::RenderPipelineInfo,
+    scene::Scene,
+};
+
+type Pos3 = cgmath::Point3<f32>;
+type Vec3 = cgmath::Vector3<f32>;
+type Vec4 = cgmath::Vector4<f32>;
+type Mat4 = cgmath::Matrix4<f32>;
+
+// cgmath projections map depth to -1..1, wgpu clips it to 0..1
+fn opengl_to_wgpu() -> Mat4 {
+    Mat4::from_translation(Vec3::new(0.0, 0.0, 0.5)) * Mat4::from_nonuniform_scale(1.0, 1.0, 0.5)
+}
+
+// One layer of the shadow map array and the light camera rendering into it
+#[allow(unused)]
+#[derive(Debug)]
+struct ShadowLayer {
+    view: wgpu::TextureView,
+    buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+}
+
+// Depth of the scene seen from the shadow casting lights: the cascades of the first
+// directional light followed by one layer per spot light, sampled by the lit shaders
+// through the `lights` bind group
+#[allow(unused)]
+#[derive(Debug)]
+pub struct ShadowMaps {
+    pub resolution: u32,
+    pub cascades: u32,
+    pub distance: f32,
+    pub spot_lights: u32,
+    texture: wgpu::Texture,
+    view: wgpu::TextureView,
+    sampler: wgpu::Sampler,
+    // `struct Shadows` in lighting.wgsl
+    buffer: wgpu::Buffer,
+    layers: Vec<ShadowLayer>,
+    // layers holding a light in the current frame
+    active: Vec<usize>,
+}
+
+#[allow(unused)]
+impl ShadowMaps {
+    pub const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+    // 0 splits the cascades uniformly, 1 logarithmically
+    const SPLIT_LAMBDA: f32 = 0.75;
+    const HEADER_LEN: usize = 12;
+    const MATRIX_LEN: usize = 16;
+    const CAMERA_LEN: usize = 20;
+    const SPOT_ZNEAR: f32 = 0.1;
+
+    pub fn new(
+        device: &wgpu::Device,
+        camera_layout: &wgpu::BindGroupLayout,
+        config: &ShadowConfig,
+    ) -> Self {
+        let cascades = config.cascades.clamp(1, ShadowConfig::MAX_CASCADES);
+        let layer_count = cascades + config.spot_lights;
+        let resolution = config.resolution.max(1);
+
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Shadow maps"),
+            size: wgpu::Extent3d {
+                width: resolution,
+                height: resolution,
+                depth_or_array_layers: layer_count,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: Self::FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            label: Some("Shadow maps"),
+            dimension: Some(wgpu::TextureViewDimension::D2Array),
+            ..Default::default()
+        });
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Shadow sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            compare: Some(wgpu::CompareFunction::LessEqual),
+            ..Default::default()
+        });
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Shadows buffer"),
+            size: (4 * (Self::HEADER_LEN + Self::MATRIX_LEN * layer_count as usize)) as u64,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let layers = (0..layer_count)
+            .map(|layer| {
+                let view = texture.create_view(&wgpu::TextureViewDescriptor {
+                    label: Some("Shadow map layer"),
+                    dimension: Some(wgpu::TextureViewDimension::D2),
+                    base_array_layer: layer,
+                    array_layer_count: Some(1),
+                    ..Default::default()
+                });
+                let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some("Shadow camera buffer"),
+                    size: (4 * Self::CAMERA_LEN) as u64,
+                    usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                    mapped_at_creation: false,
+                });
+                let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    label: Some("Shadow camera bind group"),
+                    layout: camera_layout,
+                    entries: &[wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: buffer.as_entire_binding(),
+                    }],
+                });
+                ShadowLayer {
+                    view,
+                    buffer,
+                    bind_group,
+                }
+            })
+            .collect();
+
+        Self {
+            resolution,
+            cascades,
+            distance: config.distance,
+            spot_lights: config.spot_lights,
+            texture,
+            view,
+            sampler,
+            buffer,
+            layers,
+            active: Vec::new(),
+        }
+    }
+
+    pub fn view(&self) -> &wgpu::TextureView {
+        &self.view
+    }
+
+    pub fn sampler(&self) -> &wgpu::Sampler {
+        &self.sampler
+    }
+
+    pub fn buffer(&self) -> &wgpu::Buffer {
+        &self.buffer
+    }
+
+    // First layer of every light, -1 for lights without shadows. Only the first
+    // directional light gets the cascades, spot lights get a layer each while they last.
+    pub fn assign_layers(lights: &[Light], cascades: u32, spot_lights: u32) -> Vec<i32> {
+        let mut directional = false;
+        let mut next_spot = 0;
+        lights
+            .iter()
+            .map(|light| {
+                if !light.cast_shadows {
+                    return -1;
+                }
+                match light.kind {
+                    LightKind::Directional if !directional => {
+                        directional = true;
+                        0
+                    }
+                    LightKind::Spot { .. } if next_spot < spot_lights => {
+                        next_spot += 1;
+                        (cascades + next_spot - 1) as i32
+                    }
+                    _ => -1,
+                }
+            })
+            .collect()
+    }
+
+    // Far distance of every cascade, the practical split scheme between znear and `distance`
+    pub fn cascade_splits(znear: f32, distance: f32, cascades: u32) -> [f32; 4] {
+        let znear = znear.max(1e-3);
+        let distance = distance.max(znear);
+        let mut splits = [distance; 4];
+        for (idx, split) in splits.iter_mut().enumerate().take(cascades as usize) {
+            let fraction = (idx + 1) as f32 / cascades as f32;
+            let uniform = znear + (distance - znear) * fraction;
+            let log = znear * (distance / znear).powf(fraction);
+            *split = Self::SPLIT_LAMBDA * log + (1.0 - Self::SPLIT_LAMBDA) * uniform;
+        }
+        splits
+    }
+
+    // Half width and height of the camera view at `depth`
+    fn view_extent(camera: &CameraConfig, depth: f32) -> (f32, f32) {
+        match camera.projection {
+            Projection::Perspective => {
+                let height = depth * (cgmath::Rad::from(cgmath::Deg(camera.fovy)).0 / 2.0).tan();
+                (height * camera.aspect, height)
+            }
+            Projection::Orthographic { xmag, ymag } => (xmag, ymag),
+        }
+    }
+
+    fn light_up(direction: Vec3) -> Vec3 {
+        if direction.y.abs() > 0.99 {
+            Vec3::unit_z()
+        } else {
+            Vec3::unit_y()
+        }
+    }
+
+    // Orthographic light camera around the bounding sphere of the view between `near` and
+    // `far`, moved in whole texels so the cascade does not shimmer when the camera moves
+    fn directional_matrix(
+        &self,
+        camera: &CameraConfig,
+        direction: Vec3,
+        near: f32,
+        far: f32,
+    ) -> Mat4 {
+        let forward = (camera.target - camera.eye).normalize();
+        let center_depth = (near + far) / 2.0;
+        let radius = [near, far]
+            .into_iter()
+            .map(|depth| {
+                let (width, height) = Self::view_extent(camera, depth);
+                ((depth - center_depth).powi(2) + width * width + height * height).sqrt()
+            })
+            .fold(0.0, f32::max)
+            .max(1e-3);
+
+        let direction = direction.normalize();
+        let rotation = Mat4::look_to_rh(Pos3::origin(), direction, Self::light_up(direction));
+        let center = camera.eye + forward * center_depth;
+        let texel = 2.0 * radius / self.resolution as f32;
+        let light_center = rotation * center.to_homogeneous();
+        let snapped = Vec4::new(
+            (light_center.x / texel).floor() * texel,
+            (light_center.y / texel).floor() * texel,
+            light_center.z,
+            1.0,
+        );
+        let center = Pos3::from_homogeneous(rotation.invert().unwrap() * snapped);
+
+        // casters up to `distance` behind the sphere still land in the map
+        let margin = self.distance;
+        let eye = center - direction * (radius + margin);
+        let view = Mat4::look_to_rh(eye, direction, Self::light_up(direction));
+        let projection =
+            cgmath::ortho(-radius, radius, -radius, radius, 0.0, 2.0 * radius + margin);
+        opengl_to_wgpu() * projection * view
+    }
+
+    fn spot_matrix(&self, light: &Light, outer_cone: f32) -> Mat4 {
+        let direction = light.direction.normalize();
+        let eye = Pos3::from_vec(light.position);
+        let view = Mat4::look_to_rh(eye, direction, Self::light_up(direction));
+        let fovy = cgmath::Rad((2.0 * outer_cone).clamp(0.01, 3.1));
+        let zfar = light
+            .range
+            .unwrap_or(self.distance)
+            .max(Self::SPOT_ZNEAR * 2.0);
+        let projection = cgmath::perspective(fovy, 1.0, Self::SPOT_ZNEAR, zfar);
+        opengl_to_wgpu() * projection * view
+    }
+
+    // Fits the light cameras to the view of `camera` for this frame
+    pub fn update(
+        &mut self,
+        queue: &wgpu::Queue,
+        camera: &CameraConfig,
+        lights: &[Light],
+        layers: &[i32],
+    ) {
+        let splits = Self::cascade_splits(camera.znear, self.distance, self.cascades);
+        let forward = (camera.target - camera.eye).normalize();
+
+        let mut matrices = vec![Mat4::identity(); self.layers.len()];
+        let mut positions = vec![Vec3::new(0.0, 0.0, 0.0); self.layers.len()];
+        self.active.clear();
+        for (light, &layer) in lights.iter().zip(layers.iter()) {
+            if layer < 0 {
+                continue;
+            }
+            let layer = layer as usize;
+            match light.kind {
+                LightKind::Directional => {
+                    for cascade in 0..self.cascades as usize {
+                        let near = if cascade == 0 {
+                            camera.znear
+                        } else {
+                            splits[cascade - 1]
+                        };
+                        matrices[layer + cascade] =
+                            self.directional_matrix(camera, light.direction, near, splits[cascade]);
+                        positions[layer + cascade] = camera.eye.to_vec();
+                        self.active.push(layer + cascade);
+                    }
+                }
+                LightKind::Spot { outer_cone, .. } => {
+                    matrices[layer] = self.spot_matrix(light, outer_cone);
+                    positions[layer] = light.position;
+                    self.active.push(layer);
+                }
+                LightKind::Point => {}
+            }
+        }
+
+        let mut data = vec![0.0f32; Self::HEADER_LEN];
+        data[..4].copy_from_slice(&[forward.x, forward.y, forward.z, 0.0]);
+        data[4..8].copy_from_slice(&splits);
+        data[8] = f32::from_bits(self.cascades);
+        for matrix in matrices.iter() {
+            data.extend_from_slice(AsRef::<[f32; 16]>::as_ref(matrix));
+        }
+        queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&data));
+
+        for &layer in self.active.iter() {
+            let position = positions[layer];
+            let mut camera = vec![position.x, position.y, position.z, 1.0];
+            camera.extend_from_slice(AsRef::<[f32; 16]>::as_ref(&matrices[layer]));
+            queue.write_buffer(&self.layers[layer].buffer, 0, bytemuck::cast_slice(&camera));
+        }
+    }
+
+    // Renders the models of every lit pipeline in `pipelines` with its depth only pipeline
+    // into the layers in use
+    pub fn render(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        scene: &Scene,
+        render_pipeline_info: &RenderPipelineInfo,
+        pipelines: &HashMap<String, String>,
+    ) {
+        if self.active.is_empty() {
+            return;
+        }
+        let draws = scene.shadow_draws(pipelines);
+
+        for &layer in self.active.iter() {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Shadow Pass"),
+                color_attachments: &[],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &self.layers[layer].view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(1.0),
+                        store: wgpu::StoreOp::Store,
+                    }),
+                    stencil_ops: None,
+                }),
+                ..Default::default()
+            });
+            render_pass.set_bind_group(
+                Camera::BIND_GROUP_INDEX,
+                &self.layers[layer].bind_group,
+                &[],
+            );
+            scene.render_draws(&mut render_pass, render_pipeline_info, &draws);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn cascade_splits_cover_distance() {
+        let splits = ShadowMaps::cascade_splits(0.1, 100.0, 3);
+        assert!(splits[0] > 0.1 && splits[0] < splits[1] && splits[1] < splits[2]);
+        assert!((splits[2] - 100.0).abs() < 1e-3);
+        assert_eq!(splits[3], 100.0);
+
+        let single = ShadowMaps::cascade_splits(0.1, 50.0, 1);
+        assert!((single[0] - 50.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn shadow_layers() {
+        let white = Vec3::new(1.0, 1.0, 1.0);
+        let origin = Vec3::new(0.0, 0.0, 0.0);
+        let down = Vec3::new(0.0, -1.0, 0.0);
+        let spot = Light::spot(origin, down, white, 1.0, None, 0.1, 0.5).with_shadows();
+        let lights = [
+            Light::point(origin, white, 1.0, None).with_shadows(),
+            spot,
+            Light::directional(down, white, 1.0).with_shadows(),
+            Light::directional(down, white, 1.0).with_shadows(),
+            Light::spot(origin, down, white, 1.0, None, 0.1, 0.5),
+            spot,
+            spot,
+        ];
+        assert_eq!(
+            ShadowMaps::assign_layers(&lights, 3, 2),
+            vec![-1, 3, 0, -1, -1, 4, -1]
+        );
+    }
+}
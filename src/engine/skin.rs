@@ -0,0 +1,89 @@
+use cgmath::SquareMatrix;
+use wgpu::util::DeviceExt;
+
+use crate::engine::{entity::Entity, resources};
+
+type Mat4 = cgmath::Matrix4<f32>;
+
+#[allow(unused)]
+#[derive(Debug)]
+pub struct Skin {
+    pub name: Option<String>,
+    pub joints: Vec<usize>,
+    pub inverse_bind_matrices: Vec<Mat4>,
+    pub buffer: wgpu::Buffer,
+    pub bind_group: wgpu::BindGroup,
+}
+
+#[allow(unused)]
+impl Skin {
+    // TODO: from config.pipeline
+    pub const BIND_GROUP_INDEX: u32 = 2;
+    // Keep in sync with `MAX_JOINTS` in shader/player_gltf_skinned.wgsl
+    pub const MAX_JOINTS: usize = 64;
+
+    pub fn new(
+        skin: &resources::Skin,
+        device: &wgpu::Device,
+        bind_group_layout: &wgpu::BindGroupLayout,
+    ) -> anyhow::Result<Self> {
+        if skin.joints.len() > Self::MAX_JOINTS {
+            return Err(anyhow::anyhow!(
+                "Skin {:?} has {} joints, at most {} are supported",
+                skin.name,
+                skin.joints.len(),
+                Self::MAX_JOINTS
+            ));
+        }
+
+        let inverse_bind_matrices = skin
+            .inverse_bind_matrices
+            .iter()
+            .map(|&mat| Mat4::from(mat))
+            .collect::<Vec<_>>();
+
+        let data = vec![[[0.0f32; 4]; 4]; Self::MAX_JOINTS];
+        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Joint matrices buffer"),
+            contents: bytemuck::cast_slice(&data),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Skin bind group"),
+            layout: bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: buffer.as_entire_binding(),
+            }],
+        });
+
+        Ok(Self {
+            name: skin.name.clone(),
+            joints: skin.joints.clone(),
+            inverse_bind_matrices,
+            buffer,
+            bind_group,
+        })
+    }
+
+    // The transform of the skinned node itself is ignored, joints carry the whole placement.
+    pub fn joint_matrices(&self, entities: &[Entity]) -> Vec<[[f32; 4]; 4]> {
+        self.joints
+            .iter()
+            .zip(self.inverse_bind_matrices.iter())
+            .map(|(&joint, inverse_bind)| {
+                let world = entities
+                    .get(joint)
+                    .map(|entity| entity.world_transform)
+                    .unwrap_or_else(Mat4::identity);
+                (world * inverse_bind).into()
+            })
+            .collect()
+    }
+
+    pub fn update(&self, queue: &wgpu::Queue, entities: &[Entity]) {
+        let matrices = self.joint_matrices(entities);
+        queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&matrices));
+    }
+}
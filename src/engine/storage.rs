@@ -0,0 +1,165 @@
+use std::borrow::Cow;
+
+use wgpu::util::DeviceExt;
+
+// Plain data array in a storage buffer, bound alone at `binding` of its bind group and
+// recreated with a larger size when a write does not fit
+#[allow(unused)]
+#[derive(Debug)]
+pub struct StorageBuffer {
+    label: String,
+    binding: u32,
+    layout: wgpu::BindGroupLayout,
+    buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+}
+
+#[allow(unused)]
+impl StorageBuffer {
+    // wgpu rejects zero sized bindings
+    const MIN_SIZE: u64 = 16;
+    const USAGE: wgpu::BufferUsages = wgpu::BufferUsages::STORAGE
+        .union(wgpu::BufferUsages::COPY_DST)
+        .union(wgpu::BufferUsages::COPY_SRC);
+
+    pub fn new(
+        device: &wgpu::Device,
+        bind_group_layout: &wgpu::BindGroupLayout,
+        binding: u32,
+        label: &str,
+        size: u64,
+    ) -> Self {
+        let buffer = Self::create_buffer(device, label, size);
+        Self::from_buffer(device, bind_group_layout, binding, label, buffer)
+    }
+
+    pub fn from_slice<T: bytemuck::Pod>(
+        device: &wgpu::Device,
+        bind_group_layout: &wgpu::BindGroupLayout,
+        binding: u32,
+        label: &str,
+        data: &[T],
+    ) -> Self {
+        let contents = Self::padded(bytemuck::cast_slice(data), Self::MIN_SIZE);
+        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(label),
+            contents: &contents,
+            usage: Self::USAGE,
+        });
+        Self::from_buffer(device, bind_group_layout, binding, label, buffer)
+    }
+
+    fn create_buffer(device: &wgpu::Device, label: &str, size: u64) -> wgpu::Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(label),
+            size: wgpu::util::align_to(size.max(Self::MIN_SIZE), wgpu::COPY_BUFFER_ALIGNMENT),
+            usage: Self::USAGE,
+            mapped_at_creation: false,
+        })
+    }
+
+    fn create_bind_group(
+        device: &wgpu::Device,
+        bind_group_layout: &wgpu::BindGroupLayout,
+        binding: u32,
+        label: &str,
+        buffer: &wgpu::Buffer,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some(label),
+            layout: bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding,
+                resource: buffer.as_entire_binding(),
+            }],
+        })
+    }
+
+    fn from_buffer(
+        device: &wgpu::Device,
+        bind_group_layout: &wgpu::BindGroupLayout,
+        binding: u32,
+        label: &str,
+        buffer: wgpu::Buffer,
+    ) -> Self {
+        let bind_group =
+            Self::create_bind_group(device, bind_group_layout, binding, label, &buffer);
+        Self {
+            label: label.to_string(),
+            binding,
+            layout: bind_group_layout.clone(),
+            buffer,
+            bind_group,
+        }
+    }
+
+    // Copies must be a multiple of COPY_BUFFER_ALIGNMENT
+    fn padded(contents: &[u8], min_size: u64) -> Cow<'_, [u8]> {
+        let size = wgpu::util::align_to(contents.len() as u64, wgpu::COPY_BUFFER_ALIGNMENT)
+            .max(min_size) as usize;
+        if contents.len() == size {
+            return Cow::Borrowed(contents);
+        }
+        let mut padded = contents.to_vec();
+        padded.resize(size, 0);
+        Cow::Owned(padded)
+    }
+
+    // Returns true when the buffer was recreated, bind groups taken before are stale then
+    pub fn write<T: bytemuck::Pod>(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        data: &[T],
+    ) -> bool {
+        let contents = Self::padded(bytemuck::cast_slice(data), 0);
+
+        let grown = contents.len() as u64 > self.buffer.size();
+        if grown {
+            let size = (contents.len() as u64).next_power_of_two();
+            self.buffer = Self::create_buffer(device, &self.label, size);
+            self.bind_group = Self::create_bind_group(
+                device,
+                &self.layout,
+                self.binding,
+                &self.label,
+                &self.buffer,
+            );
+        }
+
+        if !contents.is_empty() {
+            queue.write_buffer(&self.buffer, 0, &contents);
+        }
+        grown
+    }
+
+    pub fn buffer(&self) -> &wgpu::Buffer {
+        &self.buffer
+    }
+
+    pub fn bind_group(&self) -> &wgpu::BindGroup {
+        &self.bind_group
+    }
+
+    pub fn size(&self) -> u64 {
+        self.buffer.size()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn padding() {
+        assert_eq!(StorageBuffer::padded(&[1, 2, 3, 4], 0).len(), 4);
+        assert_eq!(
+            StorageBuffer::padded(&[1, 2, 3, 4, 5], 0).as_ref(),
+            &[1, 2, 3, 4, 5, 0, 0, 0]
+        );
+        assert_eq!(
+            StorageBuffer::padded(&[], StorageBuffer::MIN_SIZE).len(),
+            16
+        );
+    }
+}
@@ -1,19 +1,51 @@
-use anyhow::anyhow;
+use std::collections::HashMap;
+
+use gltf::texture::{MagFilter, MinFilter, WrappingMode};
+
+use crate::engine::{
+    mipmap::{self, MipmapGenerator},
+    resources,
+};
 
 pub struct TextureInfo {
     pub depth_texture: Option<Texture>,
+    // resolved into the surface when sample_count > 1
+    pub msaa_texture: Option<Texture>,
+    pub sample_count: u32,
 }
 
 impl TextureInfo {
     pub fn new() -> Self {
         Self {
             depth_texture: None,
+            msaa_texture: None,
+            sample_count: 1,
         }
     }
 
     pub fn setup(&mut self, device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) {
-        let depth_texture = Texture::create_depth_texture(device, config);
+        let depth_texture = Texture::create_depth_texture(device, config, self.sample_count);
         self.depth_texture.replace(depth_texture);
+
+        self.msaa_texture = (self.sample_count > 1)
+            .then(|| Texture::create_msaa_texture(device, config, self.sample_count));
+    }
+}
+
+// How the texels of an image are read: color maps are decoded from sRGB by the sampler,
+// data maps (normals, metallic-roughness, occlusion) are used as stored
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ColorSpace {
+    Srgb,
+    Linear,
+}
+
+impl ColorSpace {
+    pub fn format(self) -> wgpu::TextureFormat {
+        match self {
+            Self::Srgb => wgpu::TextureFormat::Rgba8UnormSrgb,
+            Self::Linear => wgpu::TextureFormat::Rgba8Unorm,
+        }
     }
 }
 
@@ -28,7 +60,9 @@ pub struct Texture {
 
 #[allow(unused)]
 impl Texture {
-    pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+    pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth24PlusStencil8;
+    // 1 disables anisotropic filtering
+    pub const MAX_ANISOTROPY: u16 = 16;
 
     fn single_pixel_bytes(format: wgpu::TextureFormat) -> u32 {
         use wgpu::TextureFormat;
@@ -65,69 +99,51 @@ impl Texture {
         }
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn from_image(
         device: &wgpu::Device,
         queue: &wgpu::Queue,
         label: Option<&str>,
         img: &image::DynamicImage,
+        color_space: ColorSpace,
+        sampler: &resources::TextureSampler,
+        samplers: &mut SamplerCache,
+        mipmaps: &mut MipmapGenerator,
     ) -> anyhow::Result<Self> {
-        let img = img.to_rgba8();
-        let dimension = img.dimensions();
-
-        let texture_size = wgpu::Extent3d {
-            width: dimension.0,
-            height: dimension.1,
-            depth_or_array_layers: 1,
-        };
-
-        let format = wgpu::TextureFormat::Rgba8UnormSrgb;
-        let texel = img.into_raw();
-        // let texel = img.as_bytes();
-
-        let texture = device.create_texture(&wgpu::wgt::TextureDescriptor {
+        Self::from_rgba(
+            device,
+            queue,
             label,
-            size: texture_size,
-            mip_level_count: 1,
-            sample_count: 1,
-            dimension: wgpu::TextureDimension::D2,
-            format,
-            usage: wgpu::TextureUsages::COPY_DST | wgpu::TextureUsages::TEXTURE_BINDING,
-            view_formats: &[],
-        });
+            &img.to_rgba8(),
+            color_space,
+            sampler,
+            samplers,
+            mipmaps,
+        )
+    }
 
+    // Every color texture is created here, `samplers` shares identical samplers
+    #[allow(clippy::too_many_arguments)]
+    pub fn from_rgba(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        label: Option<&str>,
+        img: &image::RgbaImage,
+        color_space: ColorSpace,
+        sampler: &resources::TextureSampler,
+        samplers: &mut SamplerCache,
+        mipmaps: &mut MipmapGenerator,
+    ) -> anyhow::Result<Self> {
+        let format = color_space.format();
+        let texture = Self::create_mipmapped(device, queue, label, img, format, mipmaps);
         println!("{label:?}: format: {:?}", texture.format());
-        let pixel_byte = Self::single_pixel_bytes(texture.format());
-
-        queue.write_texture(
-            wgpu::TexelCopyTextureInfo {
-                texture: &texture,
-                mip_level: 0,
-                origin: wgpu::Origin3d::ZERO,
-                aspect: wgpu::TextureAspect::All,
-            },
-            &texel,
-            wgpu::TexelCopyBufferLayout {
-                offset: 0,
-                bytes_per_row: Some(pixel_byte * texture_size.width),
-                rows_per_image: Some(texture_size.height),
-            },
-            texture_size,
-        );
 
         println!("[Debug] {:?}({:?})", file!(), line!());
         let texture_view = texture.create_view(&wgpu::wgt::TextureViewDescriptor {
             // format: Some(format.remove_srgb_suffix()),
             ..Default::default()
         });
-        let texture_sampler = device.create_sampler(&wgpu::wgt::SamplerDescriptor {
-            address_mode_u: wgpu::AddressMode::MirrorRepeat,
-            address_mode_v: wgpu::AddressMode::MirrorRepeat,
-            address_mode_w: wgpu::AddressMode::MirrorRepeat,
-            mag_filter: wgpu::FilterMode::Linear,
-            min_filter: wgpu::FilterMode::Nearest,
-            mipmap_filter: wgpu::FilterMode::Nearest,
-            ..Default::default()
-        });
+        let texture_sampler = samplers.get(device, sampler);
 
         Ok(Self {
             name: label.map(|val| val.to_string()),
@@ -137,19 +153,124 @@ impl Texture {
         })
     }
 
+    // Color texture with a full mip chain, filtered on the GPU when the format allows
+    fn create_mipmapped(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        label: Option<&str>,
+        img: &image::RgbaImage,
+        format: wgpu::TextureFormat,
+        mipmaps: &mut MipmapGenerator,
+    ) -> wgpu::Texture {
+        let (width, height) = img.dimensions();
+        let mip_level_count = mipmap::mip_level_count(width, height);
+        let renderable = mipmap::renderable(device, format);
+
+        let mut usage = wgpu::TextureUsages::COPY_DST | wgpu::TextureUsages::TEXTURE_BINDING;
+        if renderable {
+            usage |= wgpu::TextureUsages::RENDER_ATTACHMENT;
+        }
+        let texture = device.create_texture(&wgpu::wgt::TextureDescriptor {
+            label,
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage,
+            view_formats: &[],
+        });
+
+        let pixel_byte = Self::single_pixel_bytes(format);
+        let write_level = |level: u32, texel: &[u8]| {
+            let (width, height) = mipmap::mip_size(texture.width(), texture.height(), level);
+            queue.write_texture(
+                wgpu::TexelCopyTextureInfo {
+                    texture: &texture,
+                    mip_level: level,
+                    origin: wgpu::Origin3d::ZERO,
+                    aspect: wgpu::TextureAspect::All,
+                },
+                texel,
+                wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(pixel_byte * width),
+                    rows_per_image: Some(height),
+                },
+                wgpu::Extent3d {
+                    width,
+                    height,
+                    depth_or_array_layers: 1,
+                },
+            );
+        };
+
+        write_level(0, img.as_raw());
+        if renderable {
+            mipmaps.generate(device, queue, &texture);
+        } else {
+            for (idx, level) in mipmap::cpu_chain(img, mip_level_count).iter().enumerate() {
+                write_level(idx as u32 + 1, level.as_raw());
+            }
+        }
+        texture
+    }
+
+    #[allow(clippy::too_many_arguments)]
     pub fn load_texture_from_bytes(
         device: &wgpu::Device,
         queue: &wgpu::Queue,
         label: Option<&str>,
         bytes: &[u8],
+        color_space: ColorSpace,
+        sampler: &resources::TextureSampler,
+        samplers: &mut SamplerCache,
+        mipmaps: &mut MipmapGenerator,
     ) -> anyhow::Result<Self> {
         let img = image::load_from_memory(bytes)?;
-        Texture::from_image(device, queue, label, &img)
+        Texture::from_image(
+            device,
+            queue,
+            label,
+            &img,
+            color_space,
+            sampler,
+            samplers,
+            mipmaps,
+        )
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn load_texture_from_image(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        label: Option<&str>,
+        img: &image::RgbaImage,
+        color_space: ColorSpace,
+        sampler: &resources::TextureSampler,
+        samplers: &mut SamplerCache,
+        mipmaps: &mut MipmapGenerator,
+    ) -> anyhow::Result<Self> {
+        Texture::from_rgba(
+            device,
+            queue,
+            label,
+            img,
+            color_space,
+            sampler,
+            samplers,
+            mipmaps,
+        )
     }
 
     pub fn create_depth_texture(
         device: &wgpu::Device,
         config: &wgpu::SurfaceConfiguration,
+        sample_count: u32,
     ) -> Self {
         let texture_size = wgpu::Extent3d {
             width: config.width,
@@ -161,7 +282,7 @@ impl Texture {
             label: Some("depth texture"),
             size: texture_size,
             mip_level_count: 1,
-            sample_count: 1,
+            sample_count,
             dimension: wgpu::TextureDimension::D2,
             format: Self::DEPTH_FORMAT,
             usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
@@ -194,106 +315,257 @@ impl Texture {
         }
     }
 
-    pub fn load_texture_from_gltf(
+    // Attachment of the render graph that later passes can sample
+    pub fn create_render_target(
         device: &wgpu::Device,
-        queue: &wgpu::Queue,
-        label: Option<&str>,
-        texture: &gltf::Texture,
-        images: &Vec<gltf::image::Data>
-    ) -> anyhow::Result<Self> {
-        let img_idx = texture.source().index();
-        let sampler = texture.sampler();
-        let img_data = &images[img_idx];
-
-        if img_data.format != gltf::image::Format::R8G8B8A8 {
-            return Err(anyhow!("Not support image format!"));
-        }
-
-        let texture_size = wgpu::Extent3d {
-            width: img_data.width,
-            height: img_data.height,
-            depth_or_array_layers: 1,
-        };
-
-        let format = wgpu::TextureFormat::Rgba8UnormSrgb;
-        let texel = &img_data.pixels;
-
+        label: &str,
+        size: (u32, u32),
+        format: wgpu::TextureFormat,
+        sample_count: u32,
+    ) -> Self {
         let texture = device.create_texture(&wgpu::wgt::TextureDescriptor {
-            label,
-            size: texture_size,
+            label: Some(label),
+            size: wgpu::Extent3d {
+                width: size.0.max(1),
+                height: size.1.max(1),
+                depth_or_array_layers: 1,
+            },
             mip_level_count: 1,
-            sample_count: 1,
+            sample_count,
             dimension: wgpu::TextureDimension::D2,
             format,
-            usage: wgpu::TextureUsages::COPY_DST | wgpu::TextureUsages::TEXTURE_BINDING,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                | wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::COPY_SRC,
             view_formats: &[],
         });
+        let view = texture.create_view(&wgpu::wgt::TextureViewDescriptor::default());
+        let sampler = device.create_sampler(&wgpu::wgt::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
 
-        let pixel_byte = Self::single_pixel_bytes(texture.format());
+        Self {
+            name: Some(label.to_string()),
+            texture,
+            view,
+            sampler,
+        }
+    }
 
-        queue.write_texture(
-            wgpu::TexelCopyTextureInfo {
-                texture: &texture,
-                mip_level: 0,
-                origin: wgpu::Origin3d::ZERO,
-                aspect: wgpu::TextureAspect::All,
-            },
-            &texel,
-            wgpu::TexelCopyBufferLayout {
-                offset: 0,
-                bytes_per_row: Some(pixel_byte * texture_size.width),
-                rows_per_image: Some(texture_size.height),
+    pub fn create_msaa_texture(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        sample_count: u32,
+    ) -> Self {
+        let texture = device.create_texture(&wgpu::wgt::TextureDescriptor {
+            label: Some("msaa texture"),
+            size: wgpu::Extent3d {
+                width: config.width,
+                height: config.height,
+                depth_or_array_layers: 1,
             },
-            texture_size,
-        );
-
-        println!("[Debug] {:?}({:?})", file!(), line!());
-        let texture_view = texture.create_view(&wgpu::wgt::TextureViewDescriptor {
-            // format: Some(format.remove_srgb_suffix()),
-            ..Default::default()
+            mip_level_count: 1,
+            sample_count,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu_util::hal::color_format(config),
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
         });
+        let view = texture.create_view(&wgpu::wgt::TextureViewDescriptor::default());
+        let sampler = device.create_sampler(&wgpu::wgt::SamplerDescriptor::default());
 
-        let get_wrapping_mode = |gltf_mode: gltf::texture::WrappingMode| {
-            match gltf_mode {
-                gltf::texture::WrappingMode::Repeat => wgpu::AddressMode::MirrorRepeat,
-                gltf::texture::WrappingMode::MirroredRepeat => wgpu::AddressMode::MirrorRepeat,
-                gltf::texture::WrappingMode::ClampToEdge => wgpu::AddressMode::ClampToEdge,
-            }
-        };
+        Self {
+            name: Some("msaa texture".to_string()),
+            texture,
+            view,
+            sampler,
+        }
+    }
+}
 
-        let get_magfilter_mode = |gltf_mode: gltf::texture::MagFilter | {
-            match gltf_mode {
-                gltf::texture::MagFilter::Linear => wgpu::FilterMode::Linear,
-                gltf::texture::MagFilter::Nearest => wgpu::FilterMode::Nearest
-            }
+// Sampler state of a glTF sampler, the key of `SamplerCache`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SamplerKey {
+    pub address_mode_u: wgpu::AddressMode,
+    pub address_mode_v: wgpu::AddressMode,
+    pub mag_filter: wgpu::FilterMode,
+    pub min_filter: wgpu::FilterMode,
+    pub mipmap_filter: wgpu::FilterMode,
+    // false for the min filters without a mipmap part, only the base level is sampled
+    pub mipmapped: bool,
+    pub anisotropy_clamp: u16,
+}
+
+impl From<&resources::TextureSampler> for SamplerKey {
+    fn from(sampler: &resources::TextureSampler) -> Self {
+        let address_mode = |mode: WrappingMode| match mode {
+            WrappingMode::Repeat => wgpu::AddressMode::Repeat,
+            WrappingMode::MirroredRepeat => wgpu::AddressMode::MirrorRepeat,
+            WrappingMode::ClampToEdge => wgpu::AddressMode::ClampToEdge,
         };
 
-        let get_minfilter_mode = |gltf_mode: gltf::texture::MinFilter | {
-            match gltf_mode {
-                gltf::texture::MinFilter::Linear => (wgpu::FilterMode::Linear, wgpu::FilterMode::Linear),
-                gltf::texture::MinFilter::Nearest => (wgpu::FilterMode::Nearest, wgpu::FilterMode::Nearest),
-                gltf::texture::MinFilter::LinearMipmapLinear => (wgpu::FilterMode::Linear, wgpu::FilterMode::Linear),
-                gltf::texture::MinFilter::LinearMipmapNearest => (wgpu::FilterMode::Linear, wgpu::FilterMode::Nearest),
-                gltf::texture::MinFilter::NearestMipmapLinear => (wgpu::FilterMode::Nearest, wgpu::FilterMode::Linear),
-                gltf::texture::MinFilter::NearestMipmapNearest => (wgpu::FilterMode::Nearest, wgpu::FilterMode::Nearest),
-            }
+        let mag_filter = match sampler.mag_filter.unwrap_or(MagFilter::Linear) {
+            MagFilter::Linear => wgpu::FilterMode::Linear,
+            MagFilter::Nearest => wgpu::FilterMode::Nearest,
         };
 
-        let texture_sampler = device.create_sampler(&wgpu::wgt::SamplerDescriptor {
-            address_mode_u: get_wrapping_mode(sampler.wrap_s()),
-            address_mode_v: get_wrapping_mode(sampler.wrap_t()),
-            address_mode_w: wgpu::AddressMode::MirrorRepeat,
-            mag_filter: get_magfilter_mode(sampler.mag_filter().unwrap_or(gltf::texture::MagFilter::Linear)),
-            min_filter: get_minfilter_mode(sampler.min_filter().unwrap_or(gltf::texture::MinFilter::Nearest)).0,
-            mipmap_filter: get_minfilter_mode(sampler.min_filter().unwrap_or(gltf::texture::MinFilter::Nearest)).1,
-            ..Default::default()
-        });
+        use wgpu::FilterMode::{Linear, Nearest};
+        let (min_filter, mipmap_filter, mipmapped) =
+            match sampler.min_filter.unwrap_or(MinFilter::LinearMipmapLinear) {
+                MinFilter::Linear => (Linear, Nearest, false),
+                MinFilter::Nearest => (Nearest, Nearest, false),
+                MinFilter::LinearMipmapLinear => (Linear, Linear, true),
+                MinFilter::LinearMipmapNearest => (Linear, Nearest, true),
+                MinFilter::NearestMipmapLinear => (Nearest, Linear, true),
+                MinFilter::NearestMipmapNearest => (Nearest, Nearest, true),
+            };
+
+        // anisotropic filtering needs linear filtering throughout
+        let anisotropy_clamp =
+            if mag_filter == Linear && min_filter == Linear && mipmap_filter == Linear {
+                Texture::MAX_ANISOTROPY
+            } else {
+                1
+            };
 
+        Self {
+            address_mode_u: address_mode(sampler.wrap_s),
+            address_mode_v: address_mode(sampler.wrap_t),
+            mag_filter,
+            min_filter,
+            mipmap_filter,
+            mipmapped,
+            anisotropy_clamp,
+        }
+    }
+}
+
+// One `wgpu::Sampler` per distinct sampler state
+#[allow(unused)]
+#[derive(Debug, Default)]
+pub struct SamplerCache {
+    samplers: HashMap<SamplerKey, wgpu::Sampler>,
+}
+
+#[allow(unused)]
+impl SamplerCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(
+        &mut self,
+        device: &wgpu::Device,
+        sampler: &resources::TextureSampler,
+    ) -> wgpu::Sampler {
+        let key = SamplerKey::from(sampler);
+        self.samplers
+            .entry(key)
+            .or_insert_with(|| {
+                device.create_sampler(&wgpu::wgt::SamplerDescriptor {
+                    label: Some("Texture sampler"),
+                    address_mode_u: key.address_mode_u,
+                    address_mode_v: key.address_mode_v,
+                    address_mode_w: wgpu::AddressMode::ClampToEdge,
+                    mag_filter: key.mag_filter,
+                    min_filter: key.min_filter,
+                    mipmap_filter: key.mipmap_filter,
+                    lod_max_clamp: if key.mipmapped { 32.0 } else { 0.0 },
+                    anisotropy_clamp: key.anisotropy_clamp,
+                    ..Default::default()
+                })
+            })
+            .clone()
+    }
+
+    pub fn len(&self) -> usize {
+        self.samplers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samplers.is_empty()
+    }
+}
+
+// 1x1 textures bound in place of the maps a material doesn't have, sampled with the
+// default sampler. White reads the same in either color space.
+#[allow(unused)]
+#[derive(Debug)]
+pub struct DefaultTextures {
+    // neutral under the factors it's multiplied with
+    pub white: Texture,
+    // tangent space (0, 0, 1), the unperturbed surface normal
+    pub normal: Texture,
+}
+
+#[allow(unused)]
+impl DefaultTextures {
+    pub const WHITE: [u8; 4] = [255, 255, 255, 255];
+    pub const NORMAL: [u8; 4] = [128, 128, 255, 255];
+
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        samplers: &mut SamplerCache,
+        mipmaps: &mut MipmapGenerator,
+    ) -> anyhow::Result<Self> {
+        let mut texture = |label: &str, pixel: [u8; 4]| {
+            Texture::load_texture_from_image(
+                device,
+                queue,
+                Some(label),
+                &image::RgbaImage::from_pixel(1, 1, image::Rgba(pixel)),
+                ColorSpace::Linear,
+                &resources::TextureSampler::default(),
+                samplers,
+                mipmaps,
+            )
+        };
         Ok(Self {
-            name: label.map(|val| val.to_string()),
-            texture,
-            view: texture_view,
-            sampler: texture_sampler,
+            white: texture("Default white texture", Self::WHITE)?,
+            normal: texture("Default normal texture", Self::NORMAL)?,
         })
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn gltf_sampler_modes() {
+        let key = SamplerKey::from(&resources::TextureSampler::default());
+        assert_eq!(key.address_mode_u, wgpu::AddressMode::Repeat);
+        assert_eq!(key.mipmap_filter, wgpu::FilterMode::Linear);
+        assert!(key.mipmapped);
+        assert_eq!(key.anisotropy_clamp, Texture::MAX_ANISOTROPY);
+
+        let key = SamplerKey::from(&resources::TextureSampler {
+            mag_filter: Some(MagFilter::Nearest),
+            min_filter: Some(MinFilter::Linear),
+            wrap_s: WrappingMode::MirroredRepeat,
+            wrap_t: WrappingMode::ClampToEdge,
+        });
+        assert_eq!(key.address_mode_u, wgpu::AddressMode::MirrorRepeat);
+        assert_eq!(key.address_mode_v, wgpu::AddressMode::ClampToEdge);
+        assert_eq!(key.mag_filter, wgpu::FilterMode::Nearest);
+        assert_eq!(key.min_filter, wgpu::FilterMode::Linear);
+        assert!(!key.mipmapped);
+        assert_eq!(key.anisotropy_clamp, 1);
+
+        // unset filters fall back to trilinear
+        let key = SamplerKey::from(&resources::TextureSampler {
+            mag_filter: None,
+            min_filter: None,
+            wrap_s: WrappingMode::Repeat,
+            wrap_t: WrappingMode::Repeat,
+        });
+        assert_eq!(key, SamplerKey::from(&resources::TextureSampler::default()));
+    }
+}
@@ -0,0 +1,70 @@
+use crate::engine::entity::Entity;
+
+// One world matrix per entity in a single uniform buffer, selected with a dynamic offset
+#[allow(unused)]
+#[derive(Debug)]
+pub struct EntityTransforms {
+    pub buffer: wgpu::Buffer,
+    pub bind_group: wgpu::BindGroup,
+    stride: u64,
+    count: usize,
+}
+
+#[allow(unused)]
+impl EntityTransforms {
+    // TODO: from config.pipeline
+    pub const BIND_GROUP_INDEX: u32 = 2;
+    const MATRIX_SIZE: u64 = core::mem::size_of::<[[f32; 4]; 4]>() as u64;
+
+    pub fn new(
+        device: &wgpu::Device,
+        bind_group_layout: &wgpu::BindGroupLayout,
+        count: usize,
+    ) -> Self {
+        let alignment = device.limits().min_uniform_buffer_offset_alignment as u64;
+        let stride = wgpu::util::align_to(Self::MATRIX_SIZE, alignment);
+        let count = count.max(1);
+
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Entity transforms buffer"),
+            size: stride * count as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Entity transforms bind group"),
+            layout: bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                    buffer: &buffer,
+                    offset: 0,
+                    size: wgpu::BufferSize::new(Self::MATRIX_SIZE),
+                }),
+            }],
+        });
+
+        Self {
+            buffer,
+            bind_group,
+            stride,
+            count,
+        }
+    }
+
+    pub fn offset(&self, entity_index: usize) -> u32 {
+        (self.stride * entity_index as u64) as u32
+    }
+
+    pub fn update(&self, queue: &wgpu::Queue, entities: &[Entity]) {
+        let mut data = vec![0u8; (self.stride * self.count as u64) as usize];
+        for (idx, entity) in entities.iter().take(self.count).enumerate() {
+            let matrix: [[f32; 4]; 4] = entity.world_transform.into();
+            let start = idx * self.stride as usize;
+            data[start..start + Self::MATRIX_SIZE as usize]
+                .copy_from_slice(bytemuck::cast_slice(&matrix));
+        }
+        queue.write_buffer(&self.buffer, 0, &data);
+    }
+}
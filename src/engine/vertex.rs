@@ -60,6 +60,14 @@ impl VertexBufferInfo {
                         });
                         stride += core::mem::size_of::<[f32; 4]>() as u64;
                     }
+                    VertexBufferLayoutFormat::Uint32x4 => {
+                        attrs.push(wgpu::VertexAttribute {
+                            format: wgpu::VertexFormat::Uint32x4,
+                            offset: stride,
+                            shader_location: layout.location as u32,
+                        });
+                        stride += core::mem::size_of::<[u32; 4]>() as u64;
+                    }
                 }
             }
 
@@ -1,82 +1,66 @@
-use std::sync::Arc;
-
+use anyhow::anyhow;
 use cgmath::Rotation3;
 
-use crate::engine::{WgpuApp, instance::Instance, model::ModelAction, resource};
-
-// pub fn load_resource(app: Arc<std::sync::Mutex<WgpuApp>>) -> anyhow::Result<()> {
-//     let mut app = app.lock().unwrap();
-//     let texture_bind_group_layout = app
-//         .graph_resource
-//         .bind_group_info
-//         .get("player_gltf_texture")
-//         .unwrap();
-
-//     let obj_model = resource::load_gltf_model(
-//         &app.app_surface.device,
-//         &app.app_surface.queue,
-//         texture_bind_group_layout,
-//         "res/player_walk.gltf",
-//     )
-//     .unwrap();
+use crate::engine::{
+    instance::Instance,
+    plugin::{Plugin, PluginContext},
+    resources,
+    scene::ModelId,
+};
 
-//     let instances = vec![{
-//         let position = cgmath::vec3(0.0, 0.0, 0.0);
-//         let rotation =
-//             // cgmath::Quaternion::from_axis_angle(cgmath::vec3(1.0, 0.0, 0.0), cgmath::Deg(90.0));
-//             cgmath::Quaternion::from_axis_angle(cgmath::vec3(1.0, 0.0, 0.0), cgmath::Deg(0.0));
-//         let scale = 5.0;
+const PLAYER_MODEL_PATH: &str = "player_skeleton_walk.gltf";
+const PLAYER_WALK_ACTION: &str = "ArmatureAction-walk";
 
-//         Instance {
-//             position,
-//             rotation,
-//             scale,
-//         }
-//     }];
-
-//     let player_action = app.register_model_instances("player", obj_model, instances)?;
+pub struct PlayerModule {
+    model: Option<ModelId>,
+}
 
-//     player_action.insert("Sphere.002Action", 5, 0.0)?;
-//     player_action.insert("Sphere.003Action", 5, 0.0)?;
+impl PlayerModule {
+    pub fn new() -> Self {
+        Self { model: None }
+    }
+}
 
+impl Plugin for PlayerModule {
+    fn name(&self) -> &str {
+        "player"
+    }
 
-//     Ok(())
-// }
+    fn probe(&mut self, ctx: &mut PluginContext) -> anyhow::Result<()> {
+        let resource =
+            resources::Resource::load_gltf(PLAYER_MODEL_PATH).map_err(|err| anyhow!("{err}"))?;
 
+        let id = ctx.renderer.load_resources(
+            ctx.app_surface,
+            ctx.graph_resource,
+            resource,
+            Some("player_gltf_texture"),
+        )?;
 
-pub fn load_resource(app: Arc<std::sync::Mutex<WgpuApp>>) -> anyhow::Result<()> {
-    let mut app = app.lock().unwrap();
-    let texture_bind_group_layout = app
-        .graph_resource
-        .bind_group_info
-        .get("player_gltf_texture")
-        .unwrap();
+        let model = ctx
+            .renderer
+            .scene
+            .get_model_mut(id)
+            .ok_or_else(|| anyhow!("Player model was not added"))?;
 
-    let obj_model = resource::load_gltf_model(
-        &app.app_surface.device,
-        &app.app_surface.queue,
-        texture_bind_group_layout,
-        "res/player_skeleton_walk.gltf",
-    )
-    .unwrap();
+        model.set_pipeline("player_skinned");
 
-    let instances = vec![{
         let position = cgmath::vec3(0.0, 0.0, 0.0);
         let rotation =
-            // cgmath::Quaternion::from_axis_angle(cgmath::vec3(1.0, 0.0, 0.0), cgmath::Deg(90.0));
             cgmath::Quaternion::from_axis_angle(cgmath::vec3(1.0, 0.0, 0.0), cgmath::Deg(0.0));
-        let scale = 5.0;
-
-        Instance {
-            position,
-            rotation,
-            scale,
-        }
-    }];
+        model.instances.clear();
+        model.instances.add(Instance::new(position, rotation, 5.0));
+        model.upload(&ctx.app_surface.device, &ctx.app_surface.queue);
 
-    // let player_action = app.register_model_instances("player", obj_model, instances)?;
+        model.play_animation(PLAYER_WALK_ACTION, true)?;
 
-    // player_action.insert("ArmatureAction-walk", 5, 0.0)?;
+        self.model.replace(id);
+        Ok(())
+    }
 
-    Ok(())
+    fn teardown(&mut self, ctx: &mut PluginContext) {
+        if let Some(id) = self.model.take() {
+            ctx.renderer.scene.remove_model(id);
+        }
+    }
 }
@@ -0,0 +1,66 @@
+use anyhow::anyhow;
+use cgmath::Rotation3;
+
+use crate::engine::{
+    instance::Instance,
+    plugin::{Plugin, PluginContext},
+    resources,
+    scene::ModelId,
+};
+
+// A textured glTF drawn with the metallic-roughness `pbr` pipeline
+const PROP_MODEL_PATH: &str = "player_walk_texture/player_walk_texture.gltf";
+const PROP_BIND_GROUP: &str = "gltf_texture";
+
+pub struct PropModule {
+    model: Option<ModelId>,
+}
+
+impl PropModule {
+    pub fn new() -> Self {
+        Self { model: None }
+    }
+}
+
+impl Plugin for PropModule {
+    fn name(&self) -> &str {
+        "prop"
+    }
+
+    fn probe(&mut self, ctx: &mut PluginContext) -> anyhow::Result<()> {
+        let resource =
+            resources::Resource::load_gltf(PROP_MODEL_PATH).map_err(|err| anyhow!("{err}"))?;
+
+        let id = ctx.renderer.load_resources(
+            ctx.app_surface,
+            ctx.graph_resource,
+            resource,
+            Some(PROP_BIND_GROUP),
+        )?;
+
+        let model = ctx
+            .renderer
+            .scene
+            .get_model_mut(id)
+            .ok_or_else(|| anyhow!("Prop model was not added"))?;
+
+        model.set_pipeline("pbr");
+
+        // next to the item grid
+        let position = cgmath::vec3(20.0, 0.0, 0.0);
+        let rotation =
+            cgmath::Quaternion::from_axis_angle(cgmath::vec3(1.0, 0.0, 0.0), cgmath::Deg(0.0));
+        model.instances.clear();
+        model.instances.add(Instance::new(position, rotation, 3.0));
+        model.upload(&ctx.app_surface.device, &ctx.app_surface.queue);
+
+        self.model.replace(id);
+        Ok(())
+    }
+
+    fn teardown(&mut self, ctx: &mut PluginContext) {
+        if let Some(id) = self.model.take() {
+            ctx.renderer.scene.remove_model(id);
+        }
+    }
+}
@@ -6,12 +6,46 @@ const WINDOW_HEIGHT: usize = 800;
 
 #[allow(unused)]
 pub struct AppSurface {
-    pub window: Arc<Window>,
-    pub surface: wgpu::Surface<'static>, // surface should exist whole time.
+    pub window: Option<Arc<Window>>,
+    pub surface: Option<wgpu::Surface<'static>>, // surface should exist whole time.
     pub device: wgpu::Device,
     pub adapter: wgpu::Adapter,
     pub queue: wgpu::Queue,
     pub config: wgpu::SurfaceConfiguration,
+    // color target standing in for the surface, see `new_offscreen`
+    pub offscreen: Option<wgpu::Texture>,
+}
+
+// Format pipelines render the surface with, its sRGB view when it has one
+pub fn color_format(config: &wgpu::SurfaceConfiguration) -> wgpu::TextureFormat {
+    let srgb = config.format.add_srgb_suffix();
+    if config.format == srgb || config.view_formats.contains(&srgb) {
+        srgb
+    } else {
+        config.format
+    }
+}
+
+// Color target of one frame
+pub enum Frame {
+    Surface(wgpu::SurfaceTexture),
+    Offscreen(wgpu::Texture),
+}
+
+#[allow(unused)]
+impl Frame {
+    pub fn texture(&self) -> &wgpu::Texture {
+        match self {
+            Self::Surface(frame) => &frame.texture,
+            Self::Offscreen(texture) => texture,
+        }
+    }
+
+    pub fn present(self) {
+        if let Self::Surface(frame) = self {
+            frame.present();
+        }
+    }
 }
 
 #[allow(unused)]
@@ -36,34 +70,148 @@ impl AppSurface {
                 compatible_surface: Some(&surface),
             })
             .await?;
-
-        let (device, queue) = adapter
-            .request_device(&wgpu::wgt::DeviceDescriptor {
-                label: Some("App Gpu device"),
-                required_features: wgpu::Features::VERTEX_WRITABLE_STORAGE,
-                required_limits: wgpu::Limits::defaults(),
-                memory_hints: wgpu::MemoryHints::Performance,
-                trace: wgpu::Trace::Off,
-            })
-            .await?;
+        let (device, queue) = Self::request_device(&adapter).await?;
 
         let mut size = window.inner_size();
         size.width = size.width.max(1);
         size.height = size.height.max(1);
 
-        let config = surface
+        let mut config = surface
             .get_default_config(&adapter, size.width, size.height)
             .unwrap();
+        // pipelines render in sRGB: to the surface itself when it has an sRGB format,
+        // otherwise to an sRGB view of it where the adapter can create one
+        let capabilities = surface.get_capabilities(&adapter);
+        if let Some(format) = capabilities.formats.iter().find(|format| format.is_srgb()) {
+            config.format = *format;
+        } else if adapter
+            .get_downlevel_capabilities()
+            .flags
+            .contains(wgpu::DownlevelFlags::VIEW_FORMATS)
+            && config.format != config.format.add_srgb_suffix()
+        {
+            config.view_formats.push(config.format.add_srgb_suffix());
+        }
+        // frames can be read back for screenshots
+        if capabilities.usages.contains(wgpu::TextureUsages::COPY_SRC) {
+            config.usage |= wgpu::TextureUsages::COPY_SRC;
+        }
 
         surface.configure(&device, &config);
 
         Ok(Self {
-            window,
-            surface,
+            window: Some(window),
+            surface: Some(surface),
+            device,
+            adapter,
+            queue,
+            config,
+            offscreen: None,
+        })
+    }
+
+    // Renders into a texture instead of a window, e.g. for tests without a display.
+    // `force_fallback_adapter` picks the software adapter when there is one.
+    pub async fn new_offscreen(
+        width: u32,
+        height: u32,
+        force_fallback_adapter: bool,
+    ) -> anyhow::Result<Self> {
+        let gpu = wgpu::Instance::new(&wgpu::InstanceDescriptor {
+            backends: wgpu::Backends::all(),
+            ..Default::default()
+        });
+
+        let adapter = gpu
+            .request_adapter(&wgpu::RequestAdapterOptions {
+                power_preference: wgpu::PowerPreference::default(),
+                force_fallback_adapter,
+                compatible_surface: None,
+            })
+            .await?;
+        let (device, queue) = Self::request_device(&adapter).await?;
+
+        // already sRGB, downlevel adapters can't reinterpret formats through views
+        let format = wgpu::TextureFormat::Rgba8UnormSrgb;
+        let config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            format,
+            width: width.max(1),
+            height: height.max(1),
+            present_mode: wgpu::PresentMode::AutoVsync,
+            desired_maximum_frame_latency: 2,
+            alpha_mode: wgpu::CompositeAlphaMode::Opaque,
+            view_formats: Vec::new(),
+        };
+        let offscreen = Self::create_offscreen(&device, &config);
+
+        Ok(Self {
+            window: None,
+            surface: None,
             device,
             adapter,
             queue,
             config,
+            offscreen: Some(offscreen),
+        })
+    }
+
+    async fn request_device(
+        adapter: &wgpu::Adapter,
+    ) -> anyhow::Result<(wgpu::Device, wgpu::Queue)> {
+        // wireframe and point polygon modes when the adapter has them
+        let optional_features = adapter.features()
+            & (wgpu::Features::POLYGON_MODE_LINE | wgpu::Features::POLYGON_MODE_POINT);
+
+        let device = adapter
+            .request_device(&wgpu::wgt::DeviceDescriptor {
+                label: Some("App Gpu device"),
+                required_features: wgpu::Features::VERTEX_WRITABLE_STORAGE | optional_features,
+                required_limits: wgpu::Limits::defaults(),
+                memory_hints: wgpu::MemoryHints::Performance,
+                trace: wgpu::Trace::Off,
+            })
+            .await?;
+        Ok(device)
+    }
+
+    fn create_offscreen(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+    ) -> wgpu::Texture {
+        device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Offscreen frame"),
+            size: wgpu::Extent3d {
+                width: config.width,
+                height: config.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: config.format,
+            usage: config.usage,
+            view_formats: &config.view_formats,
         })
     }
+
+    // Reconfigures the surface, or recreates the offscreen target, at the new size
+    pub fn resize(&mut self, width: u32, height: u32) {
+        self.config.width = width.max(1);
+        self.config.height = height.max(1);
+        if let Some(surface) = self.surface.as_ref() {
+            surface.configure(&self.device, &self.config);
+        }
+        if self.offscreen.is_some() {
+            self.offscreen = Some(Self::create_offscreen(&self.device, &self.config));
+        }
+    }
+
+    pub fn get_current_frame(&self) -> Result<Frame, wgpu::SurfaceError> {
+        match (self.surface.as_ref(), self.offscreen.as_ref()) {
+            (Some(surface), _) => surface.get_current_texture().map(Frame::Surface),
+            (None, Some(texture)) => Ok(Frame::Offscreen(texture.clone())),
+            (None, None) => Err(wgpu::SurfaceError::Lost),
+        }
+    }
 }